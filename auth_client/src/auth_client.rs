@@ -4,7 +4,7 @@ use client_listener::ConnectionId;
 use std::{
     env::current_exe,
     io,
-    net::IpAddr,
+    net::{IpAddr, SocketAddr},
     os::unix::{
         io::{FromRawFd, IntoRawFd, RawFd},
         process::CommandExt,
@@ -27,6 +27,7 @@ pub struct AuthClient {
     comm_task_shutdown: oneshot::Sender<()>,
     comm_task: task::JoinHandle<CommResult>,
     child_process: Option<Child>,
+    child_pid: Option<u32>,
 }
 
 /// Opaque saved-state to reconstitute an AuthClient after an upgrade
@@ -34,6 +35,38 @@ pub struct AuthClient {
 pub struct AuthClientState {
     control_fd: RawFd,
     event_fd: RawFd,
+    /// Not recorded by versions before [`IPC_VERSION`](crate::IPC_VERSION) was introduced
+    #[serde(default)]
+    child_pid: Option<u32>,
+    /// The [`IPC_VERSION`](crate::IPC_VERSION) spoken by the worker process. State
+    /// saved before this was recorded has version zero.
+    #[serde(default)]
+    ipc_version: u32,
+}
+
+impl AuthClientState {
+    /// Whether the worker process described by this state speaks the same IPC protocol
+    /// as this build, and so can be [resumed](AuthClient::resume)
+    pub fn is_compatible(&self) -> bool {
+        self.ipc_version == crate::IPC_VERSION
+    }
+
+    /// Stop the worker process without communicating with it. Closing our ends of its
+    /// IPC channels is enough to make it exit; if its process ID is known, it is also
+    /// signalled and waited for.
+    fn terminate_worker(&self) {
+        unsafe {
+            libc::close(self.control_fd);
+            libc::close(self.event_fd);
+
+            if let Some(pid) = self.child_pid {
+                let pid = pid as libc::pid_t;
+                if libc::kill(pid, libc::SIGTERM) == 0 {
+                    libc::waitpid(pid, std::ptr::null_mut(), 0);
+                }
+            }
+        }
+    }
 }
 
 type CommResult = io::Result<(IpcSender<ControlMessage>, IpcReceiver<AuthEvent>)>;
@@ -167,6 +200,7 @@ impl AuthClient {
             control_sender: local_control_send,
             comm_task_shutdown: shutdown_send,
             comm_task,
+            child_pid: Some(child.id()),
             child_process: Some(child),
         };
 
@@ -195,6 +229,46 @@ impl AuthClient {
             .ok();
     }
 
+    /// Begin an ident (RFC 1413) lookup for the connection between `local` and `remote`.
+    /// The connection ID is used to identify the resulting `IdentResult` when the operation
+    /// completes.
+    ///
+    /// The query is made to port 113 on the remote host, from the same local address as the
+    /// client connection. If the remote host doesn't respond within a few seconds, returns
+    /// an error, or doesn't return a valid username, the result will be None.
+    #[tracing::instrument(skip(self))]
+    pub fn start_ident_lookup(&self, conn_id: ConnectionId, local: SocketAddr, remote: SocketAddr) {
+        self.control_sender
+            .send(ControlMessage::StartIdentLookup(conn_id, local, remote))
+            .ok();
+    }
+
+    /// Set the DNS blocklists against which [`start_dnsbl_lookup`](Self::start_dnsbl_lookup)
+    /// checks addresses. This replaces any previous configuration.
+    ///
+    /// The worker process outlives upgrades of the consumer, so this should be called
+    /// again after [`resume`](Self::resume) to pick up any configuration change.
+    #[tracing::instrument(skip(self))]
+    pub fn configure_dnsbl(&self, settings: DnsblSettings) {
+        self.control_sender
+            .send(ControlMessage::ConfigureDnsbl(settings))
+            .ok();
+    }
+
+    /// Begin checking the given IP address against the configured DNS blocklists. The
+    /// connection ID is used to identify the resulting `DnsblResult` when the operation
+    /// completes.
+    ///
+    /// Every zone is queried, and the result lists each zone in which the address was
+    /// found with a matching reply code. Zones which fail to respond in time are treated
+    /// as not listing the address.
+    #[tracing::instrument(skip(self))]
+    pub fn start_dnsbl_lookup(&self, conn_id: ConnectionId, addr: IpAddr) {
+        self.control_sender
+            .send(ControlMessage::StartDnsblLookup(conn_id, addr))
+            .ok();
+    }
+
     /// Shut down the communications task and child process, then wait for them to exit.
     ///
     /// Note that the child process will only be waited for if this `AuthClient` was created by
@@ -242,6 +316,8 @@ impl AuthClient {
         Ok(AuthClientState {
             control_fd,
             event_fd,
+            child_pid: self.child_pid,
+            ipc_version: crate::IPC_VERSION,
         })
     }
 
@@ -251,10 +327,24 @@ impl AuthClient {
     /// worker process, and will therefore start to emit result objects for any
     /// operations which were begun before [`save_state`](Self::save_state) was called
     ///  on the previous client object.
+    ///
+    /// This fails if the worker process is not [compatible](AuthClientState::is_compatible)
+    /// with this build; use [`replace`](Self::replace) instead in that case.
     pub fn resume(
         state: AuthClientState,
         event_channel: UnboundedSender<AuthEvent>,
     ) -> std::io::Result<Self> {
+        if !state.is_compatible() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "auth worker uses IPC version {}, expected {}",
+                    state.ipc_version,
+                    crate::IPC_VERSION
+                ),
+            ));
+        }
+
         let (control_send, event_recv) = unsafe {
             (
                 IpcSender::<ControlMessage>::from_raw_fd(state.control_fd),
@@ -278,6 +368,57 @@ impl AuthClient {
             comm_task_shutdown: shutdown_send,
             comm_task,
             child_process: None,
+            child_pid: state.child_pid,
         })
     }
+
+    /// Replace the worker process described by a previously saved state with a new one,
+    /// for use when it is not [compatible](AuthClientState::is_compatible) with this build.
+    ///
+    /// Results of any operations begun with the old worker are lost, so they need to be
+    /// started again with the new one.
+    pub fn replace(
+        state: AuthClientState,
+        event_channel: UnboundedSender<AuthEvent>,
+    ) -> Result<Self, AuthClientError> {
+        tracing::warn!(
+            old_version = state.ipc_version,
+            new_version = crate::IPC_VERSION,
+            "Replacing incompatible auth worker process"
+        );
+        state.terminate_worker();
+
+        Self::new(event_channel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unversioned_state_is_incompatible() {
+        // As saved with a worker from before the IPC was versioned, which would fail
+        // to decode the current `ControlMessage`, and whose `AuthEvent`s we couldn't decode
+        let state: AuthClientState =
+            serde_json::from_str(r#"{ "control_fd": 3, "event_fd": 4 }"#).unwrap();
+
+        assert!(!state.is_compatible());
+        assert_eq!(state.child_pid, None);
+    }
+
+    #[test]
+    fn current_state_is_compatible() {
+        let state = AuthClientState {
+            control_fd: 3,
+            event_fd: 4,
+            child_pid: Some(1234),
+            ipc_version: crate::IPC_VERSION,
+        };
+        let state: AuthClientState =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+
+        assert!(state.is_compatible());
+        assert_eq!(state.child_pid, Some(1234));
+    }
 }
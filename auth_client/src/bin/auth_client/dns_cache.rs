@@ -0,0 +1,136 @@
+use sable_network::prelude::*;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::IpAddr,
+    time::Instant,
+};
+
+struct CacheEntry {
+    hostname: Option<Hostname>,
+    expires: Instant,
+}
+
+/// A size-bounded cache of reverse DNS results.
+///
+/// Both successful lookups and failures (`None`) are cached. Entries are dropped once
+/// they expire; if the cache is full, the entry closest to expiry is evicted to make
+/// room for a new one.
+pub struct DnsCache {
+    entries: HashMap<IpAddr, CacheEntry>,
+    /// Index of entries by expiry time, so the next one to expire can be found cheaply
+    expiry: BTreeSet<(Instant, IpAddr)>,
+    max_entries: usize,
+}
+
+impl DnsCache {
+    /// Construct a cache holding at most `max_entries` results
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            expiry: BTreeSet::new(),
+            max_entries,
+        }
+    }
+
+    /// Look up a cached result for `addr`. The outer `Option` is `None` if there is no
+    /// current entry; the inner one is `None` if the cached lookup found no hostname.
+    pub fn get(&mut self, addr: IpAddr, now: Instant) -> Option<Option<Hostname>> {
+        let entry = self.entries.get(&addr)?;
+        if entry.expires > now {
+            return Some(entry.hostname);
+        }
+        self.remove(addr);
+        None
+    }
+
+    /// Cache the result of a lookup for `addr`, valid until `expires`
+    pub fn insert(
+        &mut self,
+        addr: IpAddr,
+        hostname: Option<Hostname>,
+        expires: Instant,
+        now: Instant,
+    ) {
+        if self.max_entries == 0 || expires <= now {
+            return;
+        }
+        self.remove(addr);
+
+        while self.entries.len() >= self.max_entries {
+            let Some((_, oldest)) = self.expiry.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+
+        self.entries.insert(addr, CacheEntry { hostname, expires });
+        self.expiry.insert((expires, addr));
+    }
+
+    fn remove(&mut self, addr: IpAddr) {
+        if let Some(entry) = self.entries.remove(&addr) {
+            self.expiry.remove(&(entry.expires, addr));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn addr(n: u8) -> IpAddr {
+        IpAddr::from([192, 0, 2, n])
+    }
+
+    #[test]
+    fn expires_entries() {
+        let mut cache = DnsCache::new(10);
+        let now = Instant::now();
+        let host = Hostname::convert("host.example").unwrap();
+
+        cache.insert(addr(1), Some(host), now + Duration::from_secs(10), now);
+        cache.insert(addr(2), None, now + Duration::from_secs(5), now);
+
+        assert_eq!(
+            cache.get(addr(1), now).flatten().map(|h| h.to_string()),
+            Some("host.example".to_string())
+        );
+        assert!(matches!(cache.get(addr(2), now), Some(None)));
+        assert!(cache.get(addr(3), now).is_none());
+
+        let later = now + Duration::from_secs(6);
+        assert!(cache.get(addr(2), later).is_none());
+        assert!(cache.get(addr(1), later).is_some());
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn evicts_soonest_expiry_when_full() {
+        let mut cache = DnsCache::new(2);
+        let now = Instant::now();
+
+        cache.insert(addr(1), None, now + Duration::from_secs(30), now);
+        cache.insert(addr(2), None, now + Duration::from_secs(10), now);
+        cache.insert(addr(3), None, now + Duration::from_secs(20), now);
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.get(addr(1), now).is_some());
+        assert!(cache.get(addr(2), now).is_none());
+        assert!(cache.get(addr(3), now).is_some());
+    }
+
+    #[test]
+    fn replaces_existing_entry() {
+        let mut cache = DnsCache::new(2);
+        let now = Instant::now();
+
+        cache.insert(addr(1), None, now + Duration::from_secs(5), now);
+        cache.insert(addr(1), None, now + Duration::from_secs(50), now);
+        cache.insert(addr(2), None, now + Duration::from_secs(10), now);
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.get(addr(1), now + Duration::from_secs(20)).is_some());
+    }
+}
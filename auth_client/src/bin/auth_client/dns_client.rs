@@ -2,15 +2,43 @@ use auth_client::*;
 use client_listener::ConnectionId;
 use sable_network::prelude::*;
 
-use std::net::IpAddr;
+use crate::dns_cache::DnsCache;
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use tokio::{sync::mpsc::UnboundedSender, task};
-use trust_dns_resolver::TokioAsyncResolver;
+use trust_dns_resolver::{
+    error::{ResolveError, ResolveErrorKind},
+    TokioAsyncResolver,
+};
+
+/// Maximum number of results held in the cache
+const MAX_CACHE_ENTRIES: usize = 16384;
+
+/// Upper bound on how long a result is cached, whatever its DNS TTL
+const MAX_TTL: Duration = Duration::from_secs(3600);
+
+/// How long to cache the absence of a usable hostname
+const NEGATIVE_TTL: Duration = Duration::from_secs(300);
 
 /// A simple client for [`TokioAsyncResolver`] to handle the DNS lookups
 /// required for connecting IRC clients.
+///
+/// Results, including failures to find a forward-confirmed hostname, are cached for
+/// the lifetime of the worker process, subject to their DNS TTLs. Only one lookup is
+/// made at a time for each address; connections from an address whose lookup is
+/// already in progress wait for its result.
 pub struct InternalDnsClient {
     event_channel: UnboundedSender<DnsResult>,
     resolver: TokioAsyncResolver,
+    cache: Arc<Mutex<DnsCache>>,
+    /// Lookups in progress, with the connections waiting for each. Lock this before
+    /// `cache` when both are needed.
+    pending: Arc<Mutex<HashMap<IpAddr, Vec<ConnectionId>>>>,
 }
 
 impl InternalDnsClient {
@@ -22,47 +50,109 @@ impl InternalDnsClient {
         Self {
             event_channel,
             resolver,
+            cache: Arc::new(Mutex::new(DnsCache::new(MAX_CACHE_ENTRIES))),
+            pending: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
     /// Begin a DNS lookup.
     ///
+    /// If a current result for `addr` is cached, it is sent immediately. If a lookup
+    /// for `addr` is already in progress, its result is sent for this connection too
+    /// when it completes. Otherwise a new lookup is started, and its result is cached
+    /// once it completes.
+    ///
     /// `conn_id` is not used internally, but is attached to the response message to allow the result
     ///  to be associated with the request.
     pub fn start_lookup(&self, conn_id: ConnectionId, addr: IpAddr) {
+        let mut pending = self.pending.lock().unwrap();
+
+        let cached = self.cache.lock().unwrap().get(addr, Instant::now());
+        if let Some(hostname) = cached {
+            let _res = self.event_channel.send(DnsResult {
+                conn: conn_id,
+                hostname,
+                cached: true,
+            });
+            return;
+        }
+
+        if let Some(waiting) = pending.get_mut(&addr) {
+            waiting.push(conn_id);
+            return;
+        }
+        pending.insert(addr, vec![conn_id]);
+
         let chan = self.event_channel.clone();
         let resolver = self.resolver.clone();
+        let cache = Arc::clone(&self.cache);
+        let pending = Arc::clone(&self.pending);
 
         task::spawn(async move {
-            let name = match resolver.reverse_lookup(addr).await {
-                Ok(lookup) => match lookup.iter().next() {
-                    Some(name) => {
-                        if Self::verify_forward_matches(&resolver, name, addr).await {
-                            Some(name.to_ascii())
-                        } else {
-                            None
-                        }
-                    }
-                    None => None,
-                },
-                Err(_) => None,
+            let (hostname, valid_until) = Self::lookup(&resolver, addr).await;
+
+            // Cache the result before removing the pending entry, so that nothing
+            // arriving in between starts another lookup
+            let waiting = {
+                let mut pending = pending.lock().unwrap();
+                if let Some(valid_until) = valid_until {
+                    let now = Instant::now();
+                    cache.lock().unwrap().insert(
+                        addr,
+                        hostname,
+                        valid_until.min(now + MAX_TTL),
+                        now,
+                    );
+                }
+                pending.remove(&addr).unwrap_or_default()
             };
-            let name = name.and_then(|n| Hostname::convert(n.trim_end_matches('.')).ok());
-            let _res = chan.send(DnsResult {
-                conn: conn_id,
-                hostname: name,
-            });
+
+            // Only the connection which started the lookup had to wait for it; the
+            // rest are counted as served from the cache
+            for (i, conn) in waiting.into_iter().enumerate() {
+                let _res = chan.send(DnsResult {
+                    conn,
+                    hostname,
+                    cached: i > 0,
+                });
+            }
         });
     }
 
-    async fn verify_forward_matches(
+    /// Look up a forward-confirmed hostname for `addr`, returning it along with the time
+    /// until which the result may be cached, if it may be cached at all
+    async fn lookup(
         resolver: &TokioAsyncResolver,
-        name: &trust_dns_resolver::Name,
         addr: IpAddr,
-    ) -> bool {
-        match resolver.lookup_ip(name.clone()).await {
-            Ok(lookup) => lookup.iter().any(|ip| ip == addr),
-            Err(_) => false,
+    ) -> (Option<Hostname>, Option<Instant>) {
+        let reverse = match resolver.reverse_lookup(addr).await {
+            Ok(lookup) => lookup,
+            Err(e) => return (None, Self::negative_expiry(&e)),
+        };
+        let Some(name) = reverse.iter().next() else {
+            return (None, Some(Instant::now() + NEGATIVE_TTL));
+        };
+
+        let forward = match resolver.lookup_ip(name.clone()).await {
+            Ok(lookup) => lookup,
+            Err(e) => return (None, Self::negative_expiry(&e)),
+        };
+        let valid_until = reverse.valid_until().min(forward.valid_until());
+
+        if !forward.iter().any(|ip| ip == addr) {
+            return (None, Some(valid_until));
+        }
+
+        let hostname = Hostname::convert(name.to_ascii().trim_end_matches('.')).ok();
+        (hostname, Some(valid_until))
+    }
+
+    /// How long to cache a failed lookup. Only definite negative answers are cached;
+    /// timeouts and server failures are retried for the next connection.
+    fn negative_expiry(error: &ResolveError) -> Option<Instant> {
+        match error.kind() {
+            ResolveErrorKind::NoRecordsFound { .. } => Some(Instant::now() + NEGATIVE_TTL),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,68 @@
+use auth_client::dnsbl::{self, DnsblResolver};
+use auth_client::*;
+use client_listener::ConnectionId;
+
+use std::{net::IpAddr, sync::Arc};
+use tokio::{sync::mpsc::UnboundedSender, task};
+
+/// A client to check connecting addresses against the configured DNS blocklists.
+pub struct InternalDnsblClient {
+    event_channel: UnboundedSender<DnsblResult>,
+    zones: Arc<Vec<DnsblZone>>,
+    resolver: Option<DnsblResolver>,
+}
+
+impl InternalDnsblClient {
+    /// Construct an `InternalDnsblClient`. Until [`configure`](Self::configure) is called,
+    /// no zones are checked. Responses to each request will be sent over `event_channel`
+    /// as and when they complete.
+    pub fn new(event_channel: UnboundedSender<DnsblResult>) -> Self {
+        Self {
+            event_channel,
+            zones: Arc::new(Vec::new()),
+            resolver: None,
+        }
+    }
+
+    /// Replace the current configuration with `settings`.
+    pub fn configure(&mut self, settings: DnsblSettings) {
+        if !settings.is_enabled() {
+            self.zones = Arc::new(Vec::new());
+            self.resolver = None;
+            return;
+        }
+
+        match DnsblResolver::new() {
+            Ok(resolver) => {
+                self.zones = Arc::new(settings.zones);
+                self.resolver = Some(resolver);
+            }
+            Err(e) => {
+                tracing::error!("Failed to create DNSBL resolver: {}", e);
+                self.zones = Arc::new(Vec::new());
+                self.resolver = None;
+            }
+        }
+    }
+
+    /// Begin checking `addr` against the configured zones.
+    ///
+    /// `conn_id` is not used internally, but is attached to the response message to allow the result
+    ///  to be associated with the request.
+    pub fn start_lookup(&self, conn_id: ConnectionId, addr: IpAddr) {
+        let chan = self.event_channel.clone();
+        let zones = Arc::clone(&self.zones);
+        let resolver = self.resolver.clone();
+
+        task::spawn(async move {
+            let hits = match resolver {
+                Some(resolver) => dnsbl::check(&resolver, &zones, addr).await,
+                None => Vec::new(),
+            };
+            let _res = chan.send(DnsblResult {
+                conn: conn_id,
+                hits,
+            });
+        });
+    }
+}
@@ -0,0 +1,140 @@
+use auth_client::*;
+use client_listener::ConnectionId;
+use sable_network::prelude::*;
+
+use std::{net::SocketAddr, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpSocket,
+    sync::mpsc::UnboundedSender,
+    task,
+    time::timeout,
+};
+
+const IDENT_PORT: u16 = 113;
+
+/// Time allowed for the whole lookup, including connecting and reading the response
+const IDENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Responses are limited to 1000 characters by RFC 1413
+const MAX_RESPONSE_LENGTH: u64 = 1000;
+
+/// A client for ident (RFC 1413) lookups required for connecting IRC clients.
+pub struct InternalIdentClient {
+    event_channel: UnboundedSender<IdentResult>,
+}
+
+impl InternalIdentClient {
+    /// Construct an `InternalIdentClient`. Responses to each request will be sent over
+    /// `event_channel` as and when they complete.
+    pub fn new(event_channel: UnboundedSender<IdentResult>) -> Self {
+        Self { event_channel }
+    }
+
+    /// Begin an ident lookup for the client connection between `local` and `remote`.
+    ///
+    /// `conn_id` is not used internally, but is attached to the response message to allow the result
+    ///  to be associated with the request.
+    pub fn start_lookup(&self, conn_id: ConnectionId, local: SocketAddr, remote: SocketAddr) {
+        let chan = self.event_channel.clone();
+
+        task::spawn(async move {
+            let username = match timeout(IDENT_TIMEOUT, Self::query(local, remote)).await {
+                Ok(Ok(response)) => parse_response(&response, remote.port(), local.port()),
+                Ok(Err(e)) => {
+                    tracing::debug!(?conn_id, "Ident lookup failed: {}", e);
+                    None
+                }
+                Err(_) => {
+                    tracing::debug!(?conn_id, "Ident lookup timed out");
+                    None
+                }
+            };
+            let _res = chan.send(IdentResult {
+                conn: conn_id,
+                username,
+            });
+        });
+    }
+
+    async fn query(local: SocketAddr, remote: SocketAddr) -> std::io::Result<String> {
+        let socket = if remote.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        // Query from the address the client connected to, which is the one their
+        // ident server will expect
+        socket.bind(SocketAddr::new(local.ip(), 0))?;
+
+        let mut stream = socket
+            .connect(SocketAddr::new(remote.ip(), IDENT_PORT))
+            .await?;
+        stream
+            .write_all(format!("{}, {}\r\n", remote.port(), local.port()).as_bytes())
+            .await?;
+
+        let mut response = String::new();
+        BufReader::new(stream)
+            .take(MAX_RESPONSE_LENGTH)
+            .read_line(&mut response)
+            .await?;
+        Ok(response)
+    }
+}
+
+/// Parse an ident response such as `6193, 23 : USERID : UNIX : stjohns`, returning the
+/// username if it is a successful response for the port pair we asked about.
+fn parse_response(response: &str, remote_port: u16, local_port: u16) -> Option<Username> {
+    let mut fields = response.trim_end().splitn(4, ':');
+
+    let (response_remote, response_local) = fields.next()?.split_once(',')?;
+    if response_remote.trim().parse::<u16>().ok()? != remote_port
+        || response_local.trim().parse::<u16>().ok()? != local_port
+    {
+        return None;
+    }
+
+    if fields.next()?.trim() != "USERID" {
+        return None;
+    }
+    let _operating_system = fields.next()?;
+
+    // The user ID is an arbitrary string; keep only characters that are
+    // unremarkable in a username
+    let user: String = fields
+        .next()?
+        .trim()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+
+    Username::new_coerce(&user).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn successful_response() {
+        let user = parse_response("6193, 23 : USERID : UNIX : stjohns\r\n", 6193, 23);
+        assert_eq!(user.map(|u| u.to_string()).as_deref(), Some("stjohns"));
+    }
+
+    #[test]
+    fn error_response() {
+        assert!(parse_response("6195, 23 : ERROR : NO-USER\r\n", 6195, 23).is_none());
+    }
+
+    #[test]
+    fn mismatched_ports() {
+        assert!(parse_response("6193, 24 : USERID : UNIX : stjohns\r\n", 6193, 23).is_none());
+    }
+
+    #[test]
+    fn sanitises_username() {
+        let user = parse_response("1, 2 : USERID : OTHER :  a*b@c-very-long-name\r\n", 1, 2);
+        assert_eq!(user.map(|u| u.to_string()).as_deref(), Some("abc-very-l"));
+    }
+}
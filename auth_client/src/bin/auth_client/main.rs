@@ -26,7 +26,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let (dns_event_send, mut dns_event_recv) = unbounded_channel();
 
+    let (ident_event_send, mut ident_event_recv) = unbounded_channel();
+
     let client = dns_client::InternalDnsClient::new(dns_event_send);
+    let ident_client = ident_client::InternalIdentClient::new(ident_event_send);
+
+    let (dnsbl_event_send, mut dnsbl_event_recv) = unbounded_channel();
+    let mut dnsbl_client = dnsbl_client::InternalDnsblClient::new(dnsbl_event_send);
 
     loop {
         select!(
@@ -42,6 +48,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     {
                         client.start_lookup(conn_id, addr);
                     }
+                    ControlMessage::StartIdentLookup(conn_id, local, remote) =>
+                    {
+                        ident_client.start_lookup(conn_id, local, remote);
+                    }
+                    ControlMessage::ConfigureDnsbl(settings) =>
+                    {
+                        dnsbl_client.configure(settings);
+                    }
+                    ControlMessage::StartDnsblLookup(conn_id, addr) =>
+                    {
+                        dnsbl_client.start_lookup(conn_id, addr);
+                    }
                 }
             },
             event = dns_event_recv.recv() =>
@@ -57,6 +75,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         break;
                     }
                 }
+            },
+            event = ident_event_recv.recv() =>
+            {
+                match event
+                {
+                    Some(evt) =>
+                    {
+                        event_send.send(AuthEvent::IdentResult(evt)).await?;
+                    }
+                    None =>
+                    {
+                        break;
+                    }
+                }
+            },
+            event = dnsbl_event_recv.recv() =>
+            {
+                match event
+                {
+                    Some(evt) =>
+                    {
+                        event_send.send(AuthEvent::DnsblResult(evt)).await?;
+                    }
+                    None =>
+                    {
+                        break;
+                    }
+                }
             }
         );
     }
@@ -64,4 +110,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+mod dns_cache;
 mod dns_client;
+mod dnsbl_client;
+mod ident_client;
@@ -1,5 +1,6 @@
+use crate::DnsblSettings;
 use client_listener::ConnectionId;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 
 /// A message sent from the consumer process to the worker
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -9,4 +10,14 @@ pub enum ControlMessage {
 
     /// Start a reverse DNS lookup for the given connection ID and IP address
     StartDnsLookup(ConnectionId, IpAddr),
+
+    /// Start an ident lookup for the given connection ID, with the local and remote
+    /// addresses of the client's connection, in that order
+    StartIdentLookup(ConnectionId, SocketAddr, SocketAddr),
+
+    /// Replace the DNSBL configuration
+    ConfigureDnsbl(DnsblSettings),
+
+    /// Start checking the given IP address against the configured DNSBL zones
+    StartDnsblLookup(ConnectionId, IpAddr),
 }
@@ -0,0 +1,238 @@
+//! DNS blocklist configuration and lookups.
+//!
+//! A DNSBL zone is queried by reversing the octets (or, for IPv6, nibbles) of the
+//! address being checked and appending the zone name. Listed addresses resolve to
+//! one or more addresses in `127.0.0.0/8`, the last octet of which usually
+//! indicates why the address is listed.
+
+use sable_network::network::ban::NetworkBanAction;
+
+use std::{
+    fmt::Write,
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+use tokio::time::timeout;
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// Time allowed for each zone's lookup before it's treated as not listed
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What to do with a connection whose address is listed in a zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsblAction {
+    /// Refuse the connection
+    Refuse,
+    /// Require the connection to log in with SASL before registering
+    RequireSasl,
+}
+
+impl From<DnsblAction> for NetworkBanAction {
+    fn from(action: DnsblAction) -> Self {
+        // DNSBL results only ever apply to new connections
+        match action {
+            DnsblAction::Refuse => NetworkBanAction::RefuseConnection(false),
+            DnsblAction::RequireSasl => NetworkBanAction::RequireSasl(false),
+        }
+    }
+}
+
+/// A single DNS blocklist to be checked
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DnsblZone {
+    /// The zone name, e.g. `dnsbl.dronebl.org`
+    pub zone: String,
+    /// Reply codes (the last octet of the returned address) which count as a match.
+    /// If empty, any reply matches.
+    #[serde(default)]
+    pub reply_codes: Vec<u8>,
+    /// Whether the zone lists IPv6 addresses. IPv6 connections aren't checked
+    /// against zones which don't.
+    #[serde(default)]
+    pub ipv6: bool,
+    /// What to do with matching connections
+    pub action: DnsblAction,
+    /// The reason given to matching connections
+    pub reason: String,
+}
+
+/// Configuration for DNSBL checks
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct DnsblSettings {
+    /// Zones to check, in order of precedence
+    #[serde(default)]
+    pub zones: Vec<DnsblZone>,
+}
+
+impl DnsblSettings {
+    /// Whether any zones are configured
+    pub fn is_enabled(&self) -> bool {
+        !self.zones.is_empty()
+    }
+}
+
+/// A zone in which a checked address was found
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DnsblHit {
+    pub zone: String,
+    pub action: DnsblAction,
+    pub reason: String,
+}
+
+/// Resolver used for DNSBL queries
+#[derive(Clone)]
+pub enum DnsblResolver {
+    /// Look up names in the DNS
+    System(TokioAsyncResolver),
+    /// Look up names in a fixed table
+    #[cfg(test)]
+    Stub(std::sync::Arc<std::collections::HashMap<String, Vec<Ipv4Addr>>>),
+}
+
+impl DnsblResolver {
+    /// Create a resolver using the system's DNS configuration
+    pub fn new() -> Result<Self, trust_dns_resolver::error::ResolveError> {
+        Ok(Self::System(TokioAsyncResolver::tokio_from_system_conf()?))
+    }
+
+    async fn lookup(&self, name: &str) -> Vec<Ipv4Addr> {
+        match self {
+            Self::System(resolver) => match resolver.ipv4_lookup(name).await {
+                Ok(lookup) => lookup.iter().copied().collect(),
+                Err(_) => Vec::new(),
+            },
+            #[cfg(test)]
+            Self::Stub(records) => records
+                .get(name.trim_end_matches('.'))
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The name to look up to check `addr` against `zone`, if the zone supports
+/// that address family
+fn query_name(addr: IpAddr, zone: &DnsblZone) -> Option<String> {
+    let mut name = String::new();
+    match addr.to_canonical() {
+        IpAddr::V4(v4) => {
+            for octet in v4.octets().iter().rev() {
+                write!(name, "{}.", octet).unwrap();
+            }
+        }
+        IpAddr::V6(v6) if zone.ipv6 => {
+            for octet in v6.octets().iter().rev() {
+                write!(name, "{:x}.{:x}.", octet & 0xf, octet >> 4).unwrap();
+            }
+        }
+        IpAddr::V6(_) => return None,
+    }
+    name.push_str(zone.zone.trim_end_matches('.'));
+    // Fully qualified, so that the resolver doesn't apply search domains
+    name.push('.');
+    Some(name)
+}
+
+fn reply_matches(zone: &DnsblZone, reply: Ipv4Addr) -> bool {
+    let [first, .., code] = reply.octets();
+    first == 127 && (zone.reply_codes.is_empty() || zone.reply_codes.contains(&code))
+}
+
+/// Check `addr` against each of `zones`, returning those in which it's listed.
+///
+/// Zones are queried concurrently; any which fail or time out are treated as
+/// not listing the address.
+pub async fn check(resolver: &DnsblResolver, zones: &[DnsblZone], addr: IpAddr) -> Vec<DnsblHit> {
+    let lookups: Vec<_> = zones
+        .iter()
+        .filter_map(|zone| {
+            let name = query_name(addr, zone)?;
+            let resolver = resolver.clone();
+            let lookup = tokio::spawn(async move {
+                timeout(LOOKUP_TIMEOUT, resolver.lookup(&name))
+                    .await
+                    .unwrap_or_default()
+            });
+            Some((zone, lookup))
+        })
+        .collect();
+
+    let mut hits = Vec::new();
+    for (zone, lookup) in lookups {
+        let replies = lookup.await.unwrap_or_default();
+        if replies.iter().any(|reply| reply_matches(zone, *reply)) {
+            hits.push(DnsblHit {
+                zone: zone.zone.clone(),
+                action: zone.action,
+                reason: zone.reason.clone(),
+            });
+        }
+    }
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn zone(name: &str, reply_codes: Vec<u8>, action: DnsblAction) -> DnsblZone {
+        DnsblZone {
+            zone: name.to_string(),
+            reply_codes,
+            ipv6: true,
+            action,
+            reason: format!("Listed in {}", name),
+        }
+    }
+
+    fn stub(records: &[(&str, &str)]) -> DnsblResolver {
+        DnsblResolver::Stub(Arc::new(
+            records
+                .iter()
+                .map(|(name, addr)| (name.to_string(), vec![addr.parse().unwrap()]))
+                .collect(),
+        ))
+    }
+
+    #[test]
+    fn query_names() {
+        let z = zone("dnsbl.example", vec![], DnsblAction::Refuse);
+
+        assert_eq!(
+            query_name("192.0.2.1".parse().unwrap(), &z).unwrap(),
+            "1.2.0.192.dnsbl.example."
+        );
+        assert_eq!(
+            query_name("2001:db8::1".parse().unwrap(), &z).unwrap(),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.dnsbl.example."
+        );
+        assert!(query_name(
+            "2001:db8::1".parse().unwrap(),
+            &DnsblZone { ipv6: false, ..z }
+        )
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn matches_reply_codes() {
+        let resolver = stub(&[
+            ("1.2.0.192.any.example", "127.0.0.2"),
+            ("1.2.0.192.codes.example", "127.0.0.4"),
+            ("1.2.0.192.other-codes.example", "127.0.0.5"),
+        ]);
+        let zones = vec![
+            zone("any.example", vec![], DnsblAction::RequireSasl),
+            zone("codes.example", vec![3, 4], DnsblAction::Refuse),
+            zone("other-codes.example", vec![3, 4], DnsblAction::Refuse),
+            zone("unlisted.example", vec![], DnsblAction::Refuse),
+        ];
+
+        let hits = check(&resolver, &zones, "192.0.2.1".parse().unwrap()).await;
+        let hit_zones: Vec<_> = hits.iter().map(|h| h.zone.as_str()).collect();
+
+        assert_eq!(hit_zones, ["any.example", "codes.example"]);
+        assert_eq!(hits[0].action, DnsblAction::RequireSasl);
+    }
+}
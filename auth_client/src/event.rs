@@ -1,5 +1,6 @@
+use crate::DnsblHit;
 use client_listener::ConnectionId;
-use sable_network::validated::Hostname;
+use sable_network::validated::{Hostname, Username};
 
 /// The result of a DNS lookup
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -8,11 +9,40 @@ pub struct DnsResult {
     pub conn: ConnectionId,
     /// The hostname, or None if no suitable name was found
     pub hostname: Option<Hostname>,
+    /// Whether the result was served from the worker's cache, or from a lookup
+    /// already in progress for another connection
+    pub cached: bool,
 }
 
-/// A notification that something has completed
+/// The result of an ident lookup
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct IdentResult {
+    /// The connection ID provided when initiating the request
+    pub conn: ConnectionId,
+    /// The username, or None if the lookup failed or timed out
+    pub username: Option<Username>,
+}
+
+/// The result of checking a connection's address against DNS blocklists
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct DnsblResult {
+    /// The connection ID provided when initiating the request
+    pub conn: ConnectionId,
+    /// The zones in which the address is listed, if any
+    pub hits: Vec<DnsblHit>,
+}
+
+/// A notification that something has completed.
+///
+/// This is exchanged with a worker process which may be older than the consumer, so
+/// any change to it or the types it contains requires an increase in
+/// [`IPC_VERSION`](crate::IPC_VERSION).
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum AuthEvent {
     /// A reverse DNS lookup has been completed
     DnsResult(DnsResult),
+    /// An ident lookup has been completed
+    IdentResult(IdentResult),
+    /// A DNSBL check has been completed
+    DnsblResult(DnsblResult),
 }
@@ -1,4 +1,4 @@
-//! Worker process for DNS and (eventually) identd checks, and library to communicate therewith.
+//! Worker process for DNS, DNSBL and identd checks, and library to communicate therewith.
 //!
 //! The [`AuthClient`] interface supports save and resume across `exec()` boundaries,
 //! via the [`save_state`](AuthClient::save_state) and [`resume`](AuthClient::resume)
@@ -8,6 +8,12 @@
 //!
 //! Obviously this only works on Unix-like systems.
 
+/// Version of the protocol spoken between an [`AuthClient`] and its worker process.
+/// This must be increased whenever [`ControlMessage`], [`AuthEvent`] or any type they
+/// contain changes, so that a server resuming after an upgrade can tell whether it
+/// is able to keep using a worker process started by an earlier version.
+pub const IPC_VERSION: u32 = 1;
+
 mod event;
 pub use event::*;
 
@@ -16,3 +22,6 @@ pub use control::*;
 
 mod auth_client;
 pub use crate::auth_client::*;
+
+pub mod dnsbl;
+pub use dnsbl::{DnsblAction, DnsblHit, DnsblSettings, DnsblZone};
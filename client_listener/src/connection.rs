@@ -11,20 +11,23 @@ pub struct Connection {
     pub id: ConnectionId,
     pub tls_info: Option<TlsInfo>,
     pub remote_addr: IpAddr,
+    pub websocket: bool,
+    /// The hostname assigned by the listener, for connections on a Unix socket
+    pub local_hostname: Option<String>,
+    /// Both ends of the TCP connection, if accepted directly over TCP
+    pub tcp_endpoints: Option<TcpEndpoints>,
     send_channel: UnboundedSender<ControlMessage>,
 }
 
 impl Connection {
-    pub(crate) fn new(
-        id: ConnectionId,
-        tls_info: Option<TlsInfo>,
-        remote_addr: IpAddr,
-        send_channel: UnboundedSender<ControlMessage>,
-    ) -> Self {
+    pub(crate) fn new(data: ConnectionData, send_channel: UnboundedSender<ControlMessage>) -> Self {
         Self {
-            id,
-            tls_info,
-            remote_addr,
+            id: data.id,
+            tls_info: data.tls_info,
+            remote_addr: data.remote_addr,
+            websocket: data.websocket,
+            local_hostname: data.local_hostname,
+            tcp_endpoints: data.tcp_endpoints,
             send_channel,
         }
     }
@@ -34,6 +37,16 @@ impl Connection {
         self.tls_info.is_some()
     }
 
+    /// Is this a WebSocket connection?
+    pub fn is_websocket(&self) -> bool {
+        self.websocket
+    }
+
+    /// Is this a connection from a local client, over a Unix socket?
+    pub fn is_local(&self) -> bool {
+        self.local_hostname.is_some()
+    }
+
     fn send_control(&self, msg: ConnectionControlDetail) {
         if let Err(e) = self
             .send_channel
@@ -53,6 +66,17 @@ impl Connection {
         self.send_control(ConnectionControlDetail::Send(msg));
     }
 
+    /// Change the number of outgoing lines that may be queued for this connection
+    /// before it is closed for exceeding its send queue
+    pub fn set_send_queue_length(&self, len: usize) {
+        if let Err(e) = self
+            .send_channel
+            .send(ControlMessage::SetSendQueueLength(self.id, len))
+        {
+            tracing::error!("Error sending connection control message: {}", e);
+        }
+    }
+
     /// Save the connection state for later restoration.
     ///
     /// See [`ListenerCollection::restore_connection`] for the counterpart to restore
@@ -62,6 +86,9 @@ impl Connection {
             id: self.id,
             remote_addr: self.remote_addr,
             tls_info: self.tls_info,
+            websocket: self.websocket,
+            local_hostname: self.local_hostname,
+            tcp_endpoints: self.tcp_endpoints,
         }
     }
 }
@@ -13,6 +13,12 @@ pub enum ConnectionError {
     InternalError,
     #[error("Send queue full")]
     SendQueueFull,
+    #[error("Protocol error: {0}")]
+    ProtocolError(String),
+    #[error("Line too long")]
+    LineTooLong,
+    #[error("Excess Flood")]
+    ExcessFlood,
 }
 
 /// An error that might occur when configuring a listener.
@@ -20,6 +26,8 @@ pub enum ConnectionError {
 pub enum ListenerError {
     #[error("TLS requested with no TLS config")]
     NoTlsConfig,
+    #[error("Connection type does not match listener address")]
+    AddressMismatch,
     #[error("I/O Error: {0}")]
     IoError(String),
     #[error("Control queue full")]
@@ -28,6 +36,8 @@ pub enum ListenerError {
     ControlQueueClosed,
     #[error("Error communicating with listener process")]
     CommunicationError,
+    #[error("Invalid TLS settings: {0}")]
+    BadTlsConfig(String),
 }
 
 impl From<std::io::Error> for ListenerError {
@@ -2,37 +2,134 @@ use crate::internal::*;
 use crate::*;
 
 use sha1::{Digest, Sha1};
-use std::net::IpAddr;
+use std::{
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use tokio::{
-    io::AsyncWriteExt,
-    net::TcpStream,
-    sync::mpsc::{channel, Sender},
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    sync::mpsc::{channel, Receiver, Sender},
+    time::timeout,
 };
 
+/// The default number of outgoing lines that may be queued for a connection
 const SEND_QUEUE_LEN: usize = 100;
+/// The upper bound on a connection's configured send queue length
+pub(crate) const MAX_SEND_QUEUE_LEN: usize = 10000;
+const WEBSOCKET_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Minimum interval between warnings about PROXY connections from untrusted
+/// sources, since any client can trigger one
+const UNTRUSTED_PROXY_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+static LAST_UNTRUSTED_PROXY_WARNING: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Warn about a PROXY connection from an untrusted source, unless we've done so recently
+fn warn_untrusted_proxy(addr: IpAddr) {
+    let mut last = LAST_UNTRUSTED_PROXY_WARNING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let now = Instant::now();
+    if last.map_or(true, |t| {
+        now.duration_since(t) >= UNTRUSTED_PROXY_WARNING_INTERVAL
+    }) {
+        *last = Some(now);
+        tracing::warn!(
+            "Refused PROXY connection from untrusted source {}; further occurrences will be logged at debug level for {:?}",
+            addr,
+            UNTRUSTED_PROXY_WARNING_INTERVAL
+        );
+    }
+}
 
 pub(crate) struct InternalConnection {
     pub id: ConnectionId,
     pub remote_addr: IpAddr,
     pub control_channel: Sender<ConnectionControlDetail>,
+    /// Number of outgoing lines which may be queued before the connection is
+    /// considered to have exceeded its send queue
+    pub send_queue_len: usize,
     pub tls_info: Option<TlsInfo>,
+    pub websocket: bool,
+    pub local_hostname: Option<String>,
+    pub tcp_endpoints: Option<TcpEndpoints>,
+}
+
+/// What is known about the source of a newly accepted connection
+pub(crate) struct PeerInfo {
+    /// The remote IP address
+    pub addr: IpAddr,
+    /// The configured hostname, for connections on a Unix socket, which have
+    /// no real remote address
+    pub local_hostname: Option<String>,
+    /// Both ends of the connection, if it was accepted over TCP
+    pub tcp_endpoints: Option<TcpEndpoints>,
 }
 
 impl InternalConnection {
-    pub async fn create_and_send(
+    /// Set up a newly accepted connection from `peer`, and notify it via `events`.
+    pub async fn create_and_send<S>(
         id: ConnectionId,
-        stream: TcpStream,
+        mut stream: S,
+        peer: PeerInfo,
         conntype: InternalConnectionType,
+        options: Arc<ListenerOptions>,
         events: Sender<InternalConnectionEventType>,
-    ) -> Result<(), ConnectionError> {
-        let (control_send, control_recv) = channel(SEND_QUEUE_LEN);
+    ) -> Result<(), ConnectionError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (control_send, control_recv) = channel(MAX_SEND_QUEUE_LEN);
 
-        let addr = stream.peer_addr()?.ip();
+        let PeerInfo {
+            mut addr,
+            local_hostname,
+            mut tcp_endpoints,
+        } = peer;
         let connection_type = conntype.clone();
         let mut tls_info = None;
 
-        match connection_type {
-            InternalConnectionType::Tls(tls_config) => {
+        if let Some(proxy) = &options.proxy_protocol {
+            if !proxy.trusts(addr) {
+                warn_untrusted_proxy(addr);
+                return Err(ConnectionError::ProtocolError(format!(
+                    "PROXY connection from untrusted source {}",
+                    addr
+                )));
+            }
+
+            let header = timeout(
+                PROXY_HEADER_TIMEOUT,
+                proxy_protocol::read_header(&mut stream),
+            )
+            .await
+            .map_err(|_| ConnectionError::ProtocolError("PROXY header timed out".to_string()))??;
+
+            if let Some(source) = header.source {
+                addr = source.ip().to_canonical();
+            }
+            // The TCP connection we can see is the proxy's, not the client's
+            tcp_endpoints = None;
+            // TLS terminated by the proxy counts as TLS, though we can't see the
+            // client's certificate
+            if header.tls {
+                tls_info = Some(TlsInfo { fingerprint: None });
+            }
+        }
+
+        let (tls_config, websocket) = match connection_type {
+            InternalConnectionType::Clear => (None, false),
+            InternalConnectionType::Tls(tls_config) => (Some(tls_config), false),
+            InternalConnectionType::WebSocket => (None, true),
+            InternalConnectionType::WebSocketTls(tls_config) => (Some(tls_config), true),
+            InternalConnectionType::Unix => (None, false),
+        };
+
+        match tls_config {
+            Some(tls_config) => {
+                // Take the current config; if it's replaced, that only affects later connections
+                let tls_config = Arc::clone(&tls_config.read().unwrap());
                 let tls_acceptor: tokio_rustls::TlsAcceptor = tls_config.into();
                 match tls_acceptor.accept(stream).await {
                     Ok(mut tls_stream) => {
@@ -53,9 +150,15 @@ impl InternalConnection {
 
                         tls_info = Some(TlsInfo { fingerprint });
 
-                        let conntask =
-                            ConnectionTask::new(id, tls_stream, control_recv, events.clone());
-                        tokio::spawn(conntask.run());
+                        Self::start_task(
+                            id,
+                            tls_stream,
+                            websocket,
+                            &options,
+                            control_recv,
+                            events.clone(),
+                        )
+                        .await?;
                     }
                     Err(err) => {
                         let _ = events
@@ -66,9 +169,16 @@ impl InternalConnection {
                     }
                 }
             }
-            InternalConnectionType::Clear => {
-                let conntask = ConnectionTask::new(id, stream, control_recv, events.clone());
-                tokio::spawn(conntask.run());
+            None => {
+                Self::start_task(
+                    id,
+                    stream,
+                    websocket,
+                    &options,
+                    control_recv,
+                    events.clone(),
+                )
+                .await?;
             }
         }
 
@@ -76,7 +186,11 @@ impl InternalConnection {
             id,
             remote_addr: addr,
             control_channel: control_send,
+            send_queue_len: SEND_QUEUE_LEN,
             tls_info,
+            websocket,
+            local_hostname,
+            tcp_endpoints,
         };
 
         if events
@@ -90,11 +204,63 @@ impl InternalConnection {
         Ok(())
     }
 
+    /// Spawn the task to run a connection over `stream`, once any transport-level
+    /// setup has been completed. For WebSocket connections this includes the
+    /// opening handshake.
+    async fn start_task<S>(
+        id: ConnectionId,
+        stream: S,
+        websocket: bool,
+        options: &ListenerOptions,
+        control_recv: Receiver<ConnectionControlDetail>,
+        events: Sender<InternalConnectionEventType>,
+    ) -> Result<(), ConnectionError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        if websocket {
+            let (stream, protocol) =
+                timeout(WEBSOCKET_HANDSHAKE_TIMEOUT, websocket::accept(stream))
+                    .await
+                    .map_err(|_| {
+                        ConnectionError::ProtocolError("WebSocket handshake timed out".to_string())
+                    })??;
+
+            let conntask = websocket::WebSocketConnectionTask::new(
+                id,
+                stream,
+                protocol,
+                control_recv,
+                events,
+                options.receive_limit,
+            );
+            tokio::spawn(conntask.run());
+        } else {
+            let conntask =
+                ConnectionTask::new(id, stream, control_recv, events, options.receive_limit);
+            tokio::spawn(conntask.run());
+        }
+        Ok(())
+    }
+
+    /// Pass a control message to the connection task, failing if it would exceed
+    /// the connection's send queue length
+    pub fn send_control(&self, msg: ConnectionControlDetail) -> Result<(), ConnectionError> {
+        let queued = self.control_channel.max_capacity() - self.control_channel.capacity();
+        if queued >= self.send_queue_len {
+            return Err(ConnectionError::SendQueueFull);
+        }
+        Ok(self.control_channel.try_send(msg)?)
+    }
+
     pub fn data(&self) -> ConnectionData {
         ConnectionData {
             id: self.id,
             remote_addr: self.remote_addr,
             tls_info: self.tls_info.clone(),
+            websocket: self.websocket,
+            local_hostname: self.local_hostname.clone(),
+            tcp_endpoints: self.tcp_endpoints,
         }
     }
 }
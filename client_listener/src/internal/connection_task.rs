@@ -1,17 +1,96 @@
 use crate::internal::*;
 use crate::*;
 
+use std::time::Duration;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    io::{
+        AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+        BufReader,
+    },
     select,
     sync::mpsc::{Receiver, Sender},
+    time::timeout,
 };
 
+/// How long to keep writing to a connection after a fatal error, so that the
+/// server's explanation reaches the client
+pub(crate) const FATAL_ERROR_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub(crate) struct ConnectionTask<S> {
     id: ConnectionId,
     conn: S,
     control_channel: Receiver<ConnectionControlDetail>,
     event_channel: Sender<InternalConnectionEventType>,
+    throttle: Option<ReceiveThrottle>,
+}
+
+/// Check a received line, without its line ending, against the separate limits
+/// on the length of its message tags and of the rest of the line
+pub(crate) fn check_line_length(line: &[u8]) -> Result<(), ConnectionError> {
+    let (tags, body) = match line.first() {
+        Some(b'@') => match line.iter().position(|&b| b == b' ') {
+            Some(space) => line.split_at(space + 1),
+            None => (line, &[][..]),
+        },
+        _ => (&[][..], line),
+    };
+
+    if tags.len() > MAX_TAGS_LENGTH || body.len() > MAX_BODY_LENGTH {
+        Err(ConnectionError::LineTooLong)
+    } else {
+        Ok(())
+    }
+}
+
+/// After a fatal error has been reported, stop reading but keep writing whatever
+/// the server sends, which usually explains the error, until it closes the
+/// connection or [`FATAL_ERROR_FLUSH_TIMEOUT`] has passed
+async fn flush_after_fatal_error<W>(
+    control_channel: &mut Receiver<ConnectionControlDetail>,
+    writer: &mut W,
+) where
+    W: AsyncWrite + Unpin,
+{
+    let _ = timeout(FATAL_ERROR_FLUSH_TIMEOUT, async {
+        while let Some(ConnectionControlDetail::Send(msg)) = control_channel.recv().await {
+            if writer.write_all(msg.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+        let _ = writer.flush().await;
+    })
+    .await;
+}
+
+/// Read a single line into `buf`, refusing to buffer more than [`MAX_LINE_LENGTH`]
+/// bytes. Returns `Ok(None)` at end of stream.
+///
+/// This is cancellation safe in the same way as `read_until`: if cancelled, any
+/// partial line is retained in `buf` and reading resumes on the next call.
+async fn read_line<R>(reader: &mut R, buf: &mut Vec<u8>) -> Result<Option<String>, ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    // Allow for the line ending on top of the maximum content length
+    let limit = (MAX_LINE_LENGTH + 2).saturating_sub(buf.len()) as u64;
+    reader.take(limit).read_until(b'\n', buf).await?;
+
+    // `read_until` stops at a line ending, at the length limit, or at end of stream
+    if !buf.ends_with(b"\n") && buf.len() >= MAX_LINE_LENGTH + 2 {
+        return Err(ConnectionError::LineTooLong);
+    }
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    let mut line = std::mem::take(buf);
+    while matches!(line.last(), Some(b'\r' | b'\n')) {
+        line.pop();
+    }
+    check_line_length(&line)?;
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|e| ConnectionError::IoError(e.to_string()))
 }
 
 impl<S> ConnectionTask<S>
@@ -23,19 +102,21 @@ where
         stream: S,
         control_channel: Receiver<ConnectionControlDetail>,
         event_channel: Sender<InternalConnectionEventType>,
+        receive_limit: Option<ReceiveLimitSettings>,
     ) -> Self {
         Self {
             id,
             conn: stream,
             control_channel,
             event_channel,
+            throttle: receive_limit.map(ReceiveThrottle::new),
         }
     }
 
     pub async fn run(mut self) {
         let (reader, mut writer) = tokio::io::split(self.conn);
-        let reader = BufReader::new(reader);
-        let mut lines = reader.lines();
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
         loop {
             select! {
                 control = self.control_channel.recv() => match control
@@ -48,17 +129,32 @@ where
                         }
                     }
                 },
-                message = lines.next_line() => match message {
-                    Ok(None) => { break; },
-                    Ok(Some(m)) => {
-                        if self.event_channel.send(InternalConnectionEventType::Event(InternalConnectionEvent::Message(self.id, m))).await.is_err() {
-                            tracing::error!("Error notifying socket message on connection {:?}", self.id);
+                message = read_line(&mut reader, &mut buf) => {
+                    let message = match message {
+                        Ok(Some(_)) if self.throttle.as_mut().is_some_and(|t| !t.take()) => Err(ConnectionError::ExcessFlood),
+                        m => m,
+                    };
+
+                    match message {
+                        Ok(None) => { break; },
+                        Ok(Some(m)) => {
+                            if self.event_channel.send(InternalConnectionEventType::Event(InternalConnectionEvent::Message(self.id, m))).await.is_err() {
+                                tracing::error!("Error notifying socket message on connection {:?}", self.id);
+                            }
                         }
-                    }
-                    Err(e) => {
-                        if self.event_channel.send(InternalConnectionEventType::Event(InternalConnectionEvent::ConnectionError(self.id, ConnectionError::from(e)))).await.is_err() {
-                            tracing::error!("Error notifying socket error on connection {:?}", self.id);
-                            return;
+                        Err(e) => {
+                            // Oversized lines and flooding are fatal, and the error has
+                            // already told the server why the connection is going away
+                            let fatal = matches!(e, ConnectionError::LineTooLong | ConnectionError::ExcessFlood);
+
+                            if self.event_channel.send(InternalConnectionEventType::Event(InternalConnectionEvent::ConnectionError(self.id, e))).await.is_err() {
+                                tracing::error!("Error notifying socket error on connection {:?}", self.id);
+                                return;
+                            }
+                            if fatal {
+                                flush_after_fatal_error(&mut self.control_channel, &mut writer).await;
+                                return;
+                            }
                         }
                     }
                 }
@@ -76,3 +172,54 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_and_body_limited_separately() {
+        let tags = format!("@{} ", "a".repeat(MAX_TAGS_LENGTH - 2));
+        let body = format!("PING :{}", "a".repeat(MAX_BODY_LENGTH - 6));
+
+        assert!(check_line_length(format!("{}{}", tags, body).as_bytes()).is_ok());
+        // Unused tag space can't be used for a longer body, or vice versa
+        assert!(check_line_length(format!("{}a", body).as_bytes()).is_err());
+        assert!(check_line_length(format!("@a{}{}", &tags[1..], "PING").as_bytes()).is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_overlong_lines() {
+        let tags = format!("@{} ", "a".repeat(MAX_TAGS_LENGTH - 2));
+        let mut input =
+            format!("{}PING :{}\r\n", tags, "a".repeat(MAX_BODY_LENGTH - 6)).into_bytes();
+        input.extend(format!("PING :{}\r\n", "a".repeat(MAX_LINE_LENGTH)).into_bytes());
+
+        let mut reader = &input[..];
+        let mut buf = Vec::new();
+
+        let first = read_line(&mut reader, &mut buf).await.unwrap().unwrap();
+        assert_eq!(first.len(), MAX_LINE_LENGTH);
+
+        assert!(matches!(
+            read_line(&mut reader, &mut buf).await,
+            Err(ConnectionError::LineTooLong)
+        ));
+    }
+
+    #[tokio::test]
+    async fn final_line_without_terminator() {
+        let mut reader = &b"NICK foo\r\nQUIT"[..];
+        let mut buf = Vec::new();
+
+        assert_eq!(
+            read_line(&mut reader, &mut buf).await.unwrap().as_deref(),
+            Some("NICK foo")
+        );
+        assert_eq!(
+            read_line(&mut reader, &mut buf).await.unwrap().as_deref(),
+            Some("QUIT")
+        );
+        assert!(read_line(&mut reader, &mut buf).await.unwrap().is_none());
+    }
+}
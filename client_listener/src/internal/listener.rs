@@ -2,12 +2,118 @@ use crate::internal::*;
 use crate::*;
 
 use tokio::{
-    net::TcpListener,
+    net::{TcpListener, TcpStream, UnixListener, UnixStream},
     select,
     sync::mpsc::{channel, Receiver, Sender},
 };
 
-use std::net::SocketAddr;
+use nix::unistd::{chown, Gid, Group, Uid, User};
+use std::{
+    fs, io,
+    os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// A bound listening socket of either supported kind
+enum SocketListener {
+    Tcp(TcpListener),
+    Unix(UnixListener, PathBuf),
+}
+
+/// A stream accepted from a [`SocketListener`]
+enum AcceptedStream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl SocketListener {
+    async fn bind(address: &ListenerAddress, options: &ListenerOptions) -> io::Result<Self> {
+        match address {
+            ListenerAddress::Tcp(addr) => Ok(Self::Tcp(TcpListener::bind(addr).await?)),
+            ListenerAddress::Unix(path) => {
+                let settings = options.unix_socket.clone().unwrap_or_default();
+                Ok(Self::Unix(bind_unix(path, &settings)?, path.clone()))
+            }
+        }
+    }
+
+    async fn accept(&self) -> io::Result<AcceptedStream> {
+        match self {
+            Self::Tcp(listener) => Ok(AcceptedStream::Tcp(listener.accept().await?.0)),
+            Self::Unix(listener, _) => Ok(AcceptedStream::Unix(listener.accept().await?.0)),
+        }
+    }
+}
+
+impl Drop for SocketListener {
+    fn drop(&mut self) {
+        if let Self::Unix(_, path) = self {
+            if let Err(e) = fs::remove_file(path) {
+                tracing::warn!("Couldn't remove socket {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+fn not_found(what: &str, name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("No such {}: {}", what, name),
+    )
+}
+
+fn lookup_user(name: &str) -> io::Result<Uid> {
+    if let Ok(id) = name.parse() {
+        return Ok(Uid::from_raw(id));
+    }
+    User::from_name(name)?
+        .map(|u| u.uid)
+        .ok_or_else(|| not_found("user", name))
+}
+
+fn lookup_group(name: &str) -> io::Result<Gid> {
+    if let Ok(id) = name.parse() {
+        return Ok(Gid::from_raw(id));
+    }
+    Group::from_name(name)?
+        .map(|g| g.gid)
+        .ok_or_else(|| not_found("group", name))
+}
+
+/// Bind a Unix socket at `path`, then apply the ownership and permissions in `settings`.
+///
+/// The socket's directory is created if it doesn't exist, accessible only to its owner
+/// and group, so that the socket isn't reachable before its permissions are set.
+fn bind_unix(path: &Path, settings: &UnixSocketSettings) -> io::Result<UnixListener> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o750)
+            .create(parent)?;
+    }
+
+    // A socket left behind by a previous run would prevent binding. Anything
+    // other than a socket is left alone, and reported by the bind attempt.
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            fs::remove_file(path)?;
+        }
+    }
+
+    let listener = UnixListener::bind(path)?;
+
+    let owner = settings.owner.as_deref().map(lookup_user).transpose()?;
+    let group = settings.group.as_deref().map(lookup_group).transpose()?;
+    if owner.is_some() || group.is_some() {
+        chown(path, owner, group)?;
+    }
+    if let Some(mode) = settings.mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok(listener)
+}
 
 pub(crate) struct Listener {
     //address: SocketAddr,
@@ -19,19 +125,18 @@ pub(crate) struct Listener {
 
 impl Listener {
     pub fn new(
-        listener_id: ListenerId,
-        address: SocketAddr,
+        data: ListenerData,
         connection_type: InternalConnectionType,
         event_channel: Sender<InternalConnectionEventType>,
     ) -> Self {
         let (control_send, control_receive) = channel(128);
+        let listener_id = data.id;
 
         tokio::spawn(Self::listen_and_log(
             event_channel,
             control_receive,
-            address,
+            data,
             connection_type,
-            listener_id,
         ));
 
         Self {
@@ -45,28 +150,27 @@ impl Listener {
     async fn listen_and_log(
         event_channel: Sender<InternalConnectionEventType>,
         control_channel: Receiver<ListenerControlDetail>,
-        address: SocketAddr,
+        data: ListenerData,
         connection_type: InternalConnectionType,
-        listener_id: ListenerId,
     ) {
-        if let Err(e) = match Self::listen_loop(
+        let listener_id = data.id;
+
+        let event = match Self::listen_loop(
             event_channel.clone(),
             control_channel,
-            address,
+            data,
             connection_type,
-            listener_id,
         )
         .await
         {
-            Ok(_) => return,
-            Err(e) => {
-                event_channel
-                    .send(InternalConnectionEventType::Event(
-                        InternalConnectionEvent::ListenerError(listener_id, e.into()),
-                    ))
-                    .await
-            }
-        } {
+            Ok(_) => InternalConnectionEvent::ListenerClosed(listener_id),
+            Err(e) => InternalConnectionEvent::ListenerError(listener_id, e.into()),
+        };
+
+        if let Err(e) = event_channel
+            .send(InternalConnectionEventType::Event(event))
+            .await
+        {
             tracing::error!("Error in listener loop: {}", e);
         }
     }
@@ -74,27 +178,65 @@ impl Listener {
     async fn listen_loop(
         event_channel: Sender<InternalConnectionEventType>,
         mut control_channel: Receiver<ListenerControlDetail>,
-        address: SocketAddr,
+        data: ListenerData,
         connection_type: InternalConnectionType,
-        listener_id: ListenerId,
     ) -> Result<(), std::io::Error> {
-        let listener = TcpListener::bind(address).await?;
-        let id_gen = ConnectionIdGenerator::new(listener_id, 1);
+        let listener = SocketListener::bind(&data.addr, &data.options).await?;
+        let id_gen = ConnectionIdGenerator::new(data.id, 1);
+        let options = Arc::new(data.options.clone());
+        let unix_settings = Arc::new(data.options.unix_socket.clone().unwrap_or_default());
+
+        // Only report the listener once it's actually bound, so that the parent's
+        // view of active listeners doesn't include ones that failed to start
+        if event_channel
+            .send(InternalConnectionEventType::Event(
+                InternalConnectionEvent::NewListener(data),
+            ))
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
 
         loop {
             select! {
                 res = listener.accept() => {
                     match res {
-                        Ok((stream,_)) =>
+                        Ok(stream) =>
                         {
                             let id = id_gen.next();
                             let connection_type = connection_type.clone();
+                            let options = options.clone();
+                            let unix_settings = unix_settings.clone();
                             let event_channel = event_channel.clone();
 
                             tokio::spawn(async move {
-                                if let Err(e) = InternalConnection::create_and_send(id, stream, connection_type, event_channel).await
+                                let result = match stream {
+                                    AcceptedStream::Tcp(stream) => match (stream.local_addr(), stream.peer_addr()) {
+                                        (Ok(local), Ok(remote)) => {
+                                            let peer = PeerInfo {
+                                                addr: remote.ip(),
+                                                local_hostname: None,
+                                                tcp_endpoints: Some(TcpEndpoints { local, remote }),
+                                            };
+                                            InternalConnection::create_and_send(id, stream, peer, connection_type, options, event_channel).await
+                                        }
+                                        (Err(e), _) | (_, Err(e)) => Err(e.into()),
+                                    },
+                                    AcceptedStream::Unix(stream) => {
+                                        let peer = PeerInfo {
+                                            addr: unix_settings.remote_addr,
+                                            local_hostname: Some(unix_settings.hostname.clone()),
+                                            tcp_endpoints: None,
+                                        };
+                                        InternalConnection::create_and_send(id, stream, peer, connection_type, options, event_channel).await
+                                    }
+                                };
+                                // Setup failures are usually caused by the client, and
+                                // so aren't worth more than a debug message
+                                if let Err(e) = result
                                 {
-                                    tracing::error!("Error creating connection: {}", e);
+                                    tracing::debug!("Error creating connection: {}", e);
                                 }
                             });
                             continue
@@ -126,3 +268,26 @@ impl Drop for Listener {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    #[tokio::test]
+    async fn unix_socket_replaces_stale_socket_and_sets_mode() {
+        let dir = std::env::temp_dir().join(format!("sable-listener-{}", std::process::id()));
+        let path = dir.join("ircd.sock");
+        let settings: UnixSocketSettings = serde_json::from_str(r#"{ "mode": "600" }"#).unwrap();
+
+        let first = bind_unix(&path, &settings).unwrap();
+        drop(first);
+        assert!(fs::symlink_metadata(&path).unwrap().file_type().is_socket());
+
+        let _second = bind_unix(&path, &settings).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, 0o750);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
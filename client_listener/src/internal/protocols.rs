@@ -4,13 +4,19 @@ use crate::protocols::*;
 
 use rustls::ServerConfig;
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+
+/// The TLS configuration used by the worker process. Listeners share a single
+/// instance, so that certificates can be replaced without recreating them.
+pub type SharedTlsConfig = Arc<RwLock<Arc<ServerConfig>>>;
 
 #[derive(Clone)]
 pub enum InternalConnectionType {
     Clear,
-    Tls(Arc<ServerConfig>),
+    Tls(SharedTlsConfig),
+    WebSocket,
+    WebSocketTls(SharedTlsConfig),
+    Unix,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,7 +27,7 @@ pub enum ConnectionControlDetail {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ListenerControlDetail {
-    Add(SocketAddr, ConnectionType),
+    Add(ListenerAddress, ConnectionType, ListenerOptions),
     Close,
 }
 
@@ -32,13 +38,8 @@ pub enum ControlMessage {
     LoadTlsSettings(TlsSettings),
     Shutdown,
     SaveForUpgrade,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ListenerData {
-    pub id: ListenerId,
-    pub addr: SocketAddr,
-    pub conn_type: ConnectionType,
+    /// Change the number of outgoing lines that may be queued for a connection
+    SetSendQueueLength(ConnectionId, usize),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -50,7 +51,8 @@ pub enum InternalConnectionEvent {
     NewListener(ListenerData),
     ListenerError(ListenerId, ListenerError),
     ListenerClosed(ListenerId),
-    BadTlsConfig,
+    TlsSettingsLoaded,
+    BadTlsConfig(ListenerError),
     CommunicationError,
 }
 
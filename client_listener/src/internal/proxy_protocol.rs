@@ -0,0 +1,203 @@
+//! Parsing for the HAProxy PROXY protocol header, versions 1 and 2, as
+//! described in <https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt>
+
+use crate::*;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The fixed prefix of a version 2 header
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Maximum length of a version 1 header, including the trailing CR-LF
+const V1_MAX_LENGTH: usize = 107;
+
+/// Maximum length we accept for the variable part of a version 2 header
+const V2_MAX_LENGTH: usize = 2048;
+
+const V2_COMMAND_LOCAL: u8 = 0x0;
+const V2_COMMAND_PROXY: u8 = 0x1;
+
+const V2_FAMILY_TCP4: u8 = 0x11;
+const V2_FAMILY_TCP6: u8 = 0x21;
+
+const PP2_TYPE_SSL: u8 = 0x20;
+const PP2_CLIENT_SSL: u8 = 0x01;
+
+/// The information carried by a PROXY header
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ProxyHeader {
+    /// The original client address, if the proxy supplied one. This is absent
+    /// for health checks and other connections originated by the proxy itself.
+    pub source: Option<SocketAddr>,
+    /// Whether the client connected to the proxy using TLS
+    pub tls: bool,
+}
+
+fn proxy_error(msg: &str) -> ConnectionError {
+    ConnectionError::ProtocolError(format!("PROXY header: {}", msg))
+}
+
+/// Read a PROXY header from the start of `stream`, consuming exactly the bytes
+/// belonging to the header.
+pub(crate) async fn read_header<R>(stream: &mut R) -> Result<ProxyHeader, ConnectionError>
+where
+    R: AsyncRead + Unpin,
+{
+    // The shortest valid v1 header ("PROXY UNKNOWN\r\n") is longer than the v2
+    // signature, so it's always safe to read this much
+    let mut prefix = [0u8; 12];
+    stream.read_exact(&mut prefix).await?;
+
+    if prefix == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if prefix.starts_with(b"PROXY ") {
+        let mut line = prefix.to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= V1_MAX_LENGTH {
+                return Err(proxy_error("line too long"));
+            }
+            line.push(stream.read_u8().await?);
+        }
+        parse_v1(&line)
+    } else {
+        Err(proxy_error("missing"))
+    }
+}
+
+fn parse_v1(line: &[u8]) -> Result<ProxyHeader, ConnectionError> {
+    let line = std::str::from_utf8(line).map_err(|_| proxy_error("invalid encoding"))?;
+    let mut parts = line.trim_end_matches("\r\n").split(' ').skip(1);
+
+    match parts.next() {
+        Some("UNKNOWN") => Ok(ProxyHeader::default()),
+        Some("TCP4") | Some("TCP6") => {
+            let (Some(source), Some(_dest), Some(port), Some(_dest_port), None) = (
+                parts.next(),
+                parts.next(),
+                parts.next(),
+                parts.next(),
+                parts.next(),
+            ) else {
+                return Err(proxy_error("wrong number of fields"));
+            };
+
+            let ip: IpAddr = source.parse().map_err(|_| proxy_error("invalid address"))?;
+            let port: u16 = port.parse().map_err(|_| proxy_error("invalid port"))?;
+
+            Ok(ProxyHeader {
+                source: Some(SocketAddr::new(ip, port)),
+                tls: false,
+            })
+        }
+        _ => Err(proxy_error("unknown protocol")),
+    }
+}
+
+async fn read_v2<R>(stream: &mut R) -> Result<ProxyHeader, ConnectionError>
+where
+    R: AsyncRead + Unpin,
+{
+    let version_command = stream.read_u8().await?;
+    let family = stream.read_u8().await?;
+    let length = stream.read_u16().await? as usize;
+
+    if version_command >> 4 != 2 {
+        return Err(proxy_error("unsupported version"));
+    }
+    if length > V2_MAX_LENGTH {
+        return Err(proxy_error("too long"));
+    }
+
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).await?;
+
+    parse_v2(version_command & 0x0f, family, &body)
+}
+
+fn parse_v2(command: u8, family: u8, body: &[u8]) -> Result<ProxyHeader, ConnectionError> {
+    let (source, tlvs) = match command {
+        V2_COMMAND_LOCAL => return Ok(ProxyHeader::default()),
+        V2_COMMAND_PROXY => match family {
+            V2_FAMILY_TCP4 if body.len() >= 12 => {
+                let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&body[0..4]).unwrap());
+                let port = u16::from_be_bytes([body[8], body[9]]);
+                (Some(SocketAddr::new(ip.into(), port)), &body[12..])
+            }
+            V2_FAMILY_TCP6 if body.len() >= 36 => {
+                let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&body[0..16]).unwrap());
+                let port = u16::from_be_bytes([body[32], body[33]]);
+                (Some(SocketAddr::new(ip.into(), port)), &body[36..])
+            }
+            V2_FAMILY_TCP4 | V2_FAMILY_TCP6 => return Err(proxy_error("truncated address")),
+            // Unspecified or non-TCP families carry nothing we can use
+            _ => (None, &[][..]),
+        },
+        _ => return Err(proxy_error("unknown command")),
+    };
+
+    let mut tls = false;
+    let mut remaining = tlvs;
+    while remaining.len() >= 3 {
+        let tlv_type = remaining[0];
+        let tlv_len = u16::from_be_bytes([remaining[1], remaining[2]]) as usize;
+        let Some(value) = remaining.get(3..3 + tlv_len) else {
+            return Err(proxy_error("truncated TLV"));
+        };
+
+        if tlv_type == PP2_TYPE_SSL {
+            tls = value
+                .first()
+                .map_or(false, |client| client & PP2_CLIENT_SSL != 0);
+        }
+
+        remaining = &remaining[3 + tlv_len..];
+    }
+
+    Ok(ProxyHeader { source, tls })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn v1_header() {
+        let mut input = &b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 6667\r\nNICK foo\r\n"[..];
+        let header = read_header(&mut input).await.unwrap();
+
+        assert_eq!(header.source, Some("192.0.2.1:56324".parse().unwrap()));
+        assert!(!header.tls);
+        assert_eq!(input, b"NICK foo\r\n");
+    }
+
+    #[tokio::test]
+    async fn v2_header_with_ssl_tlv() {
+        let mut input = V2_SIGNATURE.to_vec();
+        input.extend([0x21, V2_FAMILY_TCP6]);
+
+        let mut body = Vec::new();
+        body.extend("2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        body.extend("2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        body.extend(56324u16.to_be_bytes());
+        body.extend(6697u16.to_be_bytes());
+        body.extend([PP2_TYPE_SSL, 0, 5, PP2_CLIENT_SSL, 0, 0, 0, 0]);
+
+        input.extend((body.len() as u16).to_be_bytes());
+        input.extend(body);
+        input.extend(b"NICK foo\r\n");
+
+        let mut input = &input[..];
+        let header = read_header(&mut input).await.unwrap();
+
+        assert_eq!(header.source, Some("[2001:db8::1]:56324".parse().unwrap()));
+        assert!(header.tls);
+        assert_eq!(input, b"NICK foo\r\n");
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_header() {
+        let mut input = &b"NICK foo\r\nUSER foo 0 * :foo\r\n"[..];
+        assert!(read_header(&mut input).await.is_err());
+    }
+}
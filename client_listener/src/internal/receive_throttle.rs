@@ -0,0 +1,65 @@
+use crate::*;
+
+use std::time::Instant;
+
+/// Token bucket limiting the rate at which lines are accepted from a single
+/// connection, before they are forwarded to the main process.
+pub(crate) struct ReceiveThrottle {
+    settings: ReceiveLimitSettings,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl ReceiveThrottle {
+    pub fn new(settings: ReceiveLimitSettings) -> Self {
+        Self {
+            settings,
+            tokens: settings.burst as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Consume a token for one received line. Returns false if the bucket is
+    /// empty, i.e. the client has exceeded its permitted rate.
+    pub fn take(&mut self) -> bool {
+        self.take_at(Instant::now())
+    }
+
+    fn take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        self.tokens = (self.tokens + elapsed * self.settings.lines_per_second as f64)
+            .min(self.settings.burst as f64);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_then_refill() {
+        let mut throttle = ReceiveThrottle::new(ReceiveLimitSettings {
+            lines_per_second: 2,
+            burst: 3,
+        });
+        let start = throttle.last_refill;
+
+        assert!(throttle.take_at(start));
+        assert!(throttle.take_at(start));
+        assert!(throttle.take_at(start));
+        assert!(!throttle.take_at(start));
+
+        let later = start + std::time::Duration::from_millis(500);
+        assert!(throttle.take_at(later));
+        assert!(!throttle.take_at(later));
+    }
+}
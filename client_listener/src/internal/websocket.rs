@@ -0,0 +1,382 @@
+//! WebSocket transport for client connections, as described by the IRCv3
+//! WebSocket specification. Each IRC line is carried in a single WebSocket
+//! message, without the trailing CR-LF.
+
+use crate::internal::*;
+use crate::*;
+
+use futures::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, StreamExt,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    select,
+    sync::mpsc::{Receiver, Sender},
+    time::timeout,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue, StatusCode},
+        protocol::WebSocketConfig,
+        Error as WsError, Message,
+    },
+    WebSocketStream,
+};
+
+/// Maximum size of a single (possibly fragmented) incoming message. Clients
+/// shouldn't send line endings, but some do.
+const MAX_MESSAGE_SIZE: usize = MAX_LINE_LENGTH + 2;
+
+/// The subprotocol negotiated for a WebSocket connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WebSocketProtocol {
+    /// `text.ircv3.net`: lines are sent in text frames and must be valid UTF-8
+    Text,
+    /// `binary.ircv3.net`: lines are sent in binary frames
+    Binary,
+}
+
+impl WebSocketProtocol {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text.ircv3.net" => Some(Self::Text),
+            "binary.ircv3.net" => Some(Self::Binary),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Text => "text.ircv3.net",
+            Self::Binary => "binary.ircv3.net",
+        }
+    }
+
+    fn message(&self, line: &str) -> Message {
+        match self {
+            Self::Text => Message::Text(line.to_string()),
+            Self::Binary => Message::Binary(line.as_bytes().to_vec()),
+        }
+    }
+}
+
+fn protocol_error(msg: impl Into<String>) -> ConnectionError {
+    ConnectionError::ProtocolError(msg.into())
+}
+
+impl From<WsError> for ConnectionError {
+    fn from(e: WsError) -> Self {
+        match e {
+            WsError::Capacity(_) => Self::LineTooLong,
+            WsError::Io(e) => Self::IoError(e.to_string()),
+            WsError::ConnectionClosed | WsError::AlreadyClosed => Self::Closed,
+            e => protocol_error(e.to_string()),
+        }
+    }
+}
+
+/// Choose the first subprotocol offered by the client which we support. If the
+/// client offers none at all, the connection proceeds without one; if it offers
+/// only ones we don't support, it is refused.
+fn choose_protocol<'a>(
+    offered: impl IntoIterator<Item = &'a str>,
+) -> Result<Option<WebSocketProtocol>, ()> {
+    let mut any_offered = false;
+    for name in offered {
+        any_offered = true;
+        if let Some(protocol) = WebSocketProtocol::from_name(name) {
+            return Ok(Some(protocol));
+        }
+    }
+    if any_offered {
+        Err(())
+    } else {
+        Ok(None)
+    }
+}
+
+/// Perform the server side of the WebSocket opening handshake.
+///
+/// If the client offers none of the IRCv3 subprotocols, the connection proceeds
+/// without one and is treated as `text.ircv3.net`.
+pub(crate) async fn accept<S>(
+    stream: S,
+) -> Result<(WebSocketStream<S>, WebSocketProtocol), ConnectionError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut protocol = None;
+
+    let negotiate = |request: &Request, mut response: Response| {
+        let offered = request
+            .headers()
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim);
+
+        match choose_protocol(offered) {
+            Ok(chosen) => {
+                if let Some(chosen) = chosen {
+                    response.headers_mut().insert(
+                        SEC_WEBSOCKET_PROTOCOL,
+                        HeaderValue::from_static(chosen.name()),
+                    );
+                }
+                protocol = chosen;
+                Ok(response)
+            }
+            Err(()) => {
+                let mut error = ErrorResponse::new(Some(
+                    "No supported WebSocket subprotocol offered".to_string(),
+                ));
+                *error.status_mut() = StatusCode::BAD_REQUEST;
+                Err(error)
+            }
+        }
+    };
+
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_SIZE),
+        max_frame_size: Some(MAX_MESSAGE_SIZE),
+        ..Default::default()
+    };
+
+    let stream =
+        tokio_tungstenite::accept_hdr_async_with_config(stream, negotiate, Some(config)).await?;
+
+    Ok((stream, protocol.unwrap_or(WebSocketProtocol::Text)))
+}
+
+/// Extract the IRC line from a complete data message. Returns `Ok(None)` for
+/// control messages, which are answered by the WebSocket implementation itself.
+fn message_line(
+    message: Message,
+    protocol: WebSocketProtocol,
+) -> Result<Option<String>, ConnectionError> {
+    let mut line = match message {
+        Message::Text(text) => text,
+        Message::Binary(data) if protocol == WebSocketProtocol::Text => {
+            String::from_utf8(data).map_err(|_| protocol_error("invalid UTF-8 in text message"))?
+        }
+        Message::Binary(data) => String::from_utf8_lossy(&data).into_owned(),
+        Message::Close(_) => return Err(ConnectionError::Closed),
+        Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => return Ok(None),
+    };
+
+    while line.ends_with(['\r', '\n']) {
+        line.pop();
+    }
+    check_line_length(line.as_bytes())?;
+    Ok(Some(line))
+}
+
+/// Equivalent of [`ConnectionTask`] for WebSocket connections, run once the
+/// opening handshake has completed.
+pub(crate) struct WebSocketConnectionTask<S> {
+    id: ConnectionId,
+    writer: SplitSink<WebSocketStream<S>, Message>,
+    reader: SplitStream<WebSocketStream<S>>,
+    protocol: WebSocketProtocol,
+    control_channel: Receiver<ConnectionControlDetail>,
+    event_channel: Sender<InternalConnectionEventType>,
+    throttle: Option<ReceiveThrottle>,
+}
+
+impl<S> WebSocketConnectionTask<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    pub fn new(
+        id: ConnectionId,
+        stream: WebSocketStream<S>,
+        protocol: WebSocketProtocol,
+        control_channel: Receiver<ConnectionControlDetail>,
+        event_channel: Sender<InternalConnectionEventType>,
+        receive_limit: Option<ReceiveLimitSettings>,
+    ) -> Self {
+        let (writer, reader) = stream.split();
+        Self {
+            id,
+            writer,
+            reader,
+            protocol,
+            control_channel,
+            event_channel,
+            throttle: receive_limit.map(ReceiveThrottle::new),
+        }
+    }
+
+    async fn send_event(&self, event: InternalConnectionEvent) -> bool {
+        self.event_channel
+            .send(InternalConnectionEventType::Event(event))
+            .await
+            .is_ok()
+    }
+
+    /// As for plain connections, keep sending what the server sends, usually an
+    /// explanation of the error, until it closes the connection or
+    /// [`FATAL_ERROR_FLUSH_TIMEOUT`] has passed
+    async fn flush_after_fatal_error(&mut self) {
+        let protocol = self.protocol;
+        let writer = &mut self.writer;
+        let control_channel = &mut self.control_channel;
+
+        let _ = timeout(FATAL_ERROR_FLUSH_TIMEOUT, async {
+            while let Some(ConnectionControlDetail::Send(msg)) = control_channel.recv().await {
+                for line in msg.split(['\r', '\n']).filter(|l| !l.is_empty()) {
+                    if writer.feed(protocol.message(line)).await.is_err() {
+                        return;
+                    }
+                }
+                if writer.flush().await.is_err() {
+                    return;
+                }
+            }
+            let _ = writer.send(Message::Close(None)).await;
+        })
+        .await;
+    }
+
+    pub async fn run(mut self) {
+        let protocol = self.protocol;
+
+        loop {
+            select! {
+                control = self.control_channel.recv() => match control
+                {
+                    None => { break; },
+                    Some(ConnectionControlDetail::Close) => {
+                        let _ = self.writer.send(Message::Close(None)).await;
+                        break;
+                    },
+                    Some(ConnectionControlDetail::Send(msg)) => {
+                        let mut lines = futures::stream::iter(
+                            msg.split(['\r', '\n'])
+                                .filter(|l| !l.is_empty())
+                                .map(|l| Ok(protocol.message(l)))
+                        );
+                        if self.writer.send_all(&mut lines).await.is_err() {
+                            break;
+                        }
+                    }
+                },
+                message = self.reader.next() => {
+                    let line = match message {
+                        None => break,
+                        Some(message) => message
+                            .map_err(ConnectionError::from)
+                            .and_then(|m| message_line(m, protocol)),
+                    };
+                    let line = match line {
+                        Ok(Some(_)) if self.throttle.as_mut().is_some_and(|t| !t.take()) => Err(ConnectionError::ExcessFlood),
+                        l => l,
+                    };
+
+                    match line {
+                        Ok(None) => (),
+                        Ok(Some(line)) => {
+                            if !self.send_event(InternalConnectionEvent::Message(self.id, line)).await {
+                                tracing::error!("Error notifying socket message on connection {:?}", self.id);
+                            }
+                        }
+                        Err(ConnectionError::Closed) => {
+                            break;
+                        }
+                        Err(e) => {
+                            // As for plain connections, oversized lines and flooding are fatal
+                            let fatal = matches!(e, ConnectionError::LineTooLong | ConnectionError::ExcessFlood);
+
+                            if !self.send_event(InternalConnectionEvent::ConnectionError(self.id, e)).await {
+                                tracing::error!("Error notifying socket error on connection {:?}", self.id);
+                                return;
+                            }
+                            if fatal {
+                                self.flush_after_fatal_error().await;
+                                return;
+                            }
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        if !self
+            .send_event(InternalConnectionEvent::ConnectionError(
+                self.id,
+                ConnectionError::Closed,
+            ))
+            .await
+        {
+            tracing::error!("Error notifying connection closed on {:?}", self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    #[test]
+    fn negotiates_first_supported_subprotocol() {
+        assert_eq!(
+            choose_protocol(["chat", "binary.ircv3.net", "text.ircv3.net"]),
+            Ok(Some(WebSocketProtocol::Binary))
+        );
+        assert_eq!(choose_protocol(std::iter::empty()), Ok(None));
+    }
+
+    #[test]
+    fn rejects_unsupported_subprotocols() {
+        assert_eq!(choose_protocol(["chat"]), Err(()));
+    }
+
+    #[test]
+    fn strips_line_endings() {
+        assert_eq!(
+            message_line(
+                Message::Text("PRIVMSG #chan :hello\r\n".to_string()),
+                WebSocketProtocol::Text
+            )
+            .unwrap()
+            .as_deref(),
+            Some("PRIVMSG #chan :hello")
+        );
+        assert!(message_line(Message::Binary(vec![0xff]), WebSocketProtocol::Text).is_err());
+        assert!(
+            message_line(Message::Ping(Vec::new()), WebSocketProtocol::Text)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn handshake_negotiates_protocol() {
+        let (client, server) = tokio::io::duplex(4096);
+
+        let mut request = "ws://irc.example.com/".into_client_request().unwrap();
+        request.headers_mut().insert(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static("chat, binary.ircv3.net"),
+        );
+
+        let (accepted, connected) = tokio::join!(
+            accept(server),
+            tokio_tungstenite::client_async(request, client)
+        );
+
+        let (_, protocol) = accepted.unwrap();
+        assert_eq!(protocol, WebSocketProtocol::Binary);
+
+        let (_, response) = connected.unwrap();
+        assert_eq!(
+            response.headers().get(SEC_WEBSOCKET_PROTOCOL).unwrap(),
+            "binary.ircv3.net"
+        );
+    }
+}
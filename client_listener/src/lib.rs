@@ -10,13 +10,33 @@
 //! Once a listener has been created, new connections and any events on existing
 //! connections will be sent via the provided event channel.
 
-/// Maximum serialised size of a client message
-pub const MAX_MSG_SIZE: u64 = 1024;
+/// Maximum length of the message tags section of a line received from a client,
+/// including the leading `@` and trailing space, as allowed by IRCv3.
+pub const MAX_TAGS_LENGTH: usize = 8191;
+
+/// Maximum length of the rest of a line received from a client: the 512 bytes
+/// permitted by RFC 1459. Clients aren't required to fit the line ending in this.
+pub const MAX_BODY_LENGTH: usize = 512;
+
+/// Maximum length of a line received from a client, excluding the line ending.
+/// The tags and the rest of the line are also limited separately, by
+/// [`MAX_TAGS_LENGTH`] and [`MAX_BODY_LENGTH`].
+pub const MAX_LINE_LENGTH: usize = MAX_TAGS_LENGTH + MAX_BODY_LENGTH;
+
+/// Maximum serialised size of a client message. This must be large enough to
+/// hold a line of [`MAX_LINE_LENGTH`] bytes.
+pub const MAX_MSG_SIZE: u64 = 16384;
 
 /// Maximum serialised size of a control message
 /// NB: this needs to include the size of the TLS cert chain
 pub const MAX_CONTROL_SIZE: u64 = 10485760;
 
+/// Version of the protocol spoken between a [`ListenerCollection`] and its worker
+/// process. This must be increased whenever any type exchanged between them changes,
+/// so that a server resuming after an upgrade can tell whether it is able to keep
+/// using a worker process started by an earlier version.
+pub const IPC_VERSION: u32 = 1;
+
 pub mod id;
 pub use id::*;
 
@@ -45,6 +65,8 @@ mod internal {
     pub mod listener;
     pub(crate) use listener::*;
     pub mod client_verifier;
+    pub mod proxy_protocol;
+    pub mod websocket;
 }
 
 pub use internal::ControlMessage;
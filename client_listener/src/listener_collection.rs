@@ -2,18 +2,22 @@ use crate::internal::*;
 use crate::*;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     convert::TryInto,
     env::current_exe,
     io,
     net::SocketAddr,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
 use sable_ipc::{channel as ipc_channel, Receiver as IpcReceiver, Sender as IpcSender};
 use tokio::{
     select,
-    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
     task,
     task::JoinHandle,
 };
@@ -22,8 +26,11 @@ use std::os::unix::{io::RawFd, process::CommandExt};
 use std::process::{Child, Command};
 
 use nix::{
-    sys::wait::{waitpid, WaitPidFlag, WaitStatus},
-    unistd::Pid,
+    sys::{
+        signal::{kill, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{close, Pid},
 };
 use thiserror::Error;
 
@@ -35,9 +42,50 @@ pub struct SavedListenerCollection {
     event_receiver: RawFd,
     id_gen: ListenerIdGenerator,
     connection_data: HashMap<ConnectionId, ConnectionData>,
+    #[serde(default)]
+    listeners: Vec<ListenerData>,
     child_pid: i32,
+    /// The [`IPC_VERSION`](crate::IPC_VERSION) spoken by the worker process. State
+    /// saved before this was recorded has version zero.
+    #[serde(default)]
+    ipc_version: u32,
 }
 
+impl SavedListenerCollection {
+    /// Whether the worker process described by this state speaks the same IPC protocol
+    /// as this build, and so can be [resumed](ListenerCollection::resume)
+    pub fn is_compatible(&self) -> bool {
+        self.ipc_version == crate::IPC_VERSION
+    }
+
+    /// Terminate the worker process without communicating with it, and close our end
+    /// of its IPC channels
+    fn terminate_worker(&self) {
+        let pid = Pid::from_raw(self.child_pid);
+        if let Err(e) = kill(pid, Signal::SIGTERM) {
+            tracing::warn!("Couldn't terminate listener process {}: {}", pid, e);
+        } else {
+            let _ = waitpid(pid, None);
+        }
+        let _ = close(self.control_sender);
+        let _ = close(self.event_receiver);
+    }
+}
+
+/// The listeners known to be active, and those whose creators are waiting to hear
+/// whether they started, shared with the communication task
+#[derive(Default)]
+struct ListenerState {
+    active: HashMap<ListenerId, ListenerData>,
+    pending: HashMap<ListenerId, oneshot::Sender<Result<(), ListenerError>>>,
+    /// One entry for each set of TLS settings sent to the worker process and not yet
+    /// acknowledged, in the order they were sent, holding the sender for any caller
+    /// waiting for the result
+    pending_tls: VecDeque<Option<oneshot::Sender<Result<(), ListenerError>>>>,
+}
+
+type ActiveListeners = Arc<Mutex<ListenerState>>;
+
 type CommResult = std::io::Result<(
     IpcSender<ControlMessage>,
     IpcReceiver<InternalConnectionEvent>,
@@ -71,6 +119,7 @@ pub struct ListenerCollection {
     control_sender: UnboundedSender<ControlMessage>,
     comm_task: JoinHandle<CommResult>,
     connection_data: HashMap<ConnectionId, ConnectionData>,
+    listeners: ActiveListeners,
     // We can't reconstruct the Child after save/resume, so we have to do without then
     child_process: Option<Child>,
     child_pid: Pid,
@@ -141,6 +190,7 @@ impl ListenerCollection {
         };
 
         let child_pid = Pid::from_raw(child.id().try_into().unwrap());
+        let listeners = ActiveListeners::default();
 
         let comm_task = task::spawn(run_communication_task(
             control_send,
@@ -148,6 +198,7 @@ impl ListenerCollection {
             local_control_recv,
             event_recv,
             event_channel,
+            listeners.clone(),
             child_pid,
         ));
 
@@ -156,6 +207,7 @@ impl ListenerCollection {
             control_sender: local_control_send,
             comm_task,
             connection_data: HashMap::new(),
+            listeners,
             child_pid,
             child_process: Some(child),
         };
@@ -199,7 +251,9 @@ impl ListenerCollection {
             event_receiver: evt_fd,
             id_gen: self.listener_id_generator,
             connection_data: self.connection_data,
+            listeners: self.listeners(),
             child_pid: self.child_pid.as_raw(),
+            ipc_version: crate::IPC_VERSION,
         })
     }
 
@@ -214,10 +268,24 @@ impl ListenerCollection {
     /// method) along with any application-specific state relating to them; they can then
     /// be recreated using [`restore_connection`](Self::restore_connection) on the
     /// recreated listener collection.
+    ///
+    /// This fails if the worker process is not [compatible](SavedListenerCollection::is_compatible)
+    /// with this build; use [`replace`](Self::replace) instead in that case.
     pub fn resume(
         state: SavedListenerCollection,
         event_channel: UnboundedSender<ConnectionEvent>,
     ) -> std::io::Result<Self> {
+        if !state.is_compatible() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "listener process uses IPC version {}, expected {}",
+                    state.ipc_version,
+                    crate::IPC_VERSION
+                ),
+            ));
+        }
+
         let (control_sender, event_receiver) = unsafe {
             (
                 IpcSender::<ControlMessage>::from_raw_fd(
@@ -234,6 +302,10 @@ impl ListenerCollection {
         let (local_control_send, local_control_recv) = unbounded_channel();
 
         let child_pid = Pid::from_raw(state.child_pid);
+        let listeners = Arc::new(Mutex::new(ListenerState {
+            active: state.listeners.into_iter().map(|l| (l.id, l)).collect(),
+            ..Default::default()
+        }));
 
         let handle = tokio::spawn(run_communication_task(
             control_sender,
@@ -241,6 +313,7 @@ impl ListenerCollection {
             local_control_recv,
             event_receiver,
             event_channel,
+            listeners.clone(),
             child_pid,
         ));
 
@@ -249,11 +322,47 @@ impl ListenerCollection {
             comm_task: handle,
             listener_id_generator: state.id_gen,
             connection_data: state.connection_data,
+            listeners,
             child_process: None,
             child_pid,
         })
     }
 
+    /// Replace the worker process described by a previously saved state with a new one,
+    /// for use when it is not [compatible](SavedListenerCollection::is_compatible) with
+    /// this build.
+    ///
+    /// The old worker process is terminated, and the new one is given the provided TLS
+    /// settings and the same listeners. The connections held by the old process are
+    /// closed, and the application should treat any it saved as such.
+    pub fn replace(
+        state: SavedListenerCollection,
+        event_channel: UnboundedSender<ConnectionEvent>,
+        tls_settings: TlsSettings,
+    ) -> Result<Self, ListenerCollectionError> {
+        tracing::warn!(
+            old_version = state.ipc_version,
+            new_version = crate::IPC_VERSION,
+            "Replacing incompatible listener process"
+        );
+        state.terminate_worker();
+
+        let mut ret = Self::new(event_channel)?;
+        ret.listener_id_generator = state.id_gen;
+
+        // Nothing is listening on the control channel if these fail, and that will
+        // be reported by the communication task
+        let _ = ret.send_tls_settings(tls_settings, None);
+        for listener in state.listeners {
+            let _ = ret.control_sender.send(ControlMessage::Listener(
+                listener.id,
+                ListenerControlDetail::Add(listener.addr, listener.conn_type, listener.options),
+            ));
+        }
+
+        Ok(ret)
+    }
+
     /// Create a new listener with the given socket address and type.
     ///
     /// Note that this method will only return an `Err(_)` variant if sending the
@@ -264,35 +373,138 @@ impl ListenerCollection {
         &self,
         address: SocketAddr,
         conn_type: ConnectionType,
+    ) -> Result<ListenerId, ListenerError> {
+        self.add_listener_with_options(address, conn_type, ListenerOptions::default())
+    }
+
+    /// Create a new listener with the given address, type and additional options.
+    /// Unix socket addresses must be used with [`ConnectionType::Unix`], and TCP
+    /// addresses with any other type.
+    ///
+    /// As for [`add_listener`](Self::add_listener), errors in creating the listener
+    /// itself are reported asynchronously on the event channel.
+    pub fn add_listener_with_options(
+        &self,
+        address: impl Into<ListenerAddress>,
+        conn_type: ConnectionType,
+        options: ListenerOptions,
     ) -> Result<ListenerId, ListenerError> {
         let id = self.listener_id_generator.next();
 
-        let message = ControlMessage::Listener(id, ListenerControlDetail::Add(address, conn_type));
+        let message = ControlMessage::Listener(
+            id,
+            ListenerControlDetail::Add(address.into(), conn_type, options),
+        );
         self.control_sender.send(message)?;
         Ok(id)
     }
 
+    /// Create a new listener as for [`add_listener_with_options`](Self::add_listener_with_options),
+    /// but wait until the worker process has bound its socket, and return any error
+    /// in doing so.
+    pub async fn add_listener_and_wait(
+        &self,
+        address: impl Into<ListenerAddress>,
+        conn_type: ConnectionType,
+        options: ListenerOptions,
+    ) -> Result<ListenerId, ListenerError> {
+        let id = self.listener_id_generator.next();
+
+        let (result_send, result_recv) = oneshot::channel();
+        self.listeners
+            .lock()
+            .unwrap()
+            .pending
+            .insert(id, result_send);
+
+        let message = ControlMessage::Listener(
+            id,
+            ListenerControlDetail::Add(address.into(), conn_type, options),
+        );
+        if let Err(e) = self.control_sender.send(message) {
+            self.listeners.lock().unwrap().pending.remove(&id);
+            return Err(e.into());
+        }
+
+        match result_recv.await {
+            Ok(result) => result.map(|()| id),
+            // The communication task went away without hearing either way
+            Err(_) => Err(ListenerError::CommunicationError),
+        }
+    }
+
+    /// Close the listener with the given ID. Connections already accepted by it are
+    /// unaffected.
+    pub fn close_listener(&self, id: ListenerId) -> Result<(), ListenerError> {
+        let message = ControlMessage::Listener(id, ListenerControlDetail::Close);
+        Ok(self.control_sender.send(message)?)
+    }
+
+    /// Details of the currently active listeners.
+    ///
+    /// A listener appears here once the worker process has successfully bound its
+    /// socket, and is removed when it is closed or fails.
+    pub fn listeners(&self) -> Vec<ListenerData> {
+        let mut ret: Vec<_> = self
+            .listeners
+            .lock()
+            .unwrap()
+            .active
+            .values()
+            .cloned()
+            .collect();
+        ret.sort_by_key(|l| l.id);
+        ret
+    }
+
     /// Load the provided TLS settings. This must be done before a TLS listener can be
     /// created.
+    ///
+    /// Calling this again replaces the certificates used by existing TLS listeners.
+    /// Connections already established are unaffected.
     pub fn load_tls_certificates(
         &self,
         key: Vec<u8>,
         cert_chain: Vec<Vec<u8>>,
     ) -> Result<(), ListenerError> {
-        let settings = TlsSettings { key, cert_chain };
-        Ok(self
-            .control_sender
-            .send(ControlMessage::LoadTlsSettings(settings))?)
+        self.send_tls_settings(TlsSettings { key, cert_chain }, None)
+    }
+
+    /// Load certificates as for [`load_tls_certificates`](Self::load_tls_certificates),
+    /// but wait until the worker process has applied them, and return any error in
+    /// doing so.
+    pub async fn load_tls_certificates_and_wait(
+        &self,
+        key: Vec<u8>,
+        cert_chain: Vec<Vec<u8>>,
+    ) -> Result<(), ListenerError> {
+        let (result_send, result_recv) = oneshot::channel();
+        self.send_tls_settings(TlsSettings { key, cert_chain }, Some(result_send))?;
+
+        match result_recv.await {
+            Ok(result) => result,
+            // The communication task went away without hearing either way
+            Err(_) => Err(ListenerError::CommunicationError),
+        }
+    }
+
+    fn send_tls_settings(
+        &self,
+        settings: TlsSettings,
+        waiting: Option<oneshot::Sender<Result<(), ListenerError>>>,
+    ) -> Result<(), ListenerError> {
+        // The worker process answers these in order, so the queue entry has to be
+        // added in the same order as the messages are sent
+        let mut state = self.listeners.lock().unwrap();
+        self.control_sender
+            .send(ControlMessage::LoadTlsSettings(settings))?;
+        state.pending_tls.push_back(waiting);
+        Ok(())
     }
 
     /// Restore a connection belonging to this connection from its saved [`ConnectionData`]
     pub fn restore_connection(&self, data: ConnectionData) -> Connection {
-        Connection::new(
-            data.id,
-            data.tls_info,
-            data.remote_addr,
-            self.control_sender.clone(),
-        )
+        Connection::new(data, self.control_sender.clone())
     }
 
     /// Shut down the worker process and communication task.
@@ -312,6 +524,7 @@ async fn run_communication_task<'a>(
     mut local_control_recv: UnboundedReceiver<ControlMessage>,
     event_receiver: IpcReceiver<InternalConnectionEvent>,
     event_sender: UnboundedSender<ConnectionEvent>,
+    listeners: ActiveListeners,
     child_pid: Pid,
 ) -> CommResult {
     loop {
@@ -336,7 +549,7 @@ async fn run_communication_task<'a>(
                         NewConnection(data) =>
                         {
                             tracing::debug!(?data, "got new connection");
-                            let new_connection = Connection::new(data.id, data.tls_info, data.remote_addr, local_control_send.clone());
+                            let new_connection = Connection::new(data, local_control_send.clone());
                             ConnectionEvent::new(new_connection.id, new_connection)
                         },
                         ConnectionError(id, err) =>
@@ -348,6 +561,52 @@ async fn run_communication_task<'a>(
                             tracing::trace!(connection=?id, ?msg, "Got message");
                             ConnectionEvent::message(id, msg)
                         },
+                        NewListener(data) =>
+                        {
+                            tracing::info!(listener=?data.id, address=%data.addr, "Listener started");
+                            let mut state = listeners.lock().unwrap();
+                            if let Some(waiting) = state.pending.remove(&data.id)
+                            {
+                                let _ = waiting.send(Ok(()));
+                            }
+                            state.active.insert(data.id, data);
+                            continue
+                        },
+                        ListenerError(id, err) =>
+                        {
+                            tracing::error!(listener=?id, error=%err, "Listener error");
+                            let mut state = listeners.lock().unwrap();
+                            state.active.remove(&id);
+                            if let Some(waiting) = state.pending.remove(&id)
+                            {
+                                let _ = waiting.send(Err(err));
+                            }
+                            continue
+                        },
+                        ListenerClosed(id) =>
+                        {
+                            tracing::info!(listener=?id, "Listener closed");
+                            listeners.lock().unwrap().active.remove(&id);
+                            continue
+                        },
+                        TlsSettingsLoaded =>
+                        {
+                            tracing::info!("Listener process loaded TLS settings");
+                            if let Some(Some(waiting)) = listeners.lock().unwrap().pending_tls.pop_front()
+                            {
+                                let _ = waiting.send(Ok(()));
+                            }
+                            continue
+                        },
+                        BadTlsConfig(err) =>
+                        {
+                            tracing::error!(error=%err, "Listener process rejected TLS settings");
+                            if let Some(Some(waiting)) = listeners.lock().unwrap().pending_tls.pop_front()
+                            {
+                                let _ = waiting.send(Err(err));
+                            }
+                            continue
+                        },
                         _ => continue
                     };
                     if let Err(e) = event_sender.send(translated_event) {
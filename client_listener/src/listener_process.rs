@@ -17,7 +17,7 @@ use tokio::sync::mpsc::{channel, unbounded_channel, UnboundedReceiver};
 pub struct ListenerProcess {
     control_receiver: IpcReceiver<ControlMessage>,
     event_sender: Arc<IpcSender<InternalConnectionEvent>>,
-    tls_config: Option<Arc<rustls::ServerConfig>>,
+    tls_config: Option<SharedTlsConfig>,
 
     listeners: HashMap<ListenerId, Listener>,
     connections: HashMap<ConnectionId, InternalConnection>,
@@ -41,9 +41,14 @@ impl ListenerProcess {
     }
 
     fn translate_connection_type(
-        tls_config: &Option<Arc<rustls::ServerConfig>>,
+        tls_config: &Option<SharedTlsConfig>,
+        address: &ListenerAddress,
         ct: ConnectionType,
     ) -> Result<InternalConnectionType, ListenerError> {
+        if matches!(address, ListenerAddress::Unix(_)) != matches!(ct, ConnectionType::Unix) {
+            return Err(ListenerError::AddressMismatch);
+        }
+
         match ct {
             ConnectionType::Clear => Ok(InternalConnectionType::Clear),
             ConnectionType::Tls => {
@@ -53,28 +58,18 @@ impl ListenerProcess {
                     Err(ListenerError::NoTlsConfig)
                 }
             }
+            ConnectionType::WebSocket => Ok(InternalConnectionType::WebSocket),
+            ConnectionType::WebSocketTls => {
+                if let Some(conf) = &tls_config {
+                    Ok(InternalConnectionType::WebSocketTls(conf.clone()))
+                } else {
+                    Err(ListenerError::NoTlsConfig)
+                }
+            }
+            ConnectionType::Unix => Ok(InternalConnectionType::Unix),
         }
     }
 
-    fn build_tls_config(settings: TlsSettings) -> Result<Arc<rustls::ServerConfig>, rustls::Error> {
-        let key = rustls::PrivateKey(settings.key);
-        let certs: Vec<rustls::Certificate> = settings
-            .cert_chain
-            .into_iter()
-            .map(rustls::Certificate)
-            .collect();
-
-        let client_cert_verifier =
-            internal::client_verifier::AcceptAnyClientCertVerifier::new(&certs[0]);
-
-        Ok(Arc::new(
-            rustls::ServerConfig::builder()
-                .with_safe_defaults()
-                .with_client_cert_verifier(Arc::new(client_cert_verifier))
-                .with_single_cert(certs, key)?,
-        ))
-    }
-
     /// Sends events to the parent process, spawning the task into the background.
     ///
     /// Spawning a separate task avoids blocking the communication task, but does mean
@@ -121,23 +116,31 @@ impl ListenerProcess {
                         {
                             if let Some(conn) = self.connections.get(&id)
                             {
-                                if let Err(e) = conn.control_channel.try_send(msg)
+                                if let Err(e) = conn.send_control(msg)
                                 {
-                                    ipc_event_send.send(InternalConnectionEvent::ConnectionError(id, e.into())).unwrap();
+                                    ipc_event_send.send(InternalConnectionEvent::ConnectionError(id, e)).unwrap();
                                 }
                             }
                         }
+                        Ok(ControlMessage::SetSendQueueLength(id, len)) =>
+                        {
+                            if let Some(conn) = self.connections.get_mut(&id)
+                            {
+                                conn.send_queue_len = len.clamp(1, MAX_SEND_QUEUE_LEN);
+                            }
+                        }
                         Ok(ControlMessage::Listener(id, msg)) =>
                         {
                             match msg
                             {
-                                ListenerControlDetail::Add(address, conn_type) =>
+                                ListenerControlDetail::Add(address, conn_type, options) =>
                                 {
-                                    match Self::translate_connection_type(&self.tls_config, conn_type)
+                                    match Self::translate_connection_type(&self.tls_config, &address, conn_type.clone())
                                     {
                                         Ok(ct) =>
                                         {
-                                            let listener = Listener::new(id, address, ct, connection_event_send.clone());
+                                            let data = ListenerData { id, addr: address, conn_type, options };
+                                            let listener = Listener::new(data, ct, connection_event_send.clone());
 
                                             self.listeners.insert(id, listener);
                                         }
@@ -149,7 +152,7 @@ impl ListenerProcess {
                                 }
                                 ListenerControlDetail::Close =>
                                 {
-                                    if let Some(listener) = self.listeners.get(&id)
+                                    if let Some(listener) = self.listeners.remove(&id)
                                     {
                                         if let Err(e) = listener.control_channel.try_send(msg)
                                         {
@@ -161,13 +164,23 @@ impl ListenerProcess {
                         }
                         Ok(ControlMessage::LoadTlsSettings(settings)) =>
                         {
-                            if let Ok(config) = Self::build_tls_config(settings)
+                            match settings.server_config()
                             {
-                                self.tls_config = Some(config);
-                            }
-                            else
-                            {
-                                ipc_event_send.send(InternalConnectionEvent::BadTlsConfig).unwrap();
+                                Ok(config) =>
+                                {
+                                    // Existing listeners share the config, so replace it in place
+                                    // to have them pick up the new certificates
+                                    match &self.tls_config
+                                    {
+                                        Some(shared) => *shared.write().unwrap() = config,
+                                        None => self.tls_config = Some(Arc::new(std::sync::RwLock::new(config))),
+                                    }
+                                    ipc_event_send.send(InternalConnectionEvent::TlsSettingsLoaded).unwrap();
+                                }
+                                Err(e) =>
+                                {
+                                    ipc_event_send.send(InternalConnectionEvent::BadTlsConfig(e)).unwrap();
+                                }
                             }
                         }
                         Ok(ControlMessage::Shutdown) =>
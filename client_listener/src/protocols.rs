@@ -2,8 +2,11 @@ use crate::error::*;
 use crate::id::*;
 use crate::Connection;
 
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
-use std::net::IpAddr;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
 
 /// Information about a client connection's TLS status
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +18,170 @@ pub struct TlsInfo {
 pub enum ConnectionType {
     Clear,
     Tls,
+    /// IRCv3 WebSocket transport, without TLS
+    WebSocket,
+    /// IRCv3 WebSocket transport over TLS
+    WebSocketTls,
+    /// Plain text connections on a Unix domain socket, for local bridges and bots
+    Unix,
+}
+
+/// The address on which a listener accepts connections
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ListenerAddress {
+    /// A TCP socket address
+    Tcp(SocketAddr),
+    /// The filesystem path of a Unix domain socket
+    Unix(PathBuf),
+}
+
+impl From<SocketAddr> for ListenerAddress {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Tcp(addr)
+    }
+}
+
+impl fmt::Display for ListenerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => addr.fmt(f),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Settings for a listener on a Unix domain socket.
+///
+/// Connections on the socket have no meaningful remote address, so they are
+/// reported with a fixed IP address and hostname instead, which apply for the
+/// purposes of ban matching and are shown to opers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnixSocketSettings {
+    /// Permissions for the socket file, as an octal string such as `"660"`
+    #[serde(default, with = "octal_mode")]
+    pub mode: Option<u32>,
+    /// User name or numeric ID which should own the socket file
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Group name or numeric ID which should own the socket file
+    #[serde(default)]
+    pub group: Option<String>,
+    /// The IP address reported for connections on this socket
+    #[serde(default = "default_unix_remote_addr")]
+    pub remote_addr: IpAddr,
+    /// The hostname assigned to connections on this socket, in place of a DNS lookup
+    #[serde(default = "default_unix_hostname")]
+    pub hostname: String,
+}
+
+fn default_unix_remote_addr() -> IpAddr {
+    Ipv4Addr::LOCALHOST.into()
+}
+
+fn default_unix_hostname() -> String {
+    "localhost".to_string()
+}
+
+impl Default for UnixSocketSettings {
+    fn default() -> Self {
+        Self {
+            mode: None,
+            owner: None,
+            group: None,
+            remote_addr: default_unix_remote_addr(),
+            hostname: default_unix_hostname(),
+        }
+    }
+}
+
+/// (De)serialise file modes as octal strings, which is how everyone expects to
+/// write them
+mod octal_mode {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(mode: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error> {
+        mode.map(|m| format!("{:o}", m)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u32>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| u32::from_str_radix(&s, 8).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// Settings for a listener which expects connections to be forwarded by a proxy
+/// using the HAProxy PROXY protocol (version 1 or 2).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ProxyProtocolSettings {
+    /// Addresses of the proxies permitted to connect. Connections from any other
+    /// source are rejected, since the header they send can't be trusted.
+    pub trusted_sources: Vec<IpNet>,
+}
+
+impl ProxyProtocolSettings {
+    /// Whether connections from `addr` are permitted to supply a PROXY header
+    pub fn trusts(&self, addr: IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        self.trusted_sources.iter().any(|net| net.contains(&addr))
+    }
+}
+
+/// Token bucket parameters limiting the rate at which a single connection may
+/// send lines. Connections which exceed the limit are closed with
+/// [`ConnectionError::ExcessFlood`].
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ReceiveLimitSettings {
+    /// Sustained number of lines permitted per second
+    pub lines_per_second: u32,
+    /// Number of lines which may be received at once before the sustained rate applies
+    pub burst: u32,
+}
+
+impl Default for ReceiveLimitSettings {
+    fn default() -> Self {
+        Self {
+            lines_per_second: 10,
+            burst: 40,
+        }
+    }
+}
+
+/// Optional per-listener settings, in addition to the [`ConnectionType`]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ListenerOptions {
+    /// If set, every connection must begin with a PROXY protocol header
+    #[serde(default)]
+    pub proxy_protocol: Option<ProxyProtocolSettings>,
+    /// Receive rate limit applied to each connection, or `None` for no limit
+    #[serde(default)]
+    pub receive_limit: Option<ReceiveLimitSettings>,
+    /// Socket file and reported identity settings for Unix socket listeners. If
+    /// this is `None` for a Unix listener, the defaults are used.
+    #[serde(default)]
+    pub unix_socket: Option<UnixSocketSettings>,
+}
+
+/// Details of an active listener, as reported by
+/// [`ListenerCollection::listeners`](crate::ListenerCollection::listeners)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListenerData {
+    pub id: ListenerId,
+    pub addr: ListenerAddress,
+    pub conn_type: ConnectionType,
+    #[serde(default)]
+    pub options: ListenerOptions,
+}
+
+/// The socket addresses at each end of a TCP connection, as needed for ident
+/// lookups. This is only known for connections accepted directly, rather than
+/// via a proxy.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TcpEndpoints {
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
 }
 
 /// The saved state of a [`Connection`]
@@ -23,6 +190,12 @@ pub struct ConnectionData {
     pub(crate) id: ConnectionId,
     pub(crate) remote_addr: IpAddr,
     pub(crate) tls_info: Option<TlsInfo>,
+    #[serde(default)]
+    pub(crate) websocket: bool,
+    #[serde(default)]
+    pub(crate) local_hostname: Option<String>,
+    #[serde(default)]
+    pub(crate) tcp_endpoints: Option<TcpEndpoints>,
 }
 
 /// The certificate chain and private key required to create a TLS listener.
@@ -35,6 +208,45 @@ pub struct TlsSettings {
     pub key: Vec<u8>,
 }
 
+impl TlsSettings {
+    /// Check that these settings can be used to create a TLS listener, without
+    /// sending them to the worker process.
+    pub fn validate(&self) -> Result<(), ListenerError> {
+        self.server_config().map(|_| ())
+    }
+
+    /// Build the server configuration used by TLS listeners
+    pub(crate) fn server_config(
+        &self,
+    ) -> Result<std::sync::Arc<rustls::ServerConfig>, ListenerError> {
+        let bad_config = |e: rustls::Error| ListenerError::BadTlsConfig(e.to_string());
+
+        let key = rustls::PrivateKey(self.key.clone());
+        let certs: Vec<rustls::Certificate> = self
+            .cert_chain
+            .iter()
+            .cloned()
+            .map(rustls::Certificate)
+            .collect();
+
+        let Some(leaf) = certs.first() else {
+            return Err(ListenerError::BadTlsConfig(
+                "empty certificate chain".to_string(),
+            ));
+        };
+        let client_cert_verifier =
+            crate::internal::client_verifier::AcceptAnyClientCertVerifier::new(leaf);
+
+        Ok(std::sync::Arc::new(
+            rustls::ServerConfig::builder()
+                .with_safe_defaults()
+                .with_client_cert_verifier(std::sync::Arc::new(client_cert_verifier))
+                .with_single_cert(certs, key)
+                .map_err(bad_config)?,
+        ))
+    }
+}
+
 /// Possible types of event that might occur on a given connection.
 pub enum ConnectionEventDetail {
     /// A new connection was accepted
@@ -61,7 +273,9 @@ impl ConnectionEvent {
         }
     }
 
-    pub(crate) fn error(id: ConnectionId, error: ConnectionError) -> Self {
+    /// An error event for the given connection. Applications may use this to clean up
+    /// connections lost when the worker process was [replaced](crate::ListenerCollection::replace).
+    pub fn error(id: ConnectionId, error: ConnectionError) -> Self {
         Self {
             source: id,
             detail: ConnectionEventDetail::Error(error),
@@ -75,3 +289,29 @@ impl ConnectionEvent {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tls_settings_validation() {
+        let empty = TlsSettings {
+            cert_chain: Vec::new(),
+            key: vec![1, 2, 3],
+        };
+        assert!(matches!(
+            empty.validate(),
+            Err(ListenerError::BadTlsConfig(_))
+        ));
+
+        let garbage = TlsSettings {
+            cert_chain: vec![vec![1, 2, 3]],
+            key: vec![4, 5, 6],
+        };
+        assert!(matches!(
+            garbage.validate(),
+            Err(ListenerError::BadTlsConfig(_))
+        ));
+    }
+}
@@ -75,6 +75,7 @@ define_capabilities! (
         AwayNotify:             0x80 => ("away-notify", true),
         AccountTag:             0x100 => ("account-tag", true),
         MultiPrefix:            0x200 => ("multi-prefix", true),
+        Sts:                    0x400 => ("sts", false),
 
         // Draft and experimental caps
         ChatHistory:            0x1_0000 => ("draft/chathistory", true),
@@ -10,6 +10,9 @@ use strum::IntoEnumIterator;
 struct CapabilityEntry {
     cap: ClientCapability,
     values: RwLock<Vec<String>>,
+    /// Values advertised to TLS connections, if they differ from `values`
+    #[serde(default)]
+    tls_values: RwLock<Option<Vec<String>>>,
     available: AtomicBool,
 }
 
@@ -18,6 +21,8 @@ pub struct CapabilityRepository {
     supported_caps: Vec<CapabilityEntry>,
     all_caps_301: ArcSwap<String>,
     all_caps_302: ArcSwap<String>,
+    #[serde(default)]
+    all_caps_302_tls: ArcSwap<String>,
 }
 
 impl CapabilityRepository {
@@ -28,6 +33,7 @@ impl CapabilityRepository {
             supported_caps.push(CapabilityEntry {
                 cap,
                 values: RwLock::new(Vec::new()),
+                tls_values: RwLock::new(None),
                 available: AtomicBool::new(cap.is_default()),
             });
         }
@@ -36,6 +42,7 @@ impl CapabilityRepository {
             supported_caps,
             all_caps_301: ArcSwap::from_pointee(String::new()),
             all_caps_302: ArcSwap::from_pointee(String::new()),
+            all_caps_302_tls: ArcSwap::from_pointee(String::new()),
         };
 
         ret.update_supported_lists();
@@ -43,6 +50,22 @@ impl CapabilityRepository {
         ret
     }
 
+    /// Add entries for any capabilities missing from a repository restored from
+    /// saved state, as happens after upgrading to a version supporting new ones
+    pub fn add_missing_capabilities(&mut self) {
+        for cap in ClientCapability::iter() {
+            if !self.supported_caps.iter().any(|e| e.cap == cap) {
+                self.supported_caps.push(CapabilityEntry {
+                    cap,
+                    values: RwLock::new(Vec::new()),
+                    tls_values: RwLock::new(None),
+                    available: AtomicBool::new(cap.is_default()),
+                });
+            }
+        }
+        self.update_supported_lists();
+    }
+
     fn update_supported_lists(&self) {
         let all_caps_301 = self
             .supported_caps
@@ -55,19 +78,33 @@ impl CapabilityRepository {
             .supported_caps
             .iter()
             .filter(|e| e.available.load(Ordering::Relaxed))
-            .map(CapabilityEntry::token_302)
+            .map(|e| e.token_302(false))
+            .join(" ");
+
+        let all_caps_302_tls = self
+            .supported_caps
+            .iter()
+            .filter(|e| e.available.load(Ordering::Relaxed))
+            .map(|e| e.token_302(true))
             .join(" ");
 
         self.all_caps_301.store(Arc::new(all_caps_301));
         self.all_caps_302.store(Arc::new(all_caps_302));
+        self.all_caps_302_tls.store(Arc::new(all_caps_302_tls));
     }
 
     pub fn supported_caps_301(&self) -> Arc<String> {
         self.all_caps_301.load_full()
     }
 
-    pub fn supported_caps_302(&self) -> Arc<String> {
-        self.all_caps_302.load_full()
+    /// The capability list for `CAP LS 302`, with values appropriate to a TLS
+    /// or plaintext connection
+    pub fn supported_caps_302(&self, tls: bool) -> Arc<String> {
+        if tls {
+            self.all_caps_302_tls.load_full()
+        } else {
+            self.all_caps_302.load_full()
+        }
     }
 
     pub fn find(&self, name: &str) -> Option<ClientCapability> {
@@ -89,20 +126,18 @@ impl CapabilityRepository {
             }
             self.update_supported_lists();
         }
-
-        pub fn disable(&self, cap: ClientCapability)
-        {
-            for entry in &self.supported_caps
-            {
-                if entry.cap == cap
-                {
-                    entry.available.store(false, Ordering::Relaxed);
-                    entry.values.write().clear();
-                }
+    */
+    pub fn disable(&self, cap: ClientCapability) {
+        for entry in &self.supported_caps {
+            if entry.cap == cap {
+                entry.available.store(false, Ordering::Relaxed);
+                entry.values.write().clear();
+                *entry.tls_values.write() = None;
             }
-            self.update_supported_lists();
         }
-    */
+        self.update_supported_lists();
+    }
+
     pub fn enable_with_values(&self, cap: ClientCapability, values: &[String]) {
         for entry in &self.supported_caps {
             if entry.cap == cap {
@@ -112,6 +147,23 @@ impl CapabilityRepository {
         }
         self.update_supported_lists();
     }
+
+    /// Enable a capability whose values depend on whether the connection uses TLS
+    pub fn enable_with_tls_values(
+        &self,
+        cap: ClientCapability,
+        values: &[String],
+        tls_values: &[String],
+    ) {
+        for entry in &self.supported_caps {
+            if entry.cap == cap {
+                entry.available.store(true, Ordering::Relaxed);
+                *entry.values.write() = values.to_owned();
+                *entry.tls_values.write() = Some(tls_values.to_owned());
+            }
+        }
+        self.update_supported_lists();
+    }
 }
 
 impl CapabilityEntry {
@@ -119,8 +171,12 @@ impl CapabilityEntry {
         self.cap.name().to_owned()
     }
 
-    fn token_302(&self) -> String {
-        let values = self.values.read();
+    fn token_302(&self, tls: bool) -> String {
+        let tls_values = self.tls_values.read();
+        let values = match (tls, tls_values.as_ref()) {
+            (true, Some(tls_values)) => tls_values.clone(),
+            _ => self.values.read().clone(),
+        };
 
         if values.is_empty() {
             self.cap.name().to_owned()
@@ -133,3 +189,32 @@ impl CapabilityEntry {
         self.cap.name()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tls_specific_values() {
+        let repo = CapabilityRepository::new();
+        assert!(repo.find("sts").is_none());
+
+        repo.enable_with_tls_values(
+            ClientCapability::Sts,
+            &["port=6697".to_string()],
+            &["duration=300".to_string(), "preload".to_string()],
+        );
+
+        assert!(repo.supported_caps_302(false).contains("sts=port=6697"));
+        assert!(repo
+            .supported_caps_302(true)
+            .contains("sts=duration=300,preload"));
+        assert!(repo.supported_caps_301().split(' ').any(|c| c == "sts"));
+
+        repo.disable(ClientCapability::Sts);
+        assert!(!repo
+            .supported_caps_302(true)
+            .split(' ')
+            .any(|c| c.starts_with("sts")));
+    }
+}
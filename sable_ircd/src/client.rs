@@ -1,6 +1,7 @@
 use super::*;
 use crate::capability::*;
 use crate::movable::Movable;
+use crate::server::config::ConnectionClassConfig;
 use crate::throttled_queue::*;
 use crate::utils::WrapOption;
 use client_listener::*;
@@ -10,12 +11,12 @@ use sable_network::prelude::*;
 use std::{
     net::IpAddr,
     sync::{
-        atomic::{AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicI64, AtomicU32, Ordering},
         Arc,
     },
 };
 
-use arc_swap::ArcSwapOption;
+use arc_swap::{ArcSwap, ArcSwapOption};
 use serde::*;
 use serde_with::serde_as;
 use std::sync::OnceLock;
@@ -27,8 +28,9 @@ pub struct ClientConnection {
     /// The underlying network connection
     pub connection: Movable<Connection>,
 
-    /// The user and user connection IDs, if this connection has completed registration
-    user: OnceLock<(UserId, UserConnectionId)>,
+    /// The user and user connection IDs, if this connection has completed registration.
+    /// The connection ID can change if the server resynchronises with the network.
+    user: ArcSwapOption<(UserId, UserConnectionId)>,
 
     /// The registration information received so far, if this connection has not
     /// yet completed registration
@@ -39,6 +41,14 @@ pub struct ClientConnection {
 
     /// Capability flags
     pub capabilities: AtomicCapabilitySet,
+
+    /// The name of the connection class to which this connection belongs
+    class: ArcSwap<String>,
+
+    /// When a message was last received from this connection
+    last_activity: AtomicI64,
+    /// Whether we have sent a PING since the last message was received
+    ping_sent: AtomicBool,
 }
 
 /// Serialised state of a [`ClientConnection`], for later resumption
@@ -49,6 +59,23 @@ pub(super) struct ClientConnectionState {
     pre_client: Option<PreClient>,
     receive_queue: SavedThrottledQueue<String>,
     capabilities: ClientCapabilitySet,
+    #[serde(default = "default_class_name")]
+    class: String,
+}
+
+fn default_class_name() -> String {
+    ConnectionClassConfig::default_class().name
+}
+
+/// The result of checking a connection's activity against its ping frequency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingStatus {
+    /// The connection has sent something recently, or has been pinged and may yet respond
+    Active,
+    /// The connection has been idle for long enough that it should be sent a PING
+    SendPing,
+    /// The connection has failed to respond to a PING
+    TimedOut,
 }
 
 /// Operations that, while ongoing, will block a client from registering
@@ -57,6 +84,8 @@ pub(super) struct ClientConnectionState {
 pub enum ProgressFlag {
     CapNegotiation = 0x1,
     SaslAuthentication = 0x2,
+    IdentLookup = 0x4,
+    DnsblLookup = 0x8,
 }
 
 /// Information received from a client connection that has not yet completed registration
@@ -80,6 +109,14 @@ pub struct PreClient {
     pub realname: OnceLock<Realname>,
     #[serde_as(as = "WrapOption<Hostname>")]
     pub hostname: OnceLock<Hostname>,
+    /// The username returned by an ident lookup, if one succeeded
+    #[serde_as(as = "WrapOption<Username>")]
+    #[serde(default)]
+    pub ident: OnceLock<Username>,
+    /// The DNS blocklists in which this client's address is listed, once checked
+    #[serde_as(as = "WrapOption<Vec<auth_client::DnsblHit>>")]
+    #[serde(default)]
+    pub dnsbl_hits: OnceLock<Vec<auth_client::DnsblHit>>,
     #[serde_as(as = "WrapOption<SaslSessionId>")]
     pub sasl_session: OnceLock<SaslSessionId>,
     #[serde_as(as = "WrapOption<AccountId>")]
@@ -89,20 +126,20 @@ pub struct PreClient {
 }
 
 impl ClientConnection {
-    /// Construct a `ClientConnection` from an underlying [`Connection`]
-    pub fn new(conn: Connection) -> Self {
-        let throttle_settings = ThrottleSettings {
-            num: 1,
-            time: 1,
-            burst: 4,
-        };
+    /// Construct a `ClientConnection` from an underlying [`Connection`], belonging
+    /// to the given connection class
+    pub fn new(conn: Connection, class: &ConnectionClassConfig) -> Self {
+        conn.set_send_queue_length(class.send_queue);
 
         Self {
             connection: Movable::new(conn),
-            user: OnceLock::new(),
+            user: ArcSwapOption::empty(),
             pre_client: ArcSwapOption::new(Some(Arc::new(PreClient::new()))),
-            receive_queue: Movable::new(ThrottledQueue::new(throttle_settings, 16)),
+            receive_queue: Movable::new(ThrottledQueue::new(class.throttle, 16)),
             capabilities: AtomicCapabilitySet::new(),
+            class: ArcSwap::from_pointee(class.name.clone()),
+            last_activity: AtomicI64::new(sable_network::utils::now()),
+            ping_sent: AtomicBool::new(false),
         }
     }
 
@@ -110,7 +147,7 @@ impl ClientConnection {
     pub(crate) fn save(mut self) -> ClientConnectionState {
         ClientConnectionState {
             connection_data: self.connection.unwrap().save(),
-            user: self.user.get().copied(),
+            user: self.user.load_full().map(|u| *u),
             pre_client: self.pre_client.load_full().map(|a| {
                 Arc::try_unwrap(a).unwrap_or_else(|_| {
                     panic!("Outstanding reference to preclient while upgrading?")
@@ -118,6 +155,7 @@ impl ClientConnection {
             }),
             receive_queue: self.receive_queue.unwrap().save(),
             capabilities: (&self.capabilities).into(),
+            class: self.class.load().to_string(),
         }
     }
 
@@ -129,13 +167,15 @@ impl ClientConnection {
     ) -> Self {
         Self {
             connection: Movable::new(listener_collection.restore_connection(state.connection_data)),
-            user: match state.user {
-                Some(v) => OnceLock::from(v),
-                None => OnceLock::new(),
-            },
+            user: ArcSwapOption::new(state.user.map(Arc::new)),
             pre_client: ArcSwapOption::new(state.pre_client.map(Arc::new)),
             receive_queue: Movable::new(ThrottledQueue::restore_from(state.receive_queue)),
             capabilities: state.capabilities.into(),
+            class: ArcSwap::from_pointee(state.class),
+            // Reset on restore, to avoid timing out clients if the server took a long
+            // time to restart
+            last_activity: AtomicI64::new(sable_network::utils::now()),
+            ping_sent: AtomicBool::new(false),
         }
     }
 
@@ -162,17 +202,17 @@ impl ClientConnection {
 
     /// Return the associated user ID, if any
     pub fn user_id(&self) -> Option<UserId> {
-        self.user.get().map(|v| v.0)
+        self.user.load().as_ref().map(|v| v.0)
     }
 
     /// Return the associated UserConnection ID, if any
     pub fn user_connection_id(&self) -> Option<UserConnectionId> {
-        self.user.get().map(|v| v.1)
+        self.user.load().as_ref().map(|v| v.1)
     }
 
     /// Return the associated User and UserConnection IDs, if present
     pub(super) fn user_ids(&self) -> Option<(UserId, UserConnectionId)> {
-        self.user.get().copied()
+        self.user.load().as_deref().copied()
     }
 
     /// Return the associated pre-client data, if any
@@ -182,17 +222,55 @@ impl ClientConnection {
 
     /// Set the associated user ID
     pub fn set_user(&self, user_id: UserId, user_connection_id: UserConnectionId) {
-        let _ = self.user.set((user_id, user_connection_id));
+        self.user
+            .store(Some(Arc::new((user_id, user_connection_id))));
         self.pre_client.swap(None);
     }
 
+    /// The name of this connection's connection class
+    pub fn class_name(&self) -> Arc<String> {
+        self.class.load_full()
+    }
+
+    /// Move this connection into a new connection class, and apply its settings
+    pub fn set_class(&self, class: &ConnectionClassConfig) {
+        self.class.store(Arc::new(class.name.clone()));
+        self.receive_queue.change_settings(class.throttle);
+        self.connection.set_send_queue_length(class.send_queue);
+    }
+
     /// Notify that a new message has been received on this connection
     ///
     /// Returns `Ok(())` on success, `Err(message)` if the connection's receive queue is full
     pub fn new_message(&self, message: String) -> Result<(), String> {
+        self.last_activity
+            .store(sable_network::utils::now(), Ordering::Relaxed);
+        self.ping_sent.store(false, Ordering::Relaxed);
         self.receive_queue.add(message)
     }
 
+    /// Check how long this connection has been idle, given its class's ping frequency.
+    ///
+    /// If this returns [`PingStatus::SendPing`], the connection is considered to have
+    /// been pinged, and will time out if nothing is received before another
+    /// `ping_frequency` seconds have passed.
+    pub fn check_activity(&self, ping_frequency: i64) -> PingStatus {
+        let idle = sable_network::utils::now() - self.last_activity.load(Ordering::Relaxed);
+
+        if self.ping_sent.load(Ordering::Relaxed) {
+            if idle >= ping_frequency * 2 {
+                PingStatus::TimedOut
+            } else {
+                PingStatus::Active
+            }
+        } else if idle >= ping_frequency {
+            self.ping_sent.store(true, Ordering::Relaxed);
+            PingStatus::SendPing
+        } else {
+            PingStatus::Active
+        }
+    }
+
     /// Poll for messages that the throttle permits to be processed
     pub fn poll_messages(&self) -> impl Iterator<Item = String> + '_ {
         self.receive_queue.iter()
@@ -240,6 +318,8 @@ impl PreClient {
             nick: OnceLock::new(),
             realname: OnceLock::new(),
             hostname: OnceLock::new(),
+            ident: OnceLock::new(),
+            dnsbl_hits: OnceLock::new(),
             sasl_session: OnceLock::new(),
             sasl_account: OnceLock::new(),
             progress_flags: AtomicU32::new(0),
@@ -281,6 +361,11 @@ impl PreClient {
         self.progress_flags.fetch_or(flag as u32, Ordering::Relaxed);
     }
 
+    /// Whether the given operation has begun and not yet completed
+    pub fn in_progress(&self, flag: ProgressFlag) -> bool {
+        self.progress_flags.load(Ordering::Relaxed) & flag as u32 != 0
+    }
+
     /// Unset a progress flag, indicating that the given operation has completed
     ///
     /// Return true if the client is ready to register, i.e. if `can_register`
@@ -250,6 +250,12 @@ impl ClientCommand {
             }
             CommandError::Permission(pe) => {
                 match pe {
+                    PermissionError::User(UserPermissionError::NotOper) => {
+                        Some(make_numeric!(NotOper))
+                    }
+                    PermissionError::User(UserPermissionError::MissingPrivilege(privilege)) => {
+                        Some(make_numeric!(NoPrivs, &privilege.to_string()))
+                    }
                     // These have no corresponding numerics
                     PermissionError::User(_) => None,
                     PermissionError::Registration(_) => None,
@@ -0,0 +1,61 @@
+use super::*;
+
+/// How many entries are shown if the query doesn't specify a limit
+const DEFAULT_AUDIT_LIMIT: usize = 50;
+
+#[command_handler("AUDIT")]
+fn handle_audit(
+    server: &ClientServer,
+    net: &Network,
+    response: &dyn CommandResponse,
+    source: UserSource,
+    args: ArgList,
+) -> CommandResult {
+    server.policy().can_view_audit_log(&source)?;
+
+    let mut params = args.iter().peekable();
+    match params.peek().map(|p| p.to_ascii_uppercase()).as_deref() {
+        Some("SUBSCRIBE") => {
+            server.subscribe_audit_log(source.id());
+            response.notice("You will now be sent new audit log entries");
+            return Ok(());
+        }
+        Some("UNSUBSCRIBE") => {
+            if server.unsubscribe_audit_log(source.id()) {
+                response.notice("You will no longer be sent new audit log entries");
+            } else {
+                response.notice("You are not subscribed to the audit log");
+            }
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    let mut query_params = Vec::new();
+    for param in params {
+        let Some(pair) = param.split_once('=') else {
+            response.notice(&format!(
+                "Invalid query parameter {:?}; expected key=value",
+                param
+            ));
+            return Ok(());
+        };
+        query_params.push(pair);
+    }
+
+    let mut query = match AuditLogQuery::from_params(query_params) {
+        Ok(query) => query,
+        Err(e) => {
+            response.notice(&e.to_string());
+            return Ok(());
+        }
+    };
+    query.limit.get_or_insert(DEFAULT_AUDIT_LIMIT);
+
+    for entry in query.run(net) {
+        response.notice(&crate::utils::format_audit_entry(entry));
+    }
+    response.notice("End of audit log");
+
+    Ok(())
+}
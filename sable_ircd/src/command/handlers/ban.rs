@@ -28,7 +28,7 @@ fn handle_ban(
     response: &dyn CommandResponse,
     new_ban_str: &str,
 ) -> CommandResult {
-    server.policy().require_oper(&source)?;
+    server.policy().can_manage_bans(&source)?;
 
     let new_ban_details: NewBanArguments = match serde_json::from_str(new_ban_str) {
         Ok(ban) => ban,
@@ -93,6 +93,7 @@ fn handle_ban(
     let new_ban = event::details::NewNetworkBan {
         match_type,
         pattern,
+        pattern_text: new_ban_details.pattern,
         action,
         timestamp,
         expires,
@@ -0,0 +1,147 @@
+use super::*;
+use sable_network::network::ban::*;
+
+#[command_handler("BANS")]
+fn handle_bans(
+    server: &ClientServer,
+    net: &Network,
+    response: &dyn CommandResponse,
+    source: UserSource,
+    audit: AuditLogger,
+    filter: Option<&str>,
+) -> CommandResult {
+    server.policy().can_manage_bans(&source)?;
+
+    let mut entry = audit.ban();
+    if let Some(filter) = filter {
+        entry = entry.target_str(filter.to_owned());
+    }
+    entry.log();
+
+    let filter = filter.map(|f| Pattern::new(f.to_owned()));
+
+    let now = sable_network::utils::now();
+
+    let mut bans: Vec<_> = net
+        .network_bans()
+        .iter()
+        .filter(|ban| filter.as_ref().map_or(true, |f| ban_matches_filter(ban, f)))
+        .collect();
+    bans.sort_by_key(|ban| (ban.timestamp, ban.id));
+
+    for ban in bans {
+        let mut line = format!(
+            "{}: {} [{}] set by {}, {}: {}",
+            ban.id,
+            pattern_text(ban),
+            action_name(ban.action),
+            ban.setter_info,
+            remaining_time(ban.expires - now),
+            ban.reason
+        );
+        if let Some(oper_reason) = &ban.oper_reason {
+            line.push_str(" | ");
+            line.push_str(oper_reason);
+        }
+        response.notice(&line);
+    }
+    response.notice("End of ban list");
+
+    Ok(())
+}
+
+/// The pattern of a ban, for display
+pub(super) fn pattern_text(ban: &state::NetworkBan) -> String {
+    if ban.pattern_text.is_empty() {
+        // Bans created before the original text was recorded
+        format!("{:?}", ban.pattern)
+    } else {
+        ban.pattern_text.clone()
+    }
+}
+
+/// A ban is listed if the filter matches its ID, pattern, setter or either reason
+fn ban_matches_filter(ban: &state::NetworkBan, filter: &Pattern) -> bool {
+    filter.matches(&ban.id.to_string())
+        || filter.matches(&ban.pattern_text)
+        || filter.matches(&ban.setter_info)
+        || filter.matches(&ban.reason)
+        || ban
+            .oper_reason
+            .as_deref()
+            .is_some_and(|r| filter.matches(r))
+}
+
+fn action_name(action: NetworkBanAction) -> &'static str {
+    match action {
+        NetworkBanAction::RefuseConnection(true) => "refuse_connection, apply_existing",
+        NetworkBanAction::RefuseConnection(false) => "refuse_connection",
+        NetworkBanAction::RequireSasl(true) => "require_sasl, apply_existing",
+        NetworkBanAction::RequireSasl(false) => "require_sasl",
+        NetworkBanAction::DenySasl => "deny_sasl",
+    }
+}
+
+fn remaining_time(seconds: i64) -> String {
+    if seconds <= 0 {
+        return "expired".to_string();
+    }
+
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    if days > 0 {
+        format!("expires in {}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("expires in {}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("expires in {}m", minutes)
+    } else {
+        format!("expires in {}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sable_network::chert;
+
+    fn ban(pattern_text: &str, oper_reason: Option<&str>) -> state::NetworkBan {
+        let id_gen = ObjectIdGenerator::new(ServerId::new(1), EpochId::new(1));
+        state::NetworkBan {
+            id: id_gen.next_network_ban(),
+            created_by: id_gen.next_event(),
+            match_type: BanMatchType::NewConnection,
+            pattern: chert::parse::<NewConnectionBanSettings>(pattern_text)
+                .unwrap()
+                .into_root(),
+            pattern_text: pattern_text.to_string(),
+            action: NetworkBanAction::RefuseConnection(false),
+            timestamp: 0,
+            expires: 3600,
+            reason: "spam".to_string(),
+            oper_reason: oper_reason.map(str::to_string),
+            setter_info: "oper!oper@example.com".to_string(),
+        }
+    }
+
+    #[test]
+    fn filter_matches_any_field() {
+        let ban = ban("ip in 192.0.2.0/24", Some("botnet"));
+        let filter = |f: &str| ban_matches_filter(&ban, &Pattern::new(f.to_string()));
+
+        assert!(filter("*192.0.2.*"));
+        assert!(filter("oper!*"));
+        assert!(filter("spam"));
+        assert!(filter("bot*"));
+        assert!(filter(&ban.id.to_string()));
+        assert!(!filter("*198.51.100.*"));
+    }
+
+    #[test]
+    fn remaining_times() {
+        assert_eq!(remaining_time(0), "expired");
+        assert_eq!(remaining_time(45), "expires in 45s");
+        assert_eq!(remaining_time(150), "expires in 2m");
+        assert_eq!(remaining_time(3 * 3600 + 120), "expires in 3h 2m");
+        assert_eq!(remaining_time(2 * 86400 + 3600), "expires in 2d 1h");
+    }
+}
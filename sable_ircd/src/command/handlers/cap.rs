@@ -21,11 +21,19 @@ fn handle_cap(
             }
 
             if matches!(cap_list, Some("302")) {
+                // The STS policy differs between secure and insecure connections. Unix
+                // socket connections are local, so there's nothing to upgrade them to.
+                let conn = cmd.connection();
+                let secure = conn.connection.is_tls() || conn.connection.is_local();
+
                 response.send(message::Cap::new(
                     &server,
                     &UnknownTarget,
                     "LS",
-                    server.client_capabilities().supported_caps_302().as_ref(),
+                    server
+                        .client_capabilities()
+                        .supported_caps_302(secure)
+                        .as_ref(),
                 ));
             } else {
                 response.send(message::Cap::new(
@@ -0,0 +1,86 @@
+use super::*;
+
+#[allow(clippy::too_many_arguments)]
+#[command_handler("CLEARCHAN")]
+fn handle_clearchan(
+    server: &ClientServer,
+    cmd: &dyn Command,
+    response: &dyn CommandResponse,
+    source: UserSource,
+    audit: AuditLogger,
+    channel: wrapper::Channel,
+    what: &str,
+    reason: Option<&str>,
+) -> CommandResult {
+    server.policy().can_take_mass_action(&source)?;
+    server.policy().can_override_channel_modes(&source)?;
+
+    let reason = reason.unwrap_or("Channel cleared");
+
+    let (removed, noun) = if what.eq_ignore_ascii_case("USERS") {
+        // Members with any status, and opers, are left in place
+        let members: Vec<_> = channel
+            .members()
+            .filter(|m| m.permissions().is_empty() && m.user_id() != source.id())
+            .filter(|m| m.user().is_ok_and(|u| !u.is_oper()))
+            .map(|m| m.id())
+            .collect();
+
+        let removed = members.len();
+        if !members.is_empty() {
+            cmd.new_event(
+                channel.id(),
+                event::BulkChannelKick {
+                    memberships: members,
+                    source: source.id(),
+                    message: reason.to_owned(),
+                },
+            );
+        }
+        (removed, "members")
+    } else {
+        let mut list_types = Vec::new();
+        for c in what.chars() {
+            match ListModeType::from_mode_char(c) {
+                Some(list_type) => list_types.push(list_type),
+                None => return numeric_error!(UnknownMode, c),
+            }
+        }
+
+        let entries: Vec<_> = list_types
+            .into_iter()
+            .flat_map(|list_type| {
+                let list = channel.list(list_type);
+                list.entries().map(|e| e.id()).collect::<Vec<_>>()
+            })
+            .collect();
+        let removed = entries.len();
+        if !entries.is_empty() {
+            cmd.new_event(
+                channel.id(),
+                event::BulkDelListModeEntry {
+                    entries,
+                    removed_by: source.id(),
+                },
+            );
+        }
+        (removed, "list entries")
+    };
+
+    audit
+        .general()
+        .target_str(channel.name().to_string())
+        .reason(format!(
+            "Cleared {} ({} removed): {}",
+            what, removed, reason
+        ))
+        .log();
+    response.notice(&format!(
+        "Removed {} {} from {}",
+        removed,
+        noun,
+        channel.name()
+    ));
+
+    Ok(())
+}
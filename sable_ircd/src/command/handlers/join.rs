@@ -6,6 +6,7 @@ async fn handle_join(
     net: &Network,
     cmd: &dyn Command,
     source: UserSource<'_>,
+    audit: AuditLogger<'_>,
     channel_names: &str,
     keys: Option<&str>,
 ) -> CommandResult {
@@ -26,7 +27,9 @@ async fn handle_join(
 
         let (channel_id, permissions) = match net.channel_by_name(&chname) {
             Ok(channel) => {
-                server.policy().can_join(source.as_ref(), &channel, key)?;
+                let mut oper_override = OperOverride::new(server, &source, &channel);
+                oper_override.check(server.policy().can_join(source.as_ref(), &channel, key))?;
+                oper_override.record(&audit, &format!("JOIN {}", chname));
 
                 (channel.id(), MembershipFlagSet::new())
             }
@@ -1,19 +1,19 @@
 use super::*;
 
+#[allow(clippy::too_many_arguments)]
 #[command_handler("KICK")]
 async fn handle_kick(
     server: &ClientServer,
     cmd: &dyn Command,
     net: &Network,
     source: UserSource<'_>,
+    audit: AuditLogger<'_>,
     channel: wrapper::Channel<'_>,
     target: wrapper::User<'_>,
     message: Option<&str>,
 ) -> CommandResult {
-    let source_membership_id = MembershipId::new(source.id(), channel.id());
-    if net.membership(source_membership_id).is_err() {
-        return numeric_error!(NotOnChannel, channel.name());
-    }
+    let mut oper_override = OperOverride::new(server, &source, &channel);
+    oper_override.require_membership(net)?;
 
     let target_membership_id = MembershipId::new(target.id(), channel.id());
     if net.membership(target_membership_id).is_err() {
@@ -22,9 +22,15 @@ async fn handle_kick(
 
     let message = message.unwrap_or(source.nick().as_ref()).to_owned();
 
-    server
-        .policy()
-        .can_kick(&source, &channel, &target, &message)?;
+    oper_override.check(
+        server
+            .policy()
+            .can_kick(&source, &channel, &target, &message),
+    )?;
+    oper_override.record(
+        &audit,
+        &format!("KICK {} {} :{}", channel.name(), target.nick(), message),
+    );
 
     let details = event::ChannelKick {
         source: source.id(),
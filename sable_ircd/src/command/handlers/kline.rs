@@ -18,37 +18,20 @@ fn handle_kline(
 
     let duration = duration.unwrap_or(DEFAULT_KLINE_DURATION) as i64;
 
+    if let Some((user, host)) = mask.split_once('@') {
+        server.policy().can_set_kline(
+            &source,
+            &Pattern::new(user.to_owned()),
+            &Pattern::new(host.to_owned()),
+            duration,
+        )?;
+    }
+
     let parts: Vec<_> = message.splitn(2, '|').collect();
     let user_reason = parts[0];
     let oper_reason = parts.get(1);
 
-    let mask_parts: Vec<_> = mask.split('@').collect();
-
-    if let [user, host] = mask_parts[..] {
-        let user_condition = if user == "*" {
-            None
-        } else {
-            Some(format!("user == \"{}\"", user))
-        };
-
-        let host_condition = if host.parse::<std::net::IpAddr>().is_ok() {
-            format!("ip == {}", host)
-        } else if let Some((first, second)) = host.rsplit_once('/') {
-            if second.parse::<u8>().is_ok() && first.parse::<std::net::IpAddr>().is_ok() {
-                format!("ip in {}", host)
-            } else {
-                format!("host == \"{}\"", host)
-            }
-        } else {
-            format!("host == \"{}\"", host)
-        };
-
-        let condition = if let Some(user_condition) = user_condition {
-            format!("{} && {}", user_condition, host_condition)
-        } else {
-            host_condition
-        };
-
+    if let Some(condition) = kline_condition(mask) {
         let pattern = match chert::parse::<PreRegistrationBanSettings>(&condition) {
             Err(err) => {
                 tracing::error!(condition, ?err, "Translated ban condition failed to parse");
@@ -64,7 +47,7 @@ fn handle_kline(
 
         audit
             .ban()
-            .target_str(condition)
+            .target_str(condition.clone())
             .target_duration(duration)
             .reason(message.to_string())
             .log();
@@ -72,6 +55,7 @@ fn handle_kline(
         let new_kline = event::NewNetworkBan {
             match_type: BanMatchType::PreRegistration,
             pattern,
+            pattern_text: condition,
             action: NetworkBanAction::RefuseConnection(true),
             setter_info: source.nuh(),
             timestamp: sable_network::utils::now(),
@@ -88,3 +72,36 @@ fn handle_kline(
 
     Ok(())
 }
+
+/// Translate a `user@host` kline mask into the equivalent network ban pattern, or
+/// `None` if the mask is malformed
+pub(super) fn kline_condition(mask: &str) -> Option<String> {
+    let (user, host) = mask.split_once('@')?;
+    if host.contains('@') {
+        return None;
+    }
+
+    let user_condition = if user == "*" {
+        None
+    } else {
+        Some(format!("user == \"{}\"", user))
+    };
+
+    let host_condition = if host.parse::<std::net::IpAddr>().is_ok() {
+        format!("ip == {}", host)
+    } else if let Some((first, second)) = host.rsplit_once('/') {
+        if second.parse::<u8>().is_ok() && first.parse::<std::net::IpAddr>().is_ok() {
+            format!("ip in {}", host)
+        } else {
+            format!("host == \"{}\"", host)
+        }
+    } else {
+        format!("host == \"{}\"", host)
+    };
+
+    Some(if let Some(user_condition) = user_condition {
+        format!("{} && {}", user_condition, host_condition)
+    } else {
+        host_condition
+    })
+}
@@ -0,0 +1,34 @@
+use super::*;
+
+#[command_handler("LINKS")]
+fn handle_links(
+    server: &ClientServer,
+    net: &Network,
+    response: &dyn CommandResponse,
+    mask: Option<&str>,
+) -> CommandResult {
+    let filter = mask.map(|m| Pattern::new(m.to_owned()));
+    let now = sable_network::utils::now();
+
+    let mut servers: Vec<_> = net
+        .servers()
+        .filter(|s| filter.as_ref().map_or(true, |f| f.matches(s.name())))
+        .collect();
+    // Every server is directly linked to every other, so show ourselves first and
+    // everything else one hop away
+    servers.sort_by_key(|s| (s.id() != server.node().id(), *s.name()));
+
+    for s in servers {
+        let hopcount = if s.id() == server.node().id() { 0 } else { 1 };
+        response.numeric(make_numeric!(
+            Links,
+            &s,
+            server.name(),
+            hopcount,
+            &crate::utils::describe_server(&s, now)
+        ));
+    }
+    response.numeric(make_numeric!(EndOfLinks, mask.unwrap_or("*")));
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use super::*;
+
+#[command_handler("MAP")]
+fn handle_map(
+    server: &ClientServer,
+    net: &Network,
+    response: &dyn CommandResponse,
+) -> CommandResult {
+    let now = sable_network::utils::now();
+
+    let mut servers: Vec<_> = net.servers().collect();
+    servers.sort_by_key(|s| (s.id() != server.node().id(), *s.name()));
+
+    for s in servers {
+        let prefix = if s.id() == server.node().id() {
+            ""
+        } else {
+            "`- "
+        };
+        response.numeric(make_numeric!(
+            Map,
+            &format!(
+                "{}{} [{:?}] {}",
+                prefix,
+                s.name(),
+                s.id(),
+                crate::utils::describe_server(&s, now)
+            )
+        ));
+    }
+    response.numeric(make_numeric!(MapEnd));
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use super::masskill::{mass_action_targets, mass_kill, parse_mass_pattern, report_mass_matches};
+use super::*;
+use sable_network::network::ban::*;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct MassBanArguments {
+    pattern: String,
+    duration: Option<i64>,
+    reason: Option<String>,
+    oper_reason: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// In minutes, as for `KLINE`
+const DEFAULT_MASSBAN_DURATION: i64 = 1440;
+
+#[command_handler("MASSBAN")]
+fn handle_massban(
+    server: &ClientServer,
+    net: &Network,
+    source: UserSource,
+    response: &dyn CommandResponse,
+    audit: AuditLogger,
+    args_str: &str,
+) -> CommandResult {
+    server.policy().can_take_mass_action(&source)?;
+    server.policy().can_manage_bans(&source)?;
+
+    let args: MassBanArguments = match serde_json::from_str(args_str) {
+        Ok(args) => args,
+        Err(e) => {
+            response.send(message::Fail::new(
+                "MASSBAN",
+                "INVALID_ARGUMENTS",
+                "",
+                &e.to_string(),
+            ));
+            return Ok(());
+        }
+    };
+
+    let Some(pattern) = parse_mass_pattern("MASSBAN", response, &args.pattern) else {
+        return Ok(());
+    };
+    let targets = mass_action_targets(server, net, &source, &pattern)?;
+
+    if args.dry_run {
+        report_mass_matches(response, &targets);
+        return Ok(());
+    }
+
+    let Some(reason) = args.reason else {
+        response.send(message::Fail::new(
+            "MASSBAN",
+            "INVALID_ARGUMENTS",
+            "",
+            "A reason is required",
+        ));
+        return Ok(());
+    };
+    let duration = args.duration.unwrap_or(DEFAULT_MASSBAN_DURATION);
+
+    audit
+        .ban()
+        .target_str(args.pattern.clone())
+        .target_duration(duration)
+        .reason(format!("{} ({} users)", reason, targets.len()))
+        .log();
+
+    let timestamp = sable_network::utils::now();
+    let new_ban = event::details::NewNetworkBan {
+        match_type: BanMatchType::PreRegistration,
+        pattern,
+        pattern_text: args.pattern,
+        action: NetworkBanAction::RefuseConnection(true),
+        timestamp,
+        expires: timestamp + duration * 60,
+        reason: reason.clone(),
+        oper_reason: args.oper_reason,
+        setter_info: source.nuh(),
+    };
+    server
+        .node()
+        .submit_event(server.ids().next_network_ban(), new_ban);
+
+    // Existing connections aren't checked against new bans, so remove them here
+    mass_kill(server, &targets, format!("Banned: {}", reason));
+    response.notice(&format!(
+        "Added ban for {}m, disconnecting {} users",
+        duration,
+        targets.len()
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use super::*;
+use event::*;
+use sable_network::{chert, network::ban::*};
+use serde::Deserialize;
+
+/// How many matching users are listed individually in a dry run
+const MAX_LISTED_MATCHES: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct MassKillArguments {
+    pattern: String,
+    reason: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[command_handler("MASSKILL")]
+fn handle_masskill(
+    server: &ClientServer,
+    net: &Network,
+    source: UserSource,
+    response: &dyn CommandResponse,
+    audit: AuditLogger,
+    args_str: &str,
+) -> CommandResult {
+    server.policy().can_take_mass_action(&source)?;
+
+    let args: MassKillArguments = match serde_json::from_str(args_str) {
+        Ok(args) => args,
+        Err(e) => {
+            response.send(message::Fail::new(
+                "MASSKILL",
+                "INVALID_ARGUMENTS",
+                "",
+                &e.to_string(),
+            ));
+            return Ok(());
+        }
+    };
+
+    let Some(pattern) = parse_mass_pattern("MASSKILL", response, &args.pattern) else {
+        return Ok(());
+    };
+    let targets = mass_action_targets(server, net, &source, &pattern)?;
+
+    if args.dry_run {
+        report_mass_matches(response, &targets);
+        return Ok(());
+    }
+
+    let Some(reason) = args.reason else {
+        response.send(message::Fail::new(
+            "MASSKILL",
+            "INVALID_ARGUMENTS",
+            "",
+            "A reason is required",
+        ));
+        return Ok(());
+    };
+
+    audit
+        .kill()
+        .target_str(args.pattern)
+        .reason(format!("{} ({} users)", reason, targets.len()))
+        .log();
+
+    mass_kill(
+        server,
+        &targets,
+        format!("Killed by {} ({})", source.nick(), reason),
+    );
+    response.notice(&format!("Killed {} users", targets.len()));
+
+    Ok(())
+}
+
+/// Parse a mass action pattern, reporting any error to the user
+pub(super) fn parse_mass_pattern(
+    command: &str,
+    response: &dyn CommandResponse,
+    text: &str,
+) -> Option<chert::NodeBoolean> {
+    match chert::parse::<PreRegistrationBanSettings>(text) {
+        Ok(ast) => Some(ast.into_root()),
+        Err(e) => {
+            response.send(message::Fail::new(
+                command,
+                "INVALID_PATTERN",
+                "",
+                &format!("{:?}", e),
+            ));
+            None
+        }
+    }
+}
+
+/// Find the users to be acted upon by a mass action. Opers, including the one
+/// taking the action, are never included.
+pub(super) fn mass_action_targets<'a>(
+    server: &ClientServer,
+    net: &'a Network,
+    source: &wrapper::User,
+    pattern: &chert::NodeBoolean,
+) -> Result<Vec<wrapper::User<'a>>, CommandError> {
+    let targets: Vec<_> = users_matching(net, pattern)
+        .into_iter()
+        .filter(|user| !user.is_oper() && user.id() != source.id())
+        .collect();
+
+    for target in &targets {
+        server.policy().can_kill(source, target)?;
+    }
+    Ok(targets)
+}
+
+/// Describe the users matched by a dry run
+pub(super) fn report_mass_matches(response: &dyn CommandResponse, targets: &[wrapper::User]) {
+    for target in targets.iter().take(MAX_LISTED_MATCHES) {
+        let ips: Vec<_> = target.connections().map(|c| c.ip().to_string()).collect();
+        response.notice(&format!("{} [{}]", target.nuh(), ips.join(", ")));
+    }
+    if targets.len() > MAX_LISTED_MATCHES {
+        response.notice(&format!(
+            "... and {} more",
+            targets.len() - MAX_LISTED_MATCHES
+        ));
+    }
+    response.notice(&format!("{} users matched", targets.len()));
+}
+
+/// Remove every user in `targets` from the network with a single event
+pub(super) fn mass_kill(server: &ClientServer, targets: &[wrapper::User], message: String) {
+    if targets.is_empty() {
+        return;
+    }
+
+    server.add_action(CommandAction::state_change(
+        server.node().id(),
+        details::BulkUserQuit {
+            users: targets.iter().map(|u| u.id()).collect(),
+            message,
+        },
+    ));
+}
@@ -1,18 +1,20 @@
 use super::*;
 
+#[allow(clippy::too_many_arguments)]
 #[command_handler("MODE")]
 async fn handle_mode(
     server: &ClientServer,
     source: UserSource<'_>,
     cmd: &dyn Command,
     response: &dyn CommandResponse,
+    audit: AuditLogger<'_>,
     target: TargetParameter<'_>,
     mode_str: Option<&str>,
     args: ArgList<'_>,
 ) -> CommandResult {
     match target {
         TargetParameter::Channel(chan) => {
-            handle_channel_mode(server, &source, cmd, response, chan, mode_str, args).await
+            handle_channel_mode(server, &source, cmd, response, &audit, chan, mode_str, args).await
         }
         TargetParameter::User(user) => {
             handle_user_mode(server, &source, cmd, response, user, mode_str, args).await
@@ -47,7 +49,7 @@ async fn handle_user_mode(
     response: &dyn CommandResponse,
     user: wrapper::User<'_>,
     mode_str: Option<&str>,
-    _args: ArgList<'_>,
+    mut args: ArgList<'_>,
 ) -> CommandResult {
     if source.id() != user.id() {
         return numeric_error!(CantChangeOtherUserMode);
@@ -58,6 +60,7 @@ async fn handle_user_mode(
     let mut sent_unknown = false;
     let mut added = UserModeSet::new();
     let mut removed = UserModeSet::new();
+    let mut new_snomask = None;
 
     let mut dir = Direction::Query;
 
@@ -77,9 +80,20 @@ async fn handle_user_mode(
             match dir {
                 Direction::Add => {
                     added |= flag;
+                    if flag == UserModeFlag::ServerNotices {
+                        let param = if args.is_empty() {
+                            None
+                        } else {
+                            Some(args.next::<&str>()?)
+                        };
+                        new_snomask = Some(parse_snomask(server, source, param));
+                    }
                 }
                 Direction::Rem => {
                     removed |= flag;
+                    if flag == UserModeFlag::ServerNotices {
+                        new_snomask = Some(SnomaskSet::new());
+                    }
                 }
                 _ => {}
             }
@@ -88,6 +102,15 @@ async fn handle_user_mode(
             sent_unknown = true;
         }
     }
+    if let Some(snomask) = new_snomask {
+        server.set_snomask(source.id(), snomask);
+        if snomask.is_empty() {
+            // Nothing left to receive, so +s is meaningless
+            added &= !UserModeSet::from(UserModeFlag::ServerNotices);
+            removed |= UserModeFlag::ServerNotices;
+        }
+        response.numeric(make_numeric!(SnoMask, &format!("+{}", snomask.to_chars())));
+    }
     if !added.is_empty() || !removed.is_empty() {
         let detail = event::UserModeChange {
             changed_by: source.id().into(),
@@ -100,11 +123,49 @@ async fn handle_user_mode(
     Ok(())
 }
 
+/// Apply a snomask parameter such as `+ck-n` to `source`'s current snomask. Categories
+/// they aren't permitted to receive are ignored; with no parameter, every permitted
+/// category is selected.
+fn parse_snomask(
+    server: &ClientServer,
+    source: &wrapper::User<'_>,
+    param: Option<&str>,
+) -> SnomaskSet {
+    let permitted = |flag: SnomaskFlag| server.policy().can_receive_snomask(source, flag).is_ok();
+
+    let Some(param) = param else {
+        let mut snomask = SnomaskSet::new();
+        for flag in SnomaskSet::all() {
+            if permitted(flag) {
+                snomask |= flag;
+            }
+        }
+        return snomask;
+    };
+
+    let mut snomask = server.snomask(source.id());
+    let mut adding = true;
+    for c in param.chars() {
+        match c {
+            '+' => adding = true,
+            '-' => adding = false,
+            _ => match SnomaskFlag::from_mode_char(c) {
+                Some(flag) if !adding => snomask &= !SnomaskSet::from(flag),
+                Some(flag) if permitted(flag) => snomask |= flag,
+                _ => (),
+            },
+        }
+    }
+    snomask
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handle_channel_mode(
     server: &ClientServer,
     source: &wrapper::User<'_>,
     cmd: &dyn Command,
     response: &dyn CommandResponse,
+    audit: &AuditLogger<'_>,
     chan: wrapper::Channel<'_>,
     mode_str: Option<&str>,
     mut args: ArgList<'_>,
@@ -116,6 +177,13 @@ async fn handle_channel_mode(
         return Ok(());
     };
 
+    let mut oper_override = OperOverride::new(server, source, &chan);
+    let mut description = format!("MODE {} {}", chan.name(), mode_str);
+    for arg in args.iter() {
+        description.push(' ');
+        description.push_str(arg);
+    }
+
     let mut sent_unknown = false;
     let mut added = ChannelModeSet::new();
     let mut removed = ChannelModeSet::new();
@@ -126,7 +194,7 @@ async fn handle_channel_mode(
         if let Ok(d) = Direction::try_from(c) {
             dir = d;
         } else if let Some(flag) = ChannelModeFlag::from_mode_char(c) {
-            server.policy().can_change_mode(source, &chan, flag)?;
+            oper_override.check(server.policy().can_change_mode(source, &chan, flag))?;
             match dir {
                 Direction::Add => {
                     added |= flag;
@@ -146,20 +214,25 @@ async fn handle_channel_mode(
 
             match dir {
                 Direction::Add => {
-                    server
-                        .policy()
-                        .can_grant_permission(source, &chan, &target, flag)?;
+                    oper_override.check(
+                        server
+                            .policy()
+                            .can_grant_permission(source, &chan, &target, flag),
+                    )?;
                     perm_added |= flag;
                 }
                 Direction::Rem => {
-                    server
-                        .policy()
-                        .can_remove_permission(source, &chan, &target, flag)?;
+                    oper_override.check(
+                        server
+                            .policy()
+                            .can_remove_permission(source, &chan, &target, flag),
+                    )?;
                     perm_removed |= flag;
                 }
                 _ => {}
             }
 
+            oper_override.record(audit, &description);
             let detail = event::MembershipFlagChange {
                 changed_by: source.id().into(),
                 added: perm_added,
@@ -170,17 +243,18 @@ async fn handle_channel_mode(
             let list = chan.list(list_type);
 
             if dir == Direction::Query || args.is_empty() {
-                server.policy().can_query_list(source, &chan, list_type)?;
+                oper_override
+                    .check_viewing(server.policy().can_query_list(source, &chan, list_type))?;
                 send_channel_banlike_list(response, &chan, &list)?;
             } else {
                 let mask = args.next::<&str>()?;
 
                 if dir == Direction::Add {
-                    server
-                        .policy()
-                        .can_set_ban(source, &chan, list_type, mask)?;
+                    oper_override
+                        .check(server.policy().can_set_ban(source, &chan, list_type, mask))?;
                     server.policy().validate_ban_mask(mask, list_type, &chan)?;
 
+                    oper_override.record(audit, &description);
                     let detail = event::NewListModeEntry {
                         list: list.id(),
                         pattern: Pattern::new(mask.to_owned()),
@@ -191,10 +265,13 @@ async fn handle_channel_mode(
                 } else {
                     // We've already tested for Direction::Query above, so this is definitely Remove
                     if let Some(entry) = list.entries().find(|e| e.pattern() == mask) {
-                        server
-                            .policy()
-                            .can_unset_ban(source, &chan, list_type, mask)?;
+                        oper_override.check(
+                            server
+                                .policy()
+                                .can_unset_ban(source, &chan, list_type, mask),
+                        )?;
 
+                        oper_override.record(audit, &description);
                         let detail = event::DelListModeEntry {
                             removed_by: source.id(),
                         };
@@ -211,11 +288,15 @@ async fn handle_channel_mode(
                         Ok(key) => key,
                         Err(_) => return numeric_error!(InvalidKey, &chan.name()),
                     };
-                    server.policy().can_set_key(source, &chan, Some(&new_key))?;
+                    oper_override.check(server.policy().can_set_key(
+                        source,
+                        &chan,
+                        Some(&new_key),
+                    ))?;
                     key_change = OptionChange::Set(new_key);
                 }
                 Direction::Rem => {
-                    server.policy().can_set_key(source, &chan, None)?;
+                    oper_override.check(server.policy().can_set_key(source, &chan, None))?;
                     key_change = OptionChange::Unset;
                 }
             }
@@ -224,6 +305,8 @@ async fn handle_channel_mode(
             sent_unknown = true;
         }
     }
+    oper_override.record(audit, &description);
+
     if !added.is_empty() || !removed.is_empty() || !key_change.is_no_change() {
         let detail = event::ChannelModeChange {
             changed_by: source.id().into(),
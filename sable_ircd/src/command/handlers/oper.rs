@@ -3,6 +3,7 @@ use event::*;
 
 #[command_handler("OPER")]
 fn handle_oper(
+    cmd: &dyn Command,
     response: &dyn CommandResponse,
     server: &ClientServer,
     net: &Network,
@@ -14,7 +15,17 @@ fn handle_oper(
     server.policy().user_can_oper(&source)?;
 
     if let Some(conf) = find_oper_block(net, &source, oper_name) {
-        if server.policy().authenticate(conf, oper_name, password) {
+        let tls_fingerprint = cmd
+            .connection()
+            .tls_info()
+            .and_then(|ti| ti.fingerprint.as_deref());
+
+        if server
+            .policy()
+            .authenticate(conf, &source, oper_name, password, tls_fingerprint)
+        {
+            let privileges = net.config().oper_privileges(conf);
+
             audit.general().log();
 
             response.numeric(make_numeric!(YoureOper));
@@ -22,6 +33,8 @@ fn handle_oper(
                 source.id(),
                 details::OperUp {
                     oper_name: oper_name.to_owned(),
+                    class: conf.class.clone(),
+                    privileges,
                 },
             ));
             Ok(())
@@ -35,10 +35,11 @@ async fn role_list(
     cmd: &dyn Command,
     chan: wrapper::ChannelRegistration<'_>,
 ) -> CommandResult {
-    cmd.server()
-        .node()
-        .policy()
-        .can_view_roles(&source.user, &chan)?;
+    let policy = cmd.server().node().policy();
+    // Services administrators can inspect any registration
+    if policy.can_manage_services(&source.user).is_err() {
+        policy.can_view_roles(&source.user, &chan)?;
+    }
 
     cmd.notice(format_args!("Role list for {}", chan.name()));
     cmd.notice(" ");
@@ -1,5 +1,6 @@
 use super::*;
 
+#[allow(clippy::too_many_arguments)]
 #[command_handler("TOPIC")]
 async fn handle_topic(
     cmd: &dyn Command,
@@ -7,11 +8,14 @@ async fn handle_topic(
     net: &Network,
     source: UserSource<'_>,
     response: &dyn CommandResponse,
+    audit: AuditLogger<'_>,
     channel: wrapper::Channel<'_>,
     new_topic: Option<&str>,
 ) -> CommandResult {
     if let Some(text) = new_topic {
-        server.policy().can_set_topic(&source, &channel, text)?;
+        let mut oper_override = OperOverride::new(server, &source, &channel);
+        oper_override.check(server.policy().can_set_topic(&source, &channel, text))?;
+        oper_override.record(&audit, &format!("TOPIC {} :{}", channel.name(), text));
 
         let details = event::details::NewChannelTopic {
             channel: channel.id(),
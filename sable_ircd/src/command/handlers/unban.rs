@@ -0,0 +1,38 @@
+use super::*;
+use event::*;
+
+#[command_handler("UNBAN")]
+fn handle_unban(
+    server: &ClientServer,
+    net: &Network,
+    response: &dyn CommandResponse,
+    source: UserSource,
+    audit: AuditLogger,
+    ban_id: &str,
+) -> CommandResult {
+    server.policy().can_manage_bans(&source)?;
+
+    let Ok(id) = ban_id.parse::<NetworkBanId>() else {
+        response.notice(&format!("Invalid ban ID: {}", ban_id));
+        return Ok(());
+    };
+    let Some(ban) = net.network_bans().get(&id) else {
+        response.notice(&format!("No such ban: {}", id));
+        return Ok(());
+    };
+
+    audit
+        .ban()
+        .target_str(format!("{} ({})", id, super::bans::pattern_text(ban)))
+        .log();
+
+    server.node().submit_event(
+        id,
+        details::RemoveNetworkBan {
+            remover: source.id(),
+        },
+    );
+    response.notice(&format!("Removed ban {}", id));
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use super::*;
+use event::*;
+use sable_network::network::ban::*;
+
+#[command_handler("UNKLINE")]
+fn handle_unkline(
+    server: &ClientServer,
+    net: &Network,
+    response: &dyn CommandResponse,
+    source: UserSource,
+    audit: AuditLogger,
+    mask: &str,
+) -> CommandResult {
+    server.policy().can_remove_kline(&source)?;
+
+    let Some(condition) = super::kline::kline_condition(mask) else {
+        response.notice("Invalid kline mask");
+        return Ok(());
+    };
+
+    let matching = find_klines(net.network_bans(), &condition);
+
+    if matching.is_empty() {
+        response.notice(&format!("No kline found for {}", mask));
+        return Ok(());
+    }
+
+    for id in matching {
+        audit
+            .ban()
+            .target_str(format!("{} ({})", id, condition))
+            .log();
+
+        server.node().submit_event(
+            id,
+            details::RemoveNetworkBan {
+                remover: source.id(),
+            },
+        );
+        response.notice(&format!("Removed kline {} for {}", id, mask));
+    }
+
+    Ok(())
+}
+
+/// Find the klines with the given condition. Klines are stored as the pattern they
+/// were translated to, so the same translation finds them again.
+fn find_klines(bans: &BanRepository, condition: &str) -> Vec<NetworkBanId> {
+    bans.iter()
+        .filter(|ban| {
+            matches!(ban.match_type, BanMatchType::PreRegistration) && ban.pattern_text == condition
+        })
+        .map(|ban| ban.id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sable_network::chert;
+
+    fn ban(
+        id_gen: &ObjectIdGenerator,
+        match_type: BanMatchType,
+        pattern_text: &str,
+    ) -> state::NetworkBan {
+        let pattern = match match_type {
+            BanMatchType::PreRegistration => {
+                chert::parse::<PreRegistrationBanSettings>(pattern_text).map(|p| p.into_root())
+            }
+            _ => chert::parse::<NewConnectionBanSettings>(pattern_text).map(|p| p.into_root()),
+        };
+        state::NetworkBan {
+            id: id_gen.next_network_ban(),
+            created_by: id_gen.next_event(),
+            match_type,
+            pattern: pattern.unwrap(),
+            pattern_text: pattern_text.to_string(),
+            action: NetworkBanAction::RefuseConnection(true),
+            timestamp: 0,
+            expires: i64::MAX,
+            reason: "banned".to_string(),
+            oper_reason: None,
+            setter_info: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn finds_klines_by_translated_mask() {
+        let id_gen = ObjectIdGenerator::new(ServerId::new(1), EpochId::new(1));
+        let condition = kline::kline_condition("*@192.0.2.0/24").unwrap();
+
+        let kline = ban(&id_gen, BanMatchType::PreRegistration, &condition);
+        let other_kline = ban(
+            &id_gen,
+            BanMatchType::PreRegistration,
+            &kline::kline_condition("*@198.51.100.1").unwrap(),
+        );
+        // Not a kline, despite having the same pattern
+        let dline = ban(&id_gen, BanMatchType::NewConnection, "ip in 192.0.2.0/24");
+        let kline_id = kline.id;
+
+        let bans = BanRepository::from_ban_set(vec![kline, other_kline, dline]);
+
+        assert_eq!(find_klines(&bans, &condition), vec![kline_id]);
+        assert!(find_klines(&bans, "ip in 203.0.113.0/24").is_empty());
+    }
+}
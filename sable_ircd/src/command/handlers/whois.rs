@@ -27,6 +27,26 @@ fn whois_handler(
         response.numeric(make_numeric!(Away, &target, away_reason));
     }
 
+    // Oper status and privileges are only shown to other opers
+    if let (true, Some(privileges)) = (source.is_oper(), target.oper_privileges()) {
+        let privilege_list: Vec<_> = privileges
+            .privileges
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let class = privileges.class.as_deref().unwrap_or("default");
+        response.numeric(make_numeric!(
+            WhoisOperator,
+            &target,
+            &format!(
+                "is an IRC operator ({}, class {}: {})",
+                privileges.oper_name,
+                class,
+                privilege_list.join(", ")
+            )
+        ));
+    }
+
     if server.policy().can_see_connection_info(&source, &target) {
         for conn in target.connections() {
             response.numeric(make_numeric!(WhoisServer, &target, &conn.server()?));
@@ -21,6 +21,9 @@ pub use error::*;
 mod dispatcher;
 pub use dispatcher::*;
 
+mod oper_override;
+pub use oper_override::*;
+
 mod plumbing;
 pub use plumbing::{ArgListIter, Command, LoggedInUserSource, PreClientSource, UserSource};
 
@@ -38,15 +41,22 @@ mod handlers {
     use std::ops::Deref;
 
     mod admin;
+    mod audit;
     mod away;
     mod ban;
+    mod bans;
     mod cap;
     mod chathistory;
+    mod clearchan;
     mod invite;
     mod join;
     mod kick;
     mod kill;
     mod kline;
+    mod links;
+    mod map;
+    mod massban;
+    mod masskill;
     mod mode;
     mod monitor;
     mod motd;
@@ -62,6 +72,8 @@ mod handlers {
     pub mod register;
     mod rename;
     mod topic;
+    mod unban;
+    mod unkline;
     mod user;
     mod userhost;
     mod version;
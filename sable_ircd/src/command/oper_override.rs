@@ -0,0 +1,96 @@
+use super::*;
+use sable_network::policy::{ChannelPermissionError, PermissionError, PermissionResult};
+
+/// Allows an oper who has enabled override (user mode `+p`) to bypass the channel
+/// permission checks for a single command, and records when they have done so.
+pub struct OperOverride<'a> {
+    server: &'a ClientServer,
+    source: UserId,
+    channel: ChannelId,
+    channel_name: ChannelName,
+    enabled: bool,
+    used: bool,
+    recorded: bool,
+}
+
+impl<'a> OperOverride<'a> {
+    pub fn new(
+        server: &'a ClientServer,
+        source: &wrapper::User,
+        channel: &wrapper::Channel,
+    ) -> Self {
+        let enabled = source.mode().has_mode(UserModeFlag::OperOverride)
+            && server.policy().can_override_channel_modes(source).is_ok();
+
+        Self {
+            server,
+            source: source.id(),
+            channel: channel.id(),
+            channel_name: *channel.name(),
+            enabled,
+            used: false,
+            recorded: false,
+        }
+    }
+
+    /// Pass through the result of a permission check, unless it is a refusal from the
+    /// channel which can be overridden
+    pub fn check(&mut self, result: PermissionResult) -> PermissionResult {
+        match result {
+            Err(PermissionError::Channel(..)) if self.enabled => {
+                self.used = true;
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// As [`check`](Self::check), for a check which only allows viewing information.
+    /// Overriding such a check isn't recorded, as nothing is changed.
+    pub fn check_viewing(&self, result: PermissionResult) -> PermissionResult {
+        match result {
+            Err(PermissionError::Channel(..)) if self.enabled => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Require that the source is a member of the channel, unless overriding
+    pub fn require_membership(&mut self, net: &Network) -> PermissionResult {
+        if net
+            .membership(MembershipId::new(self.source, self.channel))
+            .is_ok()
+        {
+            return Ok(());
+        }
+        self.check(Err(PermissionError::Channel(
+            self.channel_name,
+            ChannelPermissionError::NotOnChannel,
+        )))
+    }
+
+    /// If override was needed, log `description` of the action taken to the audit log
+    /// and tell the channel about it. This should be called before the action takes
+    /// effect; it only records the first time override is needed.
+    pub fn record(&mut self, audit: &AuditLogger, description: &str) {
+        if !self.used || self.recorded {
+            return;
+        }
+        self.recorded = true;
+
+        audit
+            .general()
+            .target_str(self.channel_name.to_string())
+            .reason(format!("Oper override: {}", description))
+            .log();
+
+        self.server.node().submit_event(
+            self.server.ids().next_message(),
+            event::details::NewMessage {
+                source: self.source,
+                target: self.channel.into(),
+                message_type: state::MessageType::Notice,
+                text: format!("*** Oper override used: {}", description),
+            },
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use super::*;
 
+use crate::server::config::ConnectionClassConfig;
 use client_listener::ConnectionId;
+use ipnet::IpNet;
 use sable_network::prelude::*;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    net::IpAddr,
     sync::{Arc, Weak},
 };
 
@@ -14,6 +17,26 @@ pub(super) struct ConnectionCollection {
     user_conn_to_connid: HashMap<UserConnectionId, ConnectionId>,
     user_to_connid: HashMap<UserId, Vec<ConnectionId>>,
     flooded_connections: Vec<Arc<ClientConnection>>,
+    /// User connections re-created after a network resync, whose creation
+    /// events haven't yet been processed
+    moved_user_connections: HashSet<UserConnectionId>,
+    class_counts: ClassCounts,
+}
+
+/// Counts the connections in each connection class, for enforcing class limits
+/// without having to scan every connection
+#[derive(Debug, Default)]
+struct ClassCounts {
+    classes: HashMap<String, ClassCount>,
+    /// The class and address group under which each connection is counted
+    counted_as: HashMap<ConnectionId, (String, Option<IpNet>)>,
+}
+
+/// Connections in a single connection class, in total and per address group
+#[derive(Debug, Default)]
+struct ClassCount {
+    total: usize,
+    per_group: HashMap<IpNet, usize>,
 }
 
 /// Iterator over connections belonging to a given user
@@ -37,6 +60,8 @@ impl ConnectionCollection {
             user_conn_to_connid: HashMap::new(),
             user_to_connid: HashMap::new(),
             flooded_connections: Vec::new(),
+            moved_user_connections: HashSet::new(),
+            class_counts: ClassCounts::default(),
         }
     }
 
@@ -51,6 +76,7 @@ impl ConnectionCollection {
                 // so they should be disconnected for flooding. First, check whether it's a
                 // registered user connection, or a pre-client
                 if let Some(conn) = self.client_connections.remove(&conn_id) {
+                    self.uncount(conn_id);
                     self.flooded_connections.push(conn);
                 }
             }
@@ -63,14 +89,49 @@ impl ConnectionCollection {
             .flat_map(|(id, conn)| conn.poll_messages().map(move |message| (*id, message)))
     }
 
-    /// Insert a new connection, with no associated user ID
-    pub fn add(&mut self, id: ConnectionId, conn: ClientConnection) -> Weak<ClientConnection> {
+    /// Insert a new connection, with no associated user ID, in the given connection class
+    pub fn add(
+        &mut self,
+        id: ConnectionId,
+        conn: ClientConnection,
+        class: &ConnectionClassConfig,
+    ) -> Weak<ClientConnection> {
         let conn = Arc::new(conn);
         let weak_conn = Arc::downgrade(&conn);
+        self.count(id, &conn, class);
         self.client_connections.insert(id, conn);
         weak_conn
     }
 
+    /// Move an existing connection into a new connection class, and apply its settings
+    pub fn set_class(&mut self, id: ConnectionId, class: &ConnectionClassConfig) {
+        if let Some(conn) = self.client_connections.get(&id).map(Arc::clone) {
+            conn.set_class(class);
+            self.count(id, &conn, class);
+        }
+    }
+
+    /// Count the connections in `class`, not including `exclude`. Returns the total,
+    /// and the number in the same address group as `ip`.
+    pub fn class_count(
+        &self,
+        class: &ConnectionClassConfig,
+        ip: Option<IpAddr>,
+        exclude: Option<ConnectionId>,
+    ) -> (usize, usize) {
+        self.class_counts.count(class, ip, exclude)
+    }
+
+    fn count(&mut self, id: ConnectionId, conn: &ClientConnection, class: &ConnectionClassConfig) {
+        // Connections over a Unix socket all share an address, so aren't grouped by it
+        let ip = (!conn.connection.is_local()).then(|| conn.remote_addr());
+        self.class_counts.insert(id, class, ip);
+    }
+
+    fn uncount(&mut self, id: ConnectionId) {
+        self.class_counts.remove(id);
+    }
+
     /// Associate an existing connection with a user
     ///
     /// A UserId and UserConnectionId are both required - if the user has a client connection
@@ -90,6 +151,34 @@ impl ConnectionCollection {
         self.user_conn_to_connid.insert(user_connection, to);
     }
 
+    /// Re-associate the connection known by one UserConnectionId with another,
+    /// returning it if it exists.
+    ///
+    /// The new ID is remembered until [`take_moved_user_connection`](Self::take_moved_user_connection)
+    /// is called for it.
+    pub fn move_user_connection(
+        &mut self,
+        from: UserConnectionId,
+        to: UserConnectionId,
+    ) -> Option<Arc<ClientConnection>> {
+        let conn_id = self.user_conn_to_connid.remove(&from)?;
+        let conn = self.client_connections.get(&conn_id)?;
+
+        if let Some(user_id) = conn.user_id() {
+            conn.set_user(user_id, to);
+        }
+        self.user_conn_to_connid.insert(to, conn_id);
+        self.moved_user_connections.insert(to);
+
+        Some(Arc::clone(conn))
+    }
+
+    /// Check whether the given UserConnectionId was created by
+    /// [`move_user_connection`](Self::move_user_connection), and forget it if so
+    pub fn take_moved_user_connection(&mut self, id: UserConnectionId) -> bool {
+        self.moved_user_connections.remove(&id)
+    }
+
     /// Remove a connection, by connection ID
     pub fn remove(&mut self, id: ConnectionId) {
         if let Some(conn) = self.client_connections.get(&id) {
@@ -102,6 +191,7 @@ impl ConnectionCollection {
             }
         }
         self.client_connections.remove(&id);
+        self.uncount(id);
     }
 
     /// Remove all connections associated with the given user ID
@@ -109,6 +199,7 @@ impl ConnectionCollection {
         if let Some(conn_ids) = self.user_to_connid.remove(&id) {
             for connid in conn_ids {
                 self.client_connections.remove(&connid);
+                self.uncount(connid);
             }
         }
     }
@@ -117,6 +208,7 @@ impl ConnectionCollection {
     pub fn remove_user_connection(&mut self, id: UserConnectionId) {
         if let Some(conn_id) = self.user_conn_to_connid.remove(&id) {
             self.client_connections.remove(&conn_id);
+            self.uncount(conn_id);
         }
     }
 
@@ -190,7 +282,8 @@ impl ConnectionCollection {
         }
     }
 
-    /// Restore a collection from a previously stored state
+    /// Restore a collection from a previously stored state. Connections aren't counted
+    /// in their classes until [`set_class`](Self::set_class) is called for each.
     pub fn restore_from(
         state: ConnectionCollectionState,
         listener_collection: &client_listener::ListenerCollection,
@@ -214,6 +307,72 @@ impl ConnectionCollection {
     }
 }
 
+impl ClassCounts {
+    /// Count a connection as a member of `class`, in place of any class in which it
+    /// was previously counted
+    fn insert(&mut self, id: ConnectionId, class: &ConnectionClassConfig, ip: Option<IpAddr>) {
+        self.remove(id);
+
+        let group = ip.map(|ip| class.address_group(ip));
+        let count = self.classes.entry(class.name.clone()).or_default();
+        count.total += 1;
+        if let Some(group) = group {
+            *count.per_group.entry(group).or_default() += 1;
+        }
+        self.counted_as.insert(id, (class.name.clone(), group));
+    }
+
+    /// Stop counting a connection as a member of its class
+    fn remove(&mut self, id: ConnectionId) {
+        let Some((class, group)) = self.counted_as.remove(&id) else {
+            return;
+        };
+        let Some(count) = self.classes.get_mut(&class) else {
+            return;
+        };
+        count.total -= 1;
+        if let Some(group) = group {
+            if let Some(same_group) = count.per_group.get_mut(&group) {
+                *same_group -= 1;
+                if *same_group == 0 {
+                    count.per_group.remove(&group);
+                }
+            }
+        }
+        if count.total == 0 {
+            self.classes.remove(&class);
+        }
+    }
+
+    fn count(
+        &self,
+        class: &ConnectionClassConfig,
+        ip: Option<IpAddr>,
+        exclude: Option<ConnectionId>,
+    ) -> (usize, usize) {
+        let Some(count) = self.classes.get(&class.name) else {
+            return (0, 0);
+        };
+        let group = ip.map(|ip| class.address_group(ip));
+        let mut total = count.total;
+        let mut same_group = group
+            .and_then(|group| count.per_group.get(&group).copied())
+            .unwrap_or(0);
+
+        if let Some((excluded_class, excluded_group)) =
+            exclude.and_then(|id| self.counted_as.get(&id))
+        {
+            if *excluded_class == class.name {
+                total -= 1;
+                if excluded_group.is_some() && *excluded_group == group {
+                    same_group -= 1;
+                }
+            }
+        }
+        (total, same_group)
+    }
+}
+
 impl<'a> Iterator for UserConnectionIter<'a> {
     type Item = Arc<ClientConnection>;
 
@@ -235,3 +394,55 @@ impl ConnectionCollectionLockHelper for parking_lot::RwLock<ConnectionCollection
         self.read().get(id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client_listener::ListenerId;
+
+    fn class(name: &str) -> ConnectionClassConfig {
+        ConnectionClassConfig {
+            name: name.to_string(),
+            ..ConnectionClassConfig::default_class()
+        }
+    }
+
+    fn conn_id(n: i64) -> ConnectionId {
+        ConnectionId::new(ListenerId::new(1), n)
+    }
+
+    #[test]
+    fn class_counts_follow_connections() {
+        let first = class("first");
+        let second = class("second");
+        let ip = |s: &str| Some(s.parse::<IpAddr>().unwrap());
+        let mut counts = ClassCounts::default();
+
+        counts.insert(conn_id(1), &first, ip("192.0.2.1"));
+        counts.insert(conn_id(2), &first, ip("192.0.2.1"));
+        counts.insert(conn_id(3), &first, ip("192.0.2.2"));
+        counts.insert(conn_id(4), &first, None);
+        assert_eq!(counts.count(&first, ip("192.0.2.1"), None), (4, 2));
+        assert_eq!(counts.count(&first, None, None), (4, 0));
+        assert_eq!(counts.count(&second, ip("192.0.2.1"), None), (0, 0));
+
+        // A connection doesn't count against itself
+        assert_eq!(
+            counts.count(&first, ip("192.0.2.1"), Some(conn_id(1))),
+            (3, 1)
+        );
+        assert_eq!(
+            counts.count(&first, ip("192.0.2.1"), Some(conn_id(3))),
+            (3, 2)
+        );
+
+        // Changing class moves the connection between counts
+        counts.insert(conn_id(1), &second, ip("192.0.2.1"));
+        assert_eq!(counts.count(&first, ip("192.0.2.1"), None), (3, 1));
+        assert_eq!(counts.count(&second, ip("192.0.2.1"), None), (1, 1));
+
+        counts.remove(conn_id(2));
+        counts.remove(conn_id(2));
+        assert_eq!(counts.count(&first, ip("192.0.2.1"), None), (2, 0));
+    }
+}
@@ -10,6 +10,9 @@ define_messages! {
     004(MyInfo)     => { (server_name: &ServerName, version: &str, user_modes: &str, chan_modes: &str, chan_modes_with_a_parameter: &str)
                                                 => "{server_name} {version} {user_modes} {chan_modes} {chan_modes_with_a_parameter}" },
     005(ISupport)   => { (data: &str)                           => "{data} :are supported by this server" },
+    008(SnoMask)    => { (mask: &str)                           => "{mask} :Server notice mask" },
+    015(Map)        => { (line: &str)                           => ":{line}" },
+    017(MapEnd)     => { ()                                     => ":End of /MAP" },
 
     351(Version)    => { (server_name: &ServerName, version: &str) => "{server_name} {version}: Sable IRCd"},
 
@@ -22,6 +25,8 @@ define_messages! {
                                                                 => "{nick} {user} {host} * :{realname}" },
     312(WhoisServer)            => { (nick: &User.nick(), server: &Server.name(), info=server.id())
                                                                 => "{nick} {server} :{info:?}"},
+    313(WhoisOperator)          => { (nick: &User.nick(), info: &str)
+                                                                => "{nick} :{info}" },
     314(WhowasUser)             => { (nick: &HistoricUser.nickname, user=nick.user.user, host=nick.user.visible_host, realname=nick.user.realname)
                                                                 => "{nick} {user} {host} * :{realname}" },
     315(EndOfWho)               => { (arg: &str)                => "{arg} :End of /WHO list" },
@@ -55,6 +60,10 @@ define_messages! {
 
     369(EndOfWhowas)            => { (nick: &Nickname)          => "{nick} :End of /WHOWAS" },
 
+    364(Links)                  => { (server: &Server.name(), uplink: &ServerName, hopcount: usize, info: &str)
+                                                                => "{server} {uplink} :{hopcount} {info}" },
+    365(EndOfLinks)             => { (mask: &str)               => "{mask} :End of /LINKS list" },
+
     256(AdminMe)                => { (server_name: &ServerName) => "{server_name} :Administrative Info"},
     257(AdminLocation1)         => { (server_location: &str)    => ":{server_location}" },
     258(AdminLocation2)         => { (admin_info: &str)         => ":{admin_info}" },
@@ -119,6 +128,7 @@ define_messages! {
 
     481(NotOper)            => { ()     => ":You're not an IRC operator" },
     491(NoOperConf)         => { ()     => ":No oper configuration found" },
+    723(NoPrivs)            => { (privilege: &str) => "{privilege} :Insufficient oper privileges." },
 
     440(ServicesNotAvailable) => { () => ":Services are not available"},
 
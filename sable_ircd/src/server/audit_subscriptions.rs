@@ -0,0 +1,101 @@
+use super::*;
+use sable_network::network::state::AuditLogEntry;
+
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+/// How many entries may be waiting to be sent to a management stream before it
+/// is considered stalled and closed
+const STREAM_BUFFER_SIZE: usize = 256;
+
+/// A management client following the audit log
+struct AuditStream {
+    query: AuditLogQuery,
+    sender: mpsc::Sender<String>,
+}
+
+/// Local subscribers to the live audit log.
+///
+/// Opers who have subscribed are sent each new entry as a server notice; this survives
+/// an upgrade. Management streams are tied to their HTTP connection, so do not.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub(super) struct AuditSubscriptions {
+    opers: RwLock<HashSet<UserId>>,
+    #[serde(skip)]
+    streams: Mutex<Vec<AuditStream>>,
+}
+
+impl ClientServer {
+    /// Start sending new audit log entries to `user` as server notices
+    pub(crate) fn subscribe_audit_log(&self, user: UserId) {
+        self.audit_subscriptions.opers.write().insert(user);
+    }
+
+    /// Stop sending new audit log entries to `user`. Returns false if they were not subscribed.
+    pub(crate) fn unsubscribe_audit_log(&self, user: UserId) -> bool {
+        self.audit_subscriptions.opers.write().remove(&user)
+    }
+
+    /// Open a stream of new audit log entries matching `query`, each serialised as JSON
+    pub(super) fn open_audit_stream(&self, query: AuditLogQuery) -> mpsc::Receiver<String> {
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER_SIZE);
+        self.audit_subscriptions
+            .streams
+            .lock()
+            .push(AuditStream { query, sender });
+        receiver
+    }
+
+    pub(super) fn handle_new_audit_entry(&self, entry: &AuditLogEntry) {
+        let net = self.network();
+        let notice = crate::utils::format_audit_entry(entry);
+
+        // Privileges can be lost without unsubscribing, for instance by deopering,
+        // so check each subscriber is still entitled to the feed
+        let subscribers: Vec<_> = {
+            let mut opers = self.audit_subscriptions.opers.write();
+            opers.retain(|user_id| {
+                net.user(*user_id)
+                    .is_ok_and(|user| self.policy().can_view_audit_log(&user).is_ok())
+            });
+            opers.iter().copied().collect()
+        };
+
+        let connections = self.connections.read();
+        for user_id in subscribers {
+            if let Ok(user) = net.user(user_id) {
+                for conn in connections.get_user(user_id) {
+                    conn.send(message::Notice::new(self, &user, &notice));
+                }
+            }
+        }
+        drop(connections);
+
+        let mut streams = self.audit_subscriptions.streams.lock();
+        if streams.is_empty() {
+            return;
+        }
+
+        let json = match serde_json::to_string(entry) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::error!(?entry, "Couldn't serialise audit log entry: {}", e);
+                return;
+            }
+        };
+        streams.retain(|stream| {
+            if !stream.query.matches(entry) {
+                return !stream.sender.is_closed();
+            }
+            match stream.sender.try_send(json.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!("Closing stalled audit log stream");
+                    false
+                }
+                Err(TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+}
@@ -1,6 +1,6 @@
 use super::*;
 
-use parking_lot::RwLockUpgradableReadGuard;
+use parking_lot::{RwLockUpgradableReadGuard, RwLockWriteGuard};
 
 impl ClientServer {
     fn notify_access_error(&self, err: &user_access::AccessError, conn: &ClientConnection) {
@@ -27,6 +27,9 @@ impl ClientServer {
                     ));
                 }
             }
+            ConnectionLimit(reason) => {
+                conn.send(message::Error::new(reason));
+            }
             InternalError => {
                 tracing::error!(?conn, "Internal error checking access");
                 conn.send(message::Error::new("Internal error"));
@@ -34,10 +37,53 @@ impl ClientServer {
         }
     }
 
+    fn snotice_access_error(
+        &self,
+        err: &user_access::AccessError,
+        conn: &ClientConnection,
+        pre_client: &PreClient,
+    ) {
+        use user_access::AccessError::*;
+        let why = match err {
+            Banned(reason) => format!("banned ({})", reason),
+            SaslRequired(reason) => format!("SASL required ({})", reason),
+            ConnectionLimit(reason) => format!("connection limit ({})", reason),
+            InternalError => return,
+        };
+        let nick = pre_client
+            .nick
+            .get()
+            .map_or_else(|| "*".to_string(), |n| n.to_string());
+        self.send_snotice(
+            SnomaskFlag::Reject,
+            &format!(
+                "Rejected registration of {} [{}]: {}",
+                nick,
+                conn.remote_addr(),
+                why
+            ),
+        );
+    }
+
     fn register_new_user(&self, connection_id: ConnectionId) {
         let connections = self.connections.upgradable_read();
         if let Ok(conn) = connections.get(connection_id) {
             if let Some(pre_client) = conn.pre_client() {
+                let mut writable = RwLockUpgradableReadGuard::upgrade(connections);
+                let class_result = self.apply_registration_class(
+                    &self.network(),
+                    &mut writable,
+                    &conn,
+                    &pre_client,
+                );
+                let connections = RwLockWriteGuard::downgrade_to_upgradable(writable);
+                if let Err(e) = class_result {
+                    self.notify_access_error(&e, conn.as_ref());
+                    RwLockUpgradableReadGuard::upgrade(connections).remove(connection_id);
+                    self.snotice_access_error(&e, &conn, &pre_client);
+                    return;
+                }
+
                 // First check whether they're attaching, as that's an easier operation
                 if let Some(user_id) = pre_client.can_attach_to_user() {
                     let user_connection_id = self.ids().next_user_connection();
@@ -59,12 +105,17 @@ impl ClientServer {
                 if let Err(e) = self.check_user_access(&self.network(), &conn) {
                     self.notify_access_error(&e, conn.as_ref());
                     RwLockUpgradableReadGuard::upgrade(connections).remove(connection_id);
+                    // Now that the connection lock is released
+                    self.snotice_access_error(&e, &conn, &pre_client);
                     return;
                 }
 
                 let new_user_id = self.ids().next_user();
 
                 if pre_client.can_register_new_user() {
+                    // can_register_new_user() has checked that the username is set
+                    let username = self.registration_username(&conn, &pre_client).unwrap();
+
                     let mut umodes = UserModeSet::new();
                     if conn.connection.is_tls() {
                         umodes |= UserModeFlag::TlsConnection;
@@ -80,7 +131,7 @@ impl ClientServer {
 
                     let new_user = event::details::NewUser {
                         nickname: *pre_client.nick.get().unwrap(),
-                        username: *pre_client.user.get().unwrap(),
+                        username,
                         visible_hostname: *pre_client.hostname.get().unwrap(),
                         realname: *pre_client.realname.get().unwrap(),
                         mode: state::UserMode::new(umodes),
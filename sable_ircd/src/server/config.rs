@@ -1,14 +1,31 @@
 use std::fs;
 use std::path::PathBuf;
 
+use auth_client::DnsblSettings;
+use client_listener::ListenerOptions;
+use ipnet::IpNet;
+use sable_network::prelude::Pattern;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::throttled_queue::ThrottleSettings;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ListenerConfig {
+    /// The address to listen on, or the socket path for a Unix socket listener
     pub address: String,
     #[serde(default)]
     pub tls: bool,
+    /// Accept connections using the IRCv3 WebSocket transport
+    #[serde(default)]
+    pub websocket: bool,
+    /// Advertise this listener's port in the `sts` capability. Must be set on
+    /// exactly one TLS listener if an STS policy is configured.
+    #[serde(default)]
+    pub sts: bool,
+    /// PROXY protocol, receive rate limit and Unix socket settings
+    #[serde(flatten)]
+    pub options: ListenerOptions,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -24,6 +41,274 @@ pub struct RawClientServerConfig {
     pub info_paths: RawServerInfo,
     #[serde(default)]
     pub monitor: MonitorConfig,
+    /// IRCv3 Strict Transport Security policy, if any
+    #[serde(default)]
+    pub sts: Option<StsConfig>,
+    /// Make ident (RFC 1413) lookups for new connections, and prefix usernames
+    /// which aren't confirmed by ident with `~`
+    #[serde(default)]
+    pub ident: bool,
+    /// DNS blocklists against which to check new connections
+    #[serde(default)]
+    pub dnsbl: DnsblSettings,
+    /// Connection classes, in the order in which clients are matched against them
+    #[serde(default)]
+    pub connection_classes: Vec<ConnectionClassConfig>,
+    /// Limit on the rate of new connections from a single address range, if any
+    #[serde(default)]
+    pub connection_throttle: Option<ConnectionThrottleConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StsConfig {
+    /// How long, in seconds, clients should remember to only connect using TLS
+    pub duration: u64,
+    /// Whether clients may include this server in STS preload lists
+    #[serde(default)]
+    pub preload: bool,
+}
+
+/// The processed STS policy, combining [`StsConfig`] with the advertised port
+#[derive(Debug, Clone)]
+pub struct StsPolicy {
+    pub port: u16,
+    pub duration: u64,
+    pub preload: bool,
+}
+
+impl StsPolicy {
+    pub fn load(
+        config: Option<&StsConfig>,
+        listeners: &[ListenerConfig],
+    ) -> Result<Option<Self>, ConfigProcessingError> {
+        let sts_listeners: Vec<_> = listeners.iter().filter(|l| l.sts).collect();
+
+        let Some(config) = config else {
+            if sts_listeners.is_empty() {
+                return Ok(None);
+            }
+            return Err(ConfigProcessingError {
+                reason: "Listener marked for STS but no STS policy configured".to_string(),
+            });
+        };
+
+        let [listener] = sts_listeners[..] else {
+            return Err(ConfigProcessingError {
+                reason: "STS policy requires exactly one listener marked for STS".to_string(),
+            });
+        };
+        if !listener.tls || listener.websocket || listener.options.unix_socket.is_some() {
+            return Err(ConfigProcessingError {
+                reason: format!("STS listener {} is not a TLS listener", listener.address),
+            });
+        }
+        let address: std::net::SocketAddr =
+            listener
+                .address
+                .parse()
+                .map_err(|e| ConfigProcessingError {
+                    reason: format!("Invalid listener address {}: {}", listener.address, e),
+                })?;
+
+        Ok(Some(Self {
+            port: address.port(),
+            duration: config.duration,
+            preload: config.preload,
+        }))
+    }
+
+    /// Capability values advertised to plaintext connections
+    pub fn plaintext_values(&self) -> Vec<String> {
+        vec![format!("port={}", self.port)]
+    }
+
+    /// Capability values advertised to TLS connections
+    pub fn tls_values(&self) -> Vec<String> {
+        let mut values = vec![format!("duration={}", self.duration)];
+        if self.preload {
+            values.push("preload".to_string());
+        }
+        values
+    }
+}
+
+/// A class of client connections, and the limits applied to them.
+///
+/// A client belongs to the first class whose criteria it matches; criteria which aren't
+/// set match any client. Clients matching no class are given the default limits.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConnectionClassConfig {
+    pub name: String,
+    /// Address ranges from which clients in this class connect
+    #[serde(default)]
+    pub ip_ranges: Vec<IpNet>,
+    /// Match only TLS (`true`) or only plaintext (`false`) connections
+    #[serde(default)]
+    pub tls: Option<bool>,
+    /// Match clients which have authenticated to an account matching this pattern
+    #[serde(default)]
+    pub account: Option<Pattern>,
+    /// Match clients whose ident response matches this pattern
+    #[serde(default)]
+    pub ident: Option<Pattern>,
+    /// Maximum number of connections in this class from a single address range
+    #[serde(default)]
+    pub max_per_ip: Option<usize>,
+    /// Prefix length grouping IPv4 addresses for `max_per_ip`
+    #[serde(default = "default_ipv4_prefix")]
+    pub ipv4_prefix: u8,
+    /// Prefix length grouping IPv6 addresses for `max_per_ip`
+    #[serde(default = "default_ipv6_prefix")]
+    pub ipv6_prefix: u8,
+    /// Maximum number of connections in this class
+    #[serde(default)]
+    pub max_clients: Option<usize>,
+    /// Number of outgoing lines which may be queued before the client is disconnected
+    #[serde(default = "default_send_queue")]
+    pub send_queue: usize,
+    /// Rate at which the client's commands are processed
+    #[serde(default)]
+    pub throttle: ThrottleSettings,
+    /// Seconds of inactivity after which the client is sent a PING. Clients which
+    /// don't respond within the same time again are disconnected.
+    #[serde(default = "default_ping_frequency")]
+    pub ping_frequency: i64,
+}
+
+impl ConnectionClassConfig {
+    /// The class applied to clients which match no configured class
+    pub fn default_class() -> Self {
+        Self {
+            name: "default".to_string(),
+            ip_ranges: Vec::new(),
+            tls: None,
+            account: None,
+            ident: None,
+            max_per_ip: None,
+            ipv4_prefix: default_ipv4_prefix(),
+            ipv6_prefix: default_ipv6_prefix(),
+            max_clients: None,
+            send_queue: default_send_queue(),
+            throttle: ThrottleSettings::default(),
+            ping_frequency: default_ping_frequency(),
+        }
+    }
+
+    /// Check a list of configured classes for errors
+    pub fn validate_all(classes: &[Self]) -> Result<(), ConfigProcessingError> {
+        let mut names = std::collections::HashSet::new();
+        for class in classes {
+            if !names.insert(class.name.as_str()) {
+                return Err(ConfigProcessingError {
+                    reason: format!("Duplicate connection class {}", class.name),
+                });
+            }
+            if class.ipv4_prefix > 32 || class.ipv6_prefix > 128 {
+                return Err(ConfigProcessingError {
+                    reason: format!("Invalid address prefix in connection class {}", class.name),
+                });
+            }
+            if class.send_queue == 0 || class.ping_frequency <= 0 {
+                return Err(ConfigProcessingError {
+                    reason: format!(
+                        "Send queue and ping frequency for connection class {} must be positive",
+                        class.name
+                    ),
+                });
+            }
+            if class.throttle.num <= 0 || class.throttle.time <= 0 || class.throttle.burst < 0 {
+                return Err(ConfigProcessingError {
+                    reason: format!("Invalid throttle for connection class {}", class.name),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Limits on the rate at which new connections are accepted from a single address range
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConnectionThrottleConfig {
+    /// Length, in seconds, of the window over which connections are counted
+    pub window: i64,
+    /// Number of connections accepted from one address range in each window
+    pub burst: u32,
+    /// Prefix length grouping IPv4 addresses into ranges
+    #[serde(default = "default_ipv4_prefix")]
+    pub ipv4_prefix: u8,
+    /// Prefix length grouping IPv6 addresses into ranges
+    #[serde(default = "default_ipv6_prefix")]
+    pub ipv6_prefix: u8,
+    /// Address ranges which are never throttled
+    #[serde(default)]
+    pub exempt: Vec<IpNet>,
+    /// Ban address ranges which keep connecting after being throttled
+    #[serde(default)]
+    pub auto_ban: Option<ThrottleBanConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ThrottleBanConfig {
+    /// Number of throttled connections in one window after which the range is banned
+    pub after: u32,
+    /// Duration of the ban, in minutes
+    #[serde(default = "default_throttle_ban_duration")]
+    pub duration: i64,
+    #[serde(default = "default_throttle_ban_reason")]
+    pub reason: String,
+}
+
+impl ConnectionThrottleConfig {
+    /// Check the throttle settings for errors
+    pub fn validate(&self) -> Result<(), ConfigProcessingError> {
+        if self.window <= 0 || self.burst == 0 {
+            return Err(ConfigProcessingError {
+                reason: "Connection throttle window and burst must be positive".to_string(),
+            });
+        }
+        if self.ipv4_prefix > 32 || self.ipv6_prefix > 128 {
+            return Err(ConfigProcessingError {
+                reason: "Invalid address prefix in connection throttle".to_string(),
+            });
+        }
+        if let Some(auto_ban) = &self.auto_ban {
+            if auto_ban.after == 0 || auto_ban.duration <= 0 {
+                return Err(ConfigProcessingError {
+                    reason: "Connection throttle ban threshold and duration must be positive"
+                        .to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_throttle_ban_duration() -> i64 {
+    10
+}
+
+fn default_throttle_ban_reason() -> String {
+    "Too many connections; please try again later".to_string()
+}
+
+fn default_ipv4_prefix() -> u8 {
+    32
+}
+
+fn default_ipv6_prefix() -> u8 {
+    64
+}
+
+fn default_send_queue() -> usize {
+    100
+}
+
+fn default_ping_frequency() -> i64 {
+    120
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -92,6 +377,11 @@ pub struct ClientServerConfig {
     pub listeners: Vec<ListenerConfig>,
     pub info_strings: ServerInfoStrings,
     pub monitor: MonitorConfig,
+    pub sts: Option<StsPolicy>,
+    pub ident: bool,
+    pub dnsbl: DnsblSettings,
+    pub connection_classes: Vec<ConnectionClassConfig>,
+    pub connection_throttle: Option<ConnectionThrottleConfig>,
 }
 
 #[derive(Debug, Error)]
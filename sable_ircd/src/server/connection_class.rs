@@ -0,0 +1,266 @@
+use super::config::ConnectionClassConfig;
+use super::*;
+use crate::connection_collection::ConnectionCollection;
+
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// The details of a client used to select its connection class
+#[derive(Debug)]
+pub(super) struct ClassCriteria<'a> {
+    pub ip: IpAddr,
+    pub tls: bool,
+    /// The client's account and ident response, once registration is complete. While
+    /// the connection is still being accepted these aren't known, and classes which
+    /// require them don't match.
+    pub identity: Option<ClientIdentity<'a>>,
+}
+
+#[derive(Debug)]
+pub(super) struct ClientIdentity<'a> {
+    pub account: Option<&'a str>,
+    pub ident: Option<&'a str>,
+}
+
+/// The configured connection classes, in the order in which they are matched
+#[derive(Debug)]
+pub(super) struct ConnectionClasses {
+    classes: Vec<Arc<ConnectionClassConfig>>,
+    default: Arc<ConnectionClassConfig>,
+}
+
+impl ConnectionClasses {
+    pub fn new(config: &[ConnectionClassConfig]) -> Self {
+        Self {
+            classes: config.iter().cloned().map(Arc::new).collect(),
+            default: Arc::new(ConnectionClassConfig::default_class()),
+        }
+    }
+
+    /// Find the first class matching the given client
+    pub fn find(&self, criteria: &ClassCriteria) -> Arc<ConnectionClassConfig> {
+        self.classes
+            .iter()
+            .find(|class| class.matches(criteria))
+            .unwrap_or(&self.default)
+            .clone()
+    }
+
+    /// Look up a class by name, falling back to the default class if it no
+    /// longer exists
+    pub fn get(&self, name: &str) -> Arc<ConnectionClassConfig> {
+        self.classes
+            .iter()
+            .find(|class| class.name == name)
+            .unwrap_or(&self.default)
+            .clone()
+    }
+}
+
+impl ConnectionClassConfig {
+    fn matches(&self, criteria: &ClassCriteria) -> bool {
+        if !self.ip_ranges.is_empty() && !self.ip_ranges.iter().any(|r| r.contains(&criteria.ip)) {
+            return false;
+        }
+        if self.tls.is_some_and(|tls| tls != criteria.tls) {
+            return false;
+        }
+        if let Some(pattern) = &self.account {
+            let account = criteria.identity.as_ref().and_then(|id| id.account);
+            if !account.is_some_and(|a| pattern.matches(a)) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.ident {
+            let ident = criteria.identity.as_ref().and_then(|id| id.ident);
+            if !ident.is_some_and(|i| pattern.matches(i)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The address range counted together with `ip` for the purposes of `max_per_ip`
+    pub(crate) fn address_group(&self, ip: IpAddr) -> IpNet {
+        let prefix = match ip {
+            IpAddr::V4(_) => self.ipv4_prefix,
+            IpAddr::V6(_) => self.ipv6_prefix,
+        };
+        // Prefix lengths are checked when the config is loaded
+        IpNet::new(ip, prefix)
+            .expect("invalid connection class prefix")
+            .trunc()
+    }
+
+    /// Check whether a client can join this class without exceeding its limits, given
+    /// the existing connections. `ip` is the client's address, or `None` for connections
+    /// over a Unix socket, which all share an address and so aren't limited per IP.
+    /// `exclude` is the client's own connection, if it is already among them.
+    pub fn check_limits(
+        &self,
+        connections: &ConnectionCollection,
+        ip: Option<IpAddr>,
+        exclude: Option<ConnectionId>,
+    ) -> Result<(), String> {
+        if self.max_clients.is_none() && self.max_per_ip.is_none() {
+            return Ok(());
+        }
+
+        let (total, same_group) = connections.class_count(self, ip, exclude);
+
+        if self.max_clients.is_some_and(|max| total >= max) {
+            return Err("Too many connections in your connection class".to_string());
+        }
+        if self.max_per_ip.is_some_and(|max| same_group >= max) {
+            return Err("Too many connections from your host".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl ClientServer {
+    /// Re-select the connection class for a client that is completing registration,
+    /// now that its account and ident are known, and apply the new class's settings
+    pub(super) fn apply_registration_class(
+        &self,
+        net: &Network,
+        connections: &mut ConnectionCollection,
+        conn: &ClientConnection,
+        pre_client: &PreClient,
+    ) -> Result<(), user_access::AccessError> {
+        let account = pre_client
+            .sasl_account
+            .get()
+            .and_then(|id| net.account(*id).ok())
+            .map(|account| account.name().to_string());
+        let ident = pre_client.ident.get().map(|ident| ident.to_string());
+
+        let class = self.connection_classes.find(&ClassCriteria {
+            ip: conn.remote_addr(),
+            tls: conn.connection.is_tls(),
+            identity: Some(ClientIdentity {
+                account: account.as_deref(),
+                ident: ident.as_deref(),
+            }),
+        });
+
+        class
+            .check_limits(
+                connections,
+                (!conn.connection.is_local()).then_some(conn.remote_addr()),
+                Some(conn.id()),
+            )
+            .map_err(user_access::AccessError::ConnectionLimit)?;
+
+        connections.set_class(conn.id(), &class);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(name: &str) -> ConnectionClassConfig {
+        ConnectionClassConfig {
+            name: name.to_string(),
+            ..ConnectionClassConfig::default_class()
+        }
+    }
+
+    fn criteria<'a>(
+        ip: &str,
+        tls: bool,
+        identity: Option<ClientIdentity<'a>>,
+    ) -> ClassCriteria<'a> {
+        ClassCriteria {
+            ip: ip.parse().unwrap(),
+            tls,
+            identity,
+        }
+    }
+
+    #[test]
+    fn first_matching_class_wins() {
+        let classes = ConnectionClasses::new(&[
+            ConnectionClassConfig {
+                ip_ranges: vec!["10.0.0.0/8".parse().unwrap()],
+                ..class("internal")
+            },
+            ConnectionClassConfig {
+                tls: Some(true),
+                ..class("secure")
+            },
+        ]);
+
+        assert_eq!(
+            classes.find(&criteria("10.1.2.3", true, None)).name,
+            "internal"
+        );
+        assert_eq!(
+            classes.find(&criteria("192.0.2.1", true, None)).name,
+            "secure"
+        );
+        assert_eq!(
+            classes.find(&criteria("192.0.2.1", false, None)).name,
+            "default"
+        );
+    }
+
+    #[test]
+    fn identity_classes_need_identity() {
+        let classes = ConnectionClasses::new(&[ConnectionClassConfig {
+            account: Some(Pattern::new("staff-*".to_string())),
+            ..class("staff")
+        }]);
+
+        assert_eq!(
+            classes.find(&criteria("192.0.2.1", false, None)).name,
+            "default"
+        );
+
+        let unauthenticated = ClientIdentity {
+            account: None,
+            ident: None,
+        };
+        assert_eq!(
+            classes
+                .find(&criteria("192.0.2.1", false, Some(unauthenticated)))
+                .name,
+            "default"
+        );
+
+        let staff = ClientIdentity {
+            account: Some("staff-one"),
+            ident: None,
+        };
+        assert_eq!(
+            classes
+                .find(&criteria("192.0.2.1", false, Some(staff)))
+                .name,
+            "staff"
+        );
+    }
+
+    #[test]
+    fn address_groups() {
+        let class = class("test");
+
+        assert_eq!(
+            class.address_group("2001:db8::1".parse().unwrap()),
+            "2001:db8::/64".parse::<IpNet>().unwrap()
+        );
+        assert_eq!(
+            class.address_group("192.0.2.1".parse().unwrap()),
+            "192.0.2.1/32".parse::<IpNet>().unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_class_falls_back_to_default() {
+        let classes = ConnectionClasses::new(&[class("known")]);
+
+        assert_eq!(classes.get("known").name, "known");
+        assert_eq!(classes.get("removed").name, "default");
+    }
+}
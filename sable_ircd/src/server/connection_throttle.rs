@@ -0,0 +1,300 @@
+use super::config::{ConnectionThrottleConfig, ThrottleBanConfig};
+use super::*;
+use sable_network::chert;
+use sable_network::network::ban::*;
+
+use ipnet::IpNet;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// Tracks the rate of new connections from each address range
+#[derive(Debug)]
+pub(super) struct ConnectionThrottle {
+    config: Option<ConnectionThrottleConfig>,
+    ranges: parking_lot::Mutex<HashMap<IpNet, ThrottleWindow>>,
+}
+
+/// Connections seen from one address range in the current window
+#[derive(Debug)]
+struct ThrottleWindow {
+    start: i64,
+    connections: u32,
+    throttled: u32,
+}
+
+/// The outcome of checking a new connection against the throttle
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum ThrottleResult {
+    Allowed,
+    /// The connection should be refused. `first` is set for the first connection
+    /// refused from its range in the current window.
+    Throttled {
+        first: bool,
+    },
+    /// The connection should be refused, and the given range banned
+    Ban(IpNet),
+}
+
+impl ConnectionThrottle {
+    pub fn new(config: Option<ConnectionThrottleConfig>) -> Self {
+        Self {
+            config,
+            ranges: parking_lot::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a new connection from `ip` at time `now`, and decide whether to accept it
+    pub fn check(&self, ip: IpAddr, now: i64) -> ThrottleResult {
+        let Some(config) = &self.config else {
+            return ThrottleResult::Allowed;
+        };
+        if config.exempt.iter().any(|range| range.contains(&ip)) {
+            return ThrottleResult::Allowed;
+        }
+
+        let prefix = match ip {
+            IpAddr::V4(_) => config.ipv4_prefix,
+            IpAddr::V6(_) => config.ipv6_prefix,
+        };
+        // Prefix lengths are checked when the config is loaded
+        let range = IpNet::new(ip, prefix)
+            .expect("invalid connection throttle prefix")
+            .trunc();
+
+        let mut ranges = self.ranges.lock();
+        let window = ranges.entry(range).or_insert(ThrottleWindow {
+            start: now,
+            connections: 0,
+            throttled: 0,
+        });
+        if now - window.start >= config.window {
+            *window = ThrottleWindow {
+                start: now,
+                connections: 0,
+                throttled: 0,
+            };
+        }
+
+        window.connections += 1;
+        if window.connections <= config.burst {
+            return ThrottleResult::Allowed;
+        }
+
+        window.throttled += 1;
+        match &config.auto_ban {
+            // Only ban once per window; further connections are refused by the ban itself
+            Some(auto_ban) if window.throttled == auto_ban.after => ThrottleResult::Ban(range),
+            _ => ThrottleResult::Throttled {
+                first: window.throttled == 1,
+            },
+        }
+    }
+
+    /// Forget address ranges whose windows have expired
+    pub fn prune(&self, now: i64) {
+        if let Some(config) = &self.config {
+            self.ranges
+                .lock()
+                .retain(|_, window| now - window.start < config.window);
+        }
+    }
+
+    /// The automatic ban settings, if enabled
+    pub fn auto_ban(&self) -> Option<&ThrottleBanConfig> {
+        self.config.as_ref()?.auto_ban.as_ref()
+    }
+}
+
+impl ClientServer {
+    /// Check a new connection against the connection throttle. Returns `false`, having
+    /// notified the connection and opers, if it should be refused.
+    pub(super) fn check_connection_throttle(&self, conn: &Connection) -> bool {
+        // Local connections can't be coming from a flooding network
+        if conn.is_local() {
+            return true;
+        }
+
+        let result = self
+            .connection_throttle
+            .check(conn.remote_addr, sable_network::utils::now());
+
+        let notice = match result {
+            ThrottleResult::Allowed => return true,
+            ThrottleResult::Throttled { first } => first.then(|| {
+                format!(
+                    "Throttled connections from {}: too many connections",
+                    conn.remote_addr
+                )
+            }),
+            ThrottleResult::Ban(range) => {
+                self.add_throttle_ban(range);
+                Some(format!(
+                    "Throttled connections from {}: banned {}",
+                    conn.remote_addr, range
+                ))
+            }
+        };
+
+        conn.send("ERROR :*** Connecting too fast; please try again later\r\n".to_string());
+        conn.close();
+        if let Some(notice) = notice {
+            self.send_snotice(SnomaskFlag::Reject, &notice);
+        }
+        false
+    }
+
+    /// Ban `range` from connecting, for repeatedly exceeding the connection throttle
+    fn add_throttle_ban(&self, range: IpNet) {
+        let Some(auto_ban) = self.connection_throttle.auto_ban() else {
+            return;
+        };
+
+        let condition = format!("ip in {}", range);
+        let pattern = match chert::parse::<NewConnectionBanSettings>(&condition) {
+            Ok(parsed) => parsed.into_root(),
+            Err(err) => {
+                tracing::error!(condition, ?err, "Throttle ban condition failed to parse");
+                return;
+            }
+        };
+
+        let now = sable_network::utils::now();
+
+        // The range may already be banned, by another server's throttle or by an oper
+        if self
+            .network()
+            .network_bans()
+            .iter()
+            .any(|ban| ban.pattern_text == condition && ban.is_active_at(now))
+        {
+            return;
+        }
+
+        let setter = format!("{} (connection throttle)", self.name());
+
+        let entry = state::AuditLogEntry {
+            id: self.ids().next_audit_log_entry(),
+            timestamp: now,
+            category: state::AuditLogCategory::NetworkBan,
+            source_id: None,
+            source_addr: None,
+            source_str: setter.clone(),
+            action: "THROTTLE".to_string(),
+            target_id: None,
+            target_str: Some(condition.clone()),
+            target_duration: Some(auto_ban.duration),
+            reason: Some(auto_ban.reason.clone()),
+        };
+        self.node
+            .submit_event(entry.id, details::NewAuditLogEntry { entry });
+
+        let new_ban = details::NewNetworkBan {
+            match_type: BanMatchType::NewConnection,
+            pattern,
+            pattern_text: condition,
+            action: NetworkBanAction::RefuseConnection(false),
+            setter_info: setter,
+            timestamp: now,
+            expires: now + auto_ban.duration * 60,
+            reason: auto_ban.reason.clone(),
+            oper_reason: Some("Exceeded connection throttle".to_string()),
+        };
+        self.node
+            .submit_event(self.ids().next_network_ban(), new_ban);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(auto_ban: Option<ThrottleBanConfig>) -> ConnectionThrottle {
+        ConnectionThrottle::new(Some(ConnectionThrottleConfig {
+            window: 60,
+            burst: 2,
+            ipv4_prefix: 24,
+            ipv6_prefix: 64,
+            exempt: vec!["192.0.2.0/24".parse().unwrap()],
+            auto_ban,
+        }))
+    }
+
+    #[test]
+    fn throttles_range_after_burst() {
+        let throttle = throttle(None);
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        assert_eq!(
+            throttle.check(ip("198.51.100.1"), 0),
+            ThrottleResult::Allowed
+        );
+        assert_eq!(
+            throttle.check(ip("198.51.100.2"), 1),
+            ThrottleResult::Allowed
+        );
+        assert_eq!(
+            throttle.check(ip("198.51.100.3"), 2),
+            ThrottleResult::Throttled { first: true }
+        );
+        assert_eq!(
+            throttle.check(ip("198.51.100.1"), 3),
+            ThrottleResult::Throttled { first: false }
+        );
+        // A different range is counted separately
+        assert_eq!(
+            throttle.check(ip("203.0.113.1"), 3),
+            ThrottleResult::Allowed
+        );
+        // Once the window has passed, connections are accepted again
+        assert_eq!(
+            throttle.check(ip("198.51.100.1"), 60),
+            ThrottleResult::Allowed
+        );
+    }
+
+    #[test]
+    fn exempt_ranges_never_throttled() {
+        let throttle = throttle(None);
+        for now in 0..10 {
+            assert_eq!(
+                throttle.check("192.0.2.1".parse().unwrap(), now),
+                ThrottleResult::Allowed
+            );
+        }
+    }
+
+    #[test]
+    fn bans_repeat_offenders_once() {
+        let throttle = throttle(Some(ThrottleBanConfig {
+            after: 2,
+            duration: 10,
+            reason: "test".to_string(),
+        }));
+        let ip = "2001:db8::1".parse().unwrap();
+
+        assert_eq!(throttle.check(ip, 0), ThrottleResult::Allowed);
+        assert_eq!(throttle.check(ip, 0), ThrottleResult::Allowed);
+        assert_eq!(
+            throttle.check(ip, 0),
+            ThrottleResult::Throttled { first: true }
+        );
+        assert_eq!(
+            throttle.check(ip, 0),
+            ThrottleResult::Ban("2001:db8::/64".parse().unwrap())
+        );
+        assert_eq!(
+            throttle.check(ip, 0),
+            ThrottleResult::Throttled { first: false }
+        );
+    }
+
+    #[test]
+    fn prune_forgets_expired_windows() {
+        let throttle = throttle(None);
+        throttle.check("198.51.100.1".parse().unwrap(), 0);
+        throttle.prune(30);
+        assert_eq!(throttle.ranges.lock().len(), 1);
+        throttle.prune(60);
+        assert!(throttle.ranges.lock().is_empty());
+    }
+}
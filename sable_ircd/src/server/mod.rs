@@ -43,13 +43,23 @@ use crate::monitor::MonitorSet;
 
 pub mod config;
 
+mod audit_subscriptions;
 mod command_action;
+mod connection_class;
+mod connection_throttle;
 mod message_sink_repository;
 mod server_type;
+mod snomask;
+mod statistics;
 mod update_handler;
 mod user_access;
 
 const PREREG_TIMEOUT: time::Duration = time::Duration::from_secs(120);
+/// How often to check for idle client connections which need to be pinged
+const PING_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Commands which are still processed while the network node is detached. Anything
+/// else is refused, since its effects would be lost when the node resynchronises.
+const COMMANDS_WHILE_DETACHED: &[&str] = &["PING", "PONG", "QUIT"];
 
 /// Last parameters of the RPL_MYINFO (004) numeric
 struct MyInfo {
@@ -88,6 +98,15 @@ pub struct ClientServer {
 
     node: Arc<NetworkNode>,
     listeners: Movable<ListenerCollection>,
+    /// Whether to make ident lookups for new connections
+    ident_lookups: bool,
+    /// Whether to check new connections against DNS blocklists
+    dnsbl_enabled: bool,
+    connection_classes: connection_class::ConnectionClasses,
+    connection_throttle: connection_throttle::ConnectionThrottle,
+    statistics: statistics::ServerStatistics,
+    audit_subscriptions: audit_subscriptions::AuditSubscriptions,
+    snomasks: snomask::Snomasks,
 
     // Any general static info (responses for MOTD, ADMIN, and so on)
     pub info_strings: ServerInfoStrings,
@@ -102,7 +121,7 @@ impl ClientServer {
     }
 
     /// The ID generator used to identify objects created by this server
-    pub fn ids(&self) -> &ObjectIdGenerator {
+    pub fn ids(&self) -> Arc<ObjectIdGenerator> {
         self.node.ids()
     }
 
@@ -272,6 +291,10 @@ impl ClientServer {
             ConnectionEventDetail::NewConnection(conn) => {
                 tracing::trace!("Got new connection");
 
+                if !self.check_connection_throttle(&conn) {
+                    return;
+                }
+
                 let conn_details = ban::NewConnectionBanSettings {
                     ip: conn.remote_addr,
                     tls: conn.is_tls(),
@@ -284,20 +307,99 @@ impl ClientServer {
                     if let NetworkBanAction::RefuseConnection(_) = ban.action {
                         conn.send(format!("ERROR :*** Banned: {}\r\n", ban.reason));
                         conn.close();
+                        self.send_snotice(
+                            SnomaskFlag::Reject,
+                            &format!(
+                                "Rejected connection from {}: banned ({})",
+                                conn.remote_addr, ban.reason
+                            ),
+                        );
                         return;
                     }
                 }
 
-                let conn = ClientConnection::new(conn);
+                // Account and ident aren't known yet, so classes which need them can only
+                // be selected at registration
+                let class = self
+                    .connection_classes
+                    .find(&connection_class::ClassCriteria {
+                        ip: conn.remote_addr,
+                        tls: conn.is_tls(),
+                        identity: None,
+                    });
+                let limit_check = class.check_limits(
+                    &self.connections.read(),
+                    (!conn.is_local()).then_some(conn.remote_addr),
+                    None,
+                );
+                if let Err(reason) = limit_check {
+                    conn.send(format!("ERROR :{}\r\n", reason));
+                    conn.close();
+                    self.send_snotice(
+                        SnomaskFlag::Reject,
+                        &format!(
+                            "Rejected connection from {}: {} (class {})",
+                            conn.remote_addr, reason, class.name
+                        ),
+                    );
+                    return;
+                }
+
+                let conn = ClientConnection::new(conn, &class);
+
+                // Connections on a Unix socket come with a hostname from the listener
+                // configuration, so there's nothing to look up
+                let local_hostname = conn
+                    .connection
+                    .local_hostname
+                    .as_deref()
+                    .and_then(|h| h.parse::<Hostname>().ok());
+
+                match (local_hostname, conn.pre_client()) {
+                    (Some(hostname), Some(pre_client)) => {
+                        pre_client.hostname.set(hostname).ok();
+                    }
+                    _ => {
+                        conn.send(message::Notice::new(
+                            self,
+                            &UnknownTarget,
+                            "*** Looking up your hostname",
+                        ));
+                        self.auth_client
+                            .start_dns_lookup(conn.id(), conn.remote_addr());
+                    }
+                }
+
+                // Ident only makes sense for connections accepted directly over TCP
+                if let (true, Some(endpoints), Some(pre_client)) = (
+                    self.ident_lookups,
+                    conn.connection.tcp_endpoints,
+                    conn.pre_client(),
+                ) {
+                    conn.send(message::Notice::new(
+                        self,
+                        &UnknownTarget,
+                        "*** Checking Ident",
+                    ));
+                    pre_client.start_progress(ProgressFlag::IdentLookup);
+                    self.auth_client.start_ident_lookup(
+                        conn.id(),
+                        endpoints.local,
+                        endpoints.remote,
+                    );
+                }
 
-                conn.send(message::Notice::new(
-                    self,
-                    &UnknownTarget,
-                    "*** Looking up your hostname",
-                ));
-                self.auth_client
-                    .start_dns_lookup(conn.id(), conn.remote_addr());
-                let conn = self.connections.write().add(msg.source, conn);
+                // Local connections can't be listed, so don't waste queries on them
+                if let (true, false, Some(pre_client)) = (
+                    self.dnsbl_enabled,
+                    conn.connection.is_local(),
+                    conn.pre_client(),
+                ) {
+                    pre_client.start_progress(ProgressFlag::DnsblLookup);
+                    self.auth_client
+                        .start_dnsbl_lookup(conn.id(), conn.remote_addr());
+                }
+                let conn = self.connections.write().add(msg.source, conn, &class);
                 self.prereg_connections.lock().await.push_back(conn);
             }
             ConnectionEventDetail::Message(m) => {
@@ -332,6 +434,9 @@ impl ClientServer {
                         }
                     }
                     conn.send(message::Error::new(&e.to_string()));
+                    // After a fatal error the connection task waits to deliver the
+                    // ERROR above; let it know that nothing else is coming
+                    conn.connection.close();
                 }
                 self.connections.write().remove(msg.source);
             }
@@ -339,10 +444,25 @@ impl ClientServer {
     }
 
     fn process_pending_client_messages(self: &Arc<Self>, async_handlers: &AsyncHandlerCollection) {
+        let detached = self.node.is_detached();
         let connections = self.connections.read();
         for (conn_id, message) in connections.poll_messages().collect::<Vec<_>>() {
             if let Some(parsed) = ClientMessage::parse(conn_id, &message) {
                 if let Ok(connection) = connections.get(conn_id) {
+                    if detached
+                        && !COMMANDS_WHILE_DETACHED
+                            .iter()
+                            .any(|c| c.eq_ignore_ascii_case(&parsed.command))
+                    {
+                        // Any changes would be discarded when we resynchronise
+                        connection.send(message::Fail::new(
+                            &parsed.command,
+                            "TEMPORARILY_UNAVAILABLE",
+                            "",
+                            "This server is reconnecting to the network; please try again shortly",
+                        ));
+                        continue;
+                    }
                     if let Ok(command) = ClientCommand::new(Arc::clone(self), connection, parsed) {
                         if let Some(async_handler) =
                             self.command_dispatcher.dispatch_command(command)
@@ -357,7 +477,8 @@ impl ClientServer {
         }
         drop(connections);
 
-        for flooded in self.connections.write().flooded_connections() {
+        let flooded_connections: Vec<_> = self.connections.write().flooded_connections().collect();
+        for flooded in flooded_connections {
             if let Some(user_id) = flooded.user_id() {
                 if let Ok(user) = self.node.network().user(user_id) {
                     if user.session_key().is_some() {
@@ -365,6 +486,11 @@ impl ClientServer {
                         continue;
                     }
 
+                    self.send_snotice(
+                        SnomaskFlag::Flood,
+                        &format!("Excess flood: {} [{}]", user.nuh(), flooded.remote_addr()),
+                    );
+
                     self.node.submit_event(
                         user_id,
                         event::details::UserQuit {
@@ -377,6 +503,53 @@ impl ClientServer {
         }
     }
 
+    /// Ping connections which have been idle for longer than their class's ping
+    /// frequency, and disconnect those which didn't respond to the previous ping
+    fn check_client_pings(&self) {
+        let mut timed_out = Vec::new();
+        for conn in self.connections.read().iter() {
+            let class = self.connection_classes.get(&conn.class_name());
+            match conn.check_activity(class.ping_frequency) {
+                PingStatus::Active => {}
+                PingStatus::SendPing => {
+                    conn.send(message::Ping::new(
+                        self,
+                        &UnknownTarget,
+                        &self.name().to_string(),
+                    ));
+                }
+                PingStatus::TimedOut => {
+                    timed_out.push((Arc::clone(conn), class.ping_frequency * 2));
+                }
+            }
+        }
+
+        for (conn, idle) in timed_out {
+            let message = format!("Ping timeout: {} seconds", idle);
+
+            if let Some((user_id, user_conn_id)) = conn.user_ids() {
+                self.node
+                    .submit_event(user_conn_id, details::UserDisconnect {});
+
+                // As when a connection closes, a user in persistent session mode stays
+                let persistent = self
+                    .network()
+                    .user(user_id)
+                    .is_ok_and(|user| user.session_key().is_some());
+                if !persistent {
+                    self.node.submit_event(
+                        user_id,
+                        details::UserQuit {
+                            message: message.clone(),
+                        },
+                    );
+                }
+            }
+            conn.error(&message);
+            self.connections.write().remove(conn.id());
+        }
+    }
+
     /// Run the server
     ///
     /// Arguments:
@@ -397,6 +570,7 @@ impl ClientServer {
         let mut async_handlers = AsyncHandlerCollection::new();
 
         let mut reap_preclients_timer = time::interval(Duration::from_secs(60));
+        let mut ping_timer = time::interval(PING_CHECK_INTERVAL);
 
         loop {
             // tracing::trace!("ClientServer run loop");
@@ -426,6 +600,12 @@ impl ClientServer {
                     // Spawning a sub-task in order not to block all events
                     tracing::trace!("...from reap_preclients_timer");
                     tokio::spawn(self.clone().reap_preclients());
+                    self.connection_throttle.prune(sable_network::utils::now());
+                },
+                _ = ping_timer.tick() =>
+                {
+                    tracing::trace!("...from ping_timer");
+                    self.check_client_pings();
                 },
                 _ = async_handlers.poll(), if !async_handlers.is_empty() =>
                 {
@@ -472,6 +652,7 @@ impl ClientServer {
                                                                                 conn.remote_addr(),
                                                                                 msg.hostname
                                                                                 );
+                                self.statistics.record_dns_lookup(msg.cached);
                                 if let Some(pc) = conn.pre_client() {
                                     if let Some(hostname) = msg.hostname {
                                         conn.send(message::Notice::new(&self, &UnknownTarget,
@@ -496,6 +677,41 @@ impl ClientServer {
                                 }
                             }
                         },
+                        Some(AuthEvent::IdentResult(msg)) =>
+                        {
+                            if let Ok(conn) = self.connections.get(msg.conn) {
+                                tracing::trace!("Ident lookup finished for {:?}: {:?}", msg.conn, msg.username);
+                                if let Some(pc) = conn.pre_client() {
+                                    if let Some(username) = msg.username {
+                                        conn.send(message::Notice::new(&self, &UnknownTarget, "*** Got Ident response"));
+                                        pc.ident.set(username).ok();
+                                    } else {
+                                        conn.send(message::Notice::new(&self, &UnknownTarget, "*** No Ident response"));
+                                    }
+                                    if pc.complete_progress(ProgressFlag::IdentLookup) {
+                                        let res = self.action_submitter.send(CommandAction::RegisterClient(conn.id()));
+                                        if let Err(e) = res {
+                                            conn.error(&e.to_string());
+                                        }
+                                    }
+                                }
+                            }
+                        },
+                        Some(AuthEvent::DnsblResult(msg)) =>
+                        {
+                            if let Ok(conn) = self.connections.get(msg.conn) {
+                                tracing::trace!("DNSBL lookup finished for {:?}: {:?}", msg.conn, msg.hits);
+                                if let Some(pc) = conn.pre_client() {
+                                    pc.dnsbl_hits.set(msg.hits).ok();
+                                    if pc.complete_progress(ProgressFlag::DnsblLookup) {
+                                        let res = self.action_submitter.send(CommandAction::RegisterClient(conn.id()));
+                                        if let Err(e) = res {
+                                            conn.error(&e.to_string());
+                                        }
+                                    }
+                                }
+                            }
+                        },
                         None =>
                         {
                             panic!("Lost auth client task");
@@ -3,9 +3,91 @@ use crate::connection_collection::ConnectionCollectionState;
 use anyhow::Context;
 use async_trait::async_trait;
 use client_listener::SavedListenerCollection;
-use sable_server::ServerSaveError;
+use sable_server::{
+    ApplicationManagementCommand, ApplicationManagementRequest, ApplicationManagementResponse,
+    ServerSaveError,
+};
 
 use crate::monitor::MonitorSet;
+use std::net::SocketAddr;
+
+/// Check a listener description, and translate it into the address and connection
+/// type to listen with
+fn listener_parameters(
+    config: &config::ListenerConfig,
+) -> anyhow::Result<(ListenerAddress, ConnectionType)> {
+    if let Some(unix) = &config.options.unix_socket {
+        if config.tls || config.websocket {
+            anyhow::bail!("Unix socket listeners can't use TLS or WebSocket");
+        }
+        unix.hostname
+            .parse::<Hostname>()
+            .with_context(|| format!("Invalid Unix socket hostname: {}", unix.hostname))?;
+
+        return Ok((
+            ListenerAddress::Unix(config.address.clone().into()),
+            ConnectionType::Unix,
+        ));
+    }
+
+    let conn_type = match (config.tls, config.websocket) {
+        (false, false) => ConnectionType::Clear,
+        (true, false) => ConnectionType::Tls,
+        (false, true) => ConnectionType::WebSocket,
+        (true, true) => ConnectionType::WebSocketTls,
+    };
+    let address: SocketAddr = config
+        .address
+        .parse()
+        .with_context(|| format!("Invalid listener address: {}", config.address))?;
+
+    Ok((address.into(), conn_type))
+}
+
+/// Create a client listener as described by `config`
+fn add_listener(
+    listeners: &ListenerCollection,
+    config: &config::ListenerConfig,
+) -> anyhow::Result<ListenerId> {
+    let (address, conn_type) = listener_parameters(config)?;
+
+    listeners
+        .add_listener_with_options(address, conn_type, config.options.clone())
+        .context("Cannot add listener")
+}
+
+/// Start again any lookups for a pre-registration connection which haven't completed
+fn restart_auth_lookups(auth_client: &AuthClient, conn: &ClientConnection, pre_client: &PreClient) {
+    if pre_client.hostname.get().is_none() {
+        auth_client.start_dns_lookup(conn.id(), conn.remote_addr());
+    }
+    if pre_client.in_progress(ProgressFlag::IdentLookup) {
+        if let Some(endpoints) = conn.connection.tcp_endpoints {
+            auth_client.start_ident_lookup(conn.id(), endpoints.local, endpoints.remote);
+        }
+    }
+    if pre_client.in_progress(ProgressFlag::DnsblLookup) {
+        auth_client.start_dnsbl_lookup(conn.id(), conn.remote_addr());
+    }
+}
+
+/// Advertise the configured STS policy, or stop advertising it if there is none
+fn apply_sts_policy(caps: &CapabilityRepository, policy: Option<&config::StsPolicy>) {
+    match policy {
+        Some(policy) => caps.enable_with_tls_values(
+            ClientCapability::Sts,
+            &policy.plaintext_values(),
+            &policy.tls_values(),
+        ),
+        None => caps.disable(ClientCapability::Sts),
+    }
+}
+
+fn audit_query_from_params(
+    params: &[(String, String)],
+) -> Result<AuditLogQuery, AuditLogQueryError> {
+    AuditLogQuery::from_params(params.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+}
 
 /// Saved state of a [`ClientServer`] for later resumption
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -15,6 +97,12 @@ pub struct ClientServerState {
     client_caps: CapabilityRepository,
     listener_state: SavedListenerCollection,
     monitors: MonitorSet,
+    #[serde(default)]
+    statistics: statistics::ServerStatistics,
+    #[serde(default)]
+    audit_subscriptions: audit_subscriptions::AuditSubscriptions,
+    #[serde(default)]
+    snomasks: snomask::Snomasks,
 }
 
 #[async_trait]
@@ -28,10 +116,20 @@ impl sable_server::ServerType for ClientServer {
     fn validate_config(
         config: &RawClientServerConfig,
     ) -> Result<Self::ProcessedConfig, Self::ConfigError> {
+        config::ConnectionClassConfig::validate_all(&config.connection_classes)?;
+        if let Some(throttle) = &config.connection_throttle {
+            throttle.validate()?;
+        }
+
         Ok(Self::ProcessedConfig {
             listeners: config.listeners.clone(),
             info_strings: ServerInfoStrings::load(&config.info_paths)?,
             monitor: config.monitor.clone(),
+            sts: config::StsPolicy::load(config.sts.as_ref(), &config.listeners)?,
+            ident: config.ident,
+            dnsbl: config.dnsbl.clone(),
+            connection_classes: config.connection_classes.clone(),
+            connection_throttle: config.connection_throttle.clone(),
         })
     }
 
@@ -54,21 +152,16 @@ impl sable_server::ServerType for ClientServer {
             .context("Could not load TLS certificates")?;
 
         for listener in config.listeners.iter() {
-            let conn_type = if listener.tls {
-                ConnectionType::Tls
-            } else {
-                ConnectionType::Clear
-            };
-            client_listeners
-                .add_listener(
-                    listener.address.parse().with_context(|| {
-                        format!("Invalid listener address: {}", listener.address)
-                    })?,
-                    conn_type,
-                )
-                .context("Cannot add listener")?;
+            add_listener(&client_listeners, listener)?;
         }
 
+        let client_caps = CapabilityRepository::new();
+        apply_sts_policy(&client_caps, config.sts.as_ref());
+
+        let auth_client =
+            AuthClient::new(auth_sender).context("Could not initialize auth client")?;
+        auth_client.configure_dnsbl(config.dnsbl.clone());
+
         Ok(Self {
             action_receiver: Mutex::new(action_receiver),
             connection_events: Mutex::new(client_recv),
@@ -77,8 +170,7 @@ impl sable_server::ServerType for ClientServer {
 
             stored_response_sinks: RwLock::new(MessageSinkRepository::new()),
 
-            auth_client: AuthClient::new(auth_sender)
-                .context("Could not initialize auth client")?,
+            auth_client,
 
             action_submitter,
             command_dispatcher: CommandDispatcher::new(),
@@ -86,9 +178,20 @@ impl sable_server::ServerType for ClientServer {
             prereg_connections: Mutex::new(VecDeque::new()),
             myinfo: Self::build_myinfo(),
             isupport: Self::build_basic_isupport(&config),
-            client_caps: CapabilityRepository::new(),
+            client_caps,
             node,
             listeners: Movable::new(client_listeners),
+            ident_lookups: config.ident,
+            dnsbl_enabled: config.dnsbl.is_enabled(),
+            connection_classes: connection_class::ConnectionClasses::new(
+                &config.connection_classes,
+            ),
+            connection_throttle: connection_throttle::ConnectionThrottle::new(
+                config.connection_throttle,
+            ),
+            statistics: Default::default(),
+            audit_subscriptions: Default::default(),
+            snomasks: Default::default(),
             info_strings: config.info_strings,
             monitors: MonitorSet::new(config.monitor.max_per_connection.into()).into(),
         })
@@ -112,12 +215,16 @@ impl sable_server::ServerType for ClientServer {
                 .await
                 .map_err(ServerSaveError::IoError)?,
             monitors: self.monitors.into_inner(),
+            statistics: self.statistics,
+            audit_subscriptions: self.audit_subscriptions,
+            snomasks: self.snomasks,
         })
     }
 
     /// Restore from a previously saved state.
     fn restore(
         mut state: ClientServerState,
+        tls_data: &TlsData,
         node: Arc<NetworkNode>,
         history_receiver: UnboundedReceiver<NetworkHistoryUpdate>,
         config: &Self::ProcessedConfig,
@@ -126,11 +233,64 @@ impl sable_server::ServerType for ClientServer {
         let (action_send, action_recv) = unbounded_channel();
         let (client_send, client_recv) = unbounded_channel();
 
-        let listeners = ListenerCollection::resume(state.listener_state, client_send)?;
+        // The listener process survives the upgrade, unless it can't understand us
+        let listeners_replaced = !state.listener_state.is_compatible();
+        let listeners = if listeners_replaced {
+            let tls_settings = TlsSettings {
+                key: tls_data.key.clone(),
+                cert_chain: tls_data.cert_chain.clone(),
+            };
+            ListenerCollection::replace(state.listener_state, client_send.clone(), tls_settings)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+        } else {
+            ListenerCollection::resume(state.listener_state, client_send.clone())?
+        };
+
+        let mut connections = ConnectionCollection::restore_from(state.connections, &listeners);
+
+        if listeners_replaced {
+            // Their sockets were closed along with the old listener process
+            for conn in connections.iter() {
+                let _ =
+                    client_send.send(ConnectionEvent::error(conn.id(), ConnectionError::Closed));
+            }
+        }
 
-        let connections = ConnectionCollection::restore_from(state.connections, &listeners);
+        // Class settings may have changed in the new configuration
+        let connection_classes =
+            connection_class::ConnectionClasses::new(&config.connection_classes);
+        let classes: Vec<_> = connections
+            .iter()
+            .map(|conn| (conn.id(), connection_classes.get(&conn.class_name())))
+            .collect();
+        for (id, class) in classes {
+            connections.set_class(id, &class);
+        }
 
         state.monitors.max_per_connection = config.monitor.max_per_connection.into();
+        state.client_caps.add_missing_capabilities();
+        apply_sts_policy(&state.client_caps, config.sts.as_ref());
+
+        // As for the listener process, the auth client process survives the upgrade
+        // unless it can't understand us. Its configuration might not be current, though.
+        let auth_replaced = !state.auth_state.is_compatible();
+        let auth_client = if auth_replaced {
+            AuthClient::replace(state.auth_state, auth_send)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+        } else {
+            AuthClient::resume(state.auth_state, auth_send)?
+        };
+        auth_client.configure_dnsbl(config.dnsbl.clone());
+
+        if auth_replaced {
+            // Lookups in progress were lost with the old worker process
+            for conn in connections.iter() {
+                if let Some(pre_client) = conn.pre_client() {
+                    restart_auth_lookups(&auth_client, conn, &pre_client);
+                }
+            }
+        }
+
         Ok(Self {
             node,
             action_receiver: Mutex::new(action_recv),
@@ -148,13 +308,22 @@ impl sable_server::ServerType for ClientServer {
             ),
             connections: RwLock::new(connections),
             command_dispatcher: command::CommandDispatcher::new(),
-            auth_client: AuthClient::resume(state.auth_state, auth_send)?,
+            auth_client,
             auth_events: Mutex::new(auth_recv),
             myinfo: Self::build_myinfo(),
             isupport: Self::build_basic_isupport(config),
             client_caps: state.client_caps,
             history_receiver: Mutex::new(history_receiver),
             listeners: Movable::new(listeners),
+            ident_lookups: config.ident,
+            dnsbl_enabled: config.dnsbl.is_enabled(),
+            connection_classes,
+            connection_throttle: connection_throttle::ConnectionThrottle::new(
+                config.connection_throttle.clone(),
+            ),
+            statistics: state.statistics,
+            audit_subscriptions: state.audit_subscriptions,
+            snomasks: state.snomasks,
             info_strings: config.info_strings.clone(),
             monitors: state.monitors.into(),
         })
@@ -170,6 +339,59 @@ impl sable_server::ServerType for ClientServer {
         }
     }
 
+    fn validate_tls(&self, tls_data: &TlsData) -> anyhow::Result<()> {
+        TlsSettings {
+            key: tls_data.key.clone(),
+            cert_chain: tls_data.cert_chain.clone(),
+        }
+        .validate()
+        .context("Invalid TLS certificates for client listeners")
+    }
+
+    async fn reload_tls(&self, tls_data: &TlsData) -> anyhow::Result<()> {
+        self.listeners
+            .load_tls_certificates_and_wait(tls_data.key.clone(), tls_data.cert_chain.clone())
+            .await
+            .context("Listener process could not load TLS certificates")
+    }
+
+    async fn handle_management_command(
+        &self,
+        cmd: ApplicationManagementCommand,
+    ) -> ApplicationManagementResponse {
+        match cmd {
+            ApplicationManagementCommand::Request(request) => {
+                self.handle_management_request(request).await
+            }
+            ApplicationManagementCommand::Statistics => {
+                let connections = self.connections.read().iter().count();
+                let report = self.statistics.report(connections);
+                ApplicationManagementResponse::Success(
+                    serde_json::to_string_pretty(&report).expect("Failed to serialise statistics"),
+                )
+            }
+            ApplicationManagementCommand::QueryAuditLog(params) => {
+                let query = match audit_query_from_params(&params) {
+                    Ok(query) => query,
+                    Err(e) => return ApplicationManagementResponse::BadRequest(e.to_string()),
+                };
+                let net = self.network();
+                match serde_json::to_string_pretty(&query.run(&net)) {
+                    Ok(json) => ApplicationManagementResponse::Success(json),
+                    Err(e) => ApplicationManagementResponse::BadRequest(e.to_string()),
+                }
+            }
+            ApplicationManagementCommand::StreamAuditLog(params) => {
+                match audit_query_from_params(&params) {
+                    Ok(query) => {
+                        ApplicationManagementResponse::Stream(self.open_audit_stream(query))
+                    }
+                    Err(e) => ApplicationManagementResponse::BadRequest(e.to_string()),
+                }
+            }
+        }
+    }
+
     fn handle_remote_command(&self, cmd: RemoteServerRequestType) -> RemoteServerResponse {
         match cmd {
             RemoteServerRequestType::Ping => RemoteServerResponse::Success,
@@ -177,3 +399,56 @@ impl sable_server::ServerType for ClientServer {
         }
     }
 }
+
+impl ClientServer {
+    /// Handle the ircd-specific management endpoints, which manage client listeners
+    async fn handle_management_request(
+        &self,
+        request: ApplicationManagementRequest,
+    ) -> ApplicationManagementResponse {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/listeners") => {
+                match serde_json::to_string_pretty(&self.listeners.listeners()) {
+                    Ok(json) => ApplicationManagementResponse::Success(json),
+                    Err(e) => ApplicationManagementResponse::Failed(e.to_string()),
+                }
+            }
+            ("POST", "/listeners") => {
+                let config: config::ListenerConfig = match serde_json::from_slice(&request.body) {
+                    Ok(config) => config,
+                    Err(e) => return ApplicationManagementResponse::BadRequest(e.to_string()),
+                };
+                let (address, conn_type) = match listener_parameters(&config) {
+                    Ok(params) => params,
+                    Err(e) => return ApplicationManagementResponse::BadRequest(format!("{:#}", e)),
+                };
+                match self
+                    .listeners
+                    .add_listener_and_wait(address, conn_type, config.options)
+                    .await
+                {
+                    Ok(id) => ApplicationManagementResponse::Success(
+                        serde_json::json!({ "id": id }).to_string(),
+                    ),
+                    Err(e) => {
+                        ApplicationManagementResponse::Failed(format!("Cannot add listener: {}", e))
+                    }
+                }
+            }
+            ("DELETE", path) => {
+                let Some(Ok(id)) = path.strip_prefix("/listeners/").map(str::parse) else {
+                    return ApplicationManagementResponse::NotFound;
+                };
+                let id = ListenerId::new(id);
+                if !self.listeners.listeners().iter().any(|l| l.id == id) {
+                    return ApplicationManagementResponse::NotFound;
+                }
+                match self.listeners.close_listener(id) {
+                    Ok(()) => ApplicationManagementResponse::Success(String::new()),
+                    Err(e) => ApplicationManagementResponse::Failed(e.to_string()),
+                }
+            }
+            _ => ApplicationManagementResponse::NotFound,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use super::*;
+use sable_network::network::{state::AuditLogCategory, update::HistoricUser};
+
+use std::collections::HashMap;
+
+/// The server notice masks of local users.
+///
+/// The `+s` user mode itself is part of the network state, but which notices a user
+/// receives is a local matter, in the same way that server notices are only sent to
+/// local users.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub(super) struct Snomasks {
+    masks: RwLock<HashMap<UserId, SnomaskSet>>,
+}
+
+impl ClientServer {
+    /// The categories of server notice currently sent to `user`
+    pub(crate) fn snomask(&self, user: UserId) -> SnomaskSet {
+        self.snomasks
+            .masks
+            .read()
+            .get(&user)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Set the categories of server notice sent to `user`
+    pub(crate) fn set_snomask(&self, user: UserId, mask: SnomaskSet) {
+        let mut masks = self.snomasks.masks.write();
+        if mask.is_empty() {
+            masks.remove(&user);
+        } else {
+            masks.insert(user, mask);
+        }
+    }
+
+    /// Send a server notice to each local user whose snomask includes `category`.
+    ///
+    /// This takes a read lock on the connection collection, so must not be called while
+    /// a write or upgradable lock is held.
+    pub(crate) fn send_snotice(&self, category: SnomaskFlag, text: &str) {
+        let net = self.network();
+
+        // Recipients who have since quit or lost the required privileges are dropped
+        let recipients: Vec<_> = {
+            let mut masks = self.snomasks.masks.write();
+            masks.retain(|user_id, mask| {
+                let Ok(user) = net.user(*user_id) else {
+                    return false;
+                };
+                if mask.is_set(category)
+                    && self.policy().can_receive_snomask(&user, category).is_err()
+                {
+                    *mask &= !SnomaskSet::from(category);
+                }
+                !mask.is_empty()
+            });
+            masks
+                .iter()
+                .filter(|(_, mask)| mask.is_set(category))
+                .map(|(user_id, _)| *user_id)
+                .collect()
+        };
+        if recipients.is_empty() {
+            return;
+        }
+
+        let text = format!("*** Notice -- {}", text);
+        let connections = self.connections.read();
+        for user_id in recipients {
+            if let Ok(user) = net.user(user_id) {
+                for conn in connections.get_user(user_id) {
+                    conn.send(message::Notice::new(self, &user, &text));
+                }
+            }
+        }
+    }
+
+    /// Send the server notices, if any, for a replicated state change
+    pub(super) fn send_state_change_snotices(&self, change: &NetworkStateChange) {
+        match change {
+            NetworkStateChange::NewUser(detail) => {
+                let net = self.network();
+                let ip = net
+                    .user(detail.user.user.id)
+                    .ok()
+                    .and_then(|user| user.connections().next().map(|conn| *conn.ip()));
+                let ip = ip.map_or_else(|| "unknown address".to_string(), |ip| ip.to_string());
+
+                self.send_snotice(
+                    SnomaskFlag::Connect,
+                    &format!(
+                        "Client connecting: {} [{}] [{}]",
+                        describe_user(&detail.user),
+                        ip,
+                        detail.user.user.realname
+                    ),
+                );
+            }
+            NetworkStateChange::UserQuit(detail) => self.send_quit_snotice(detail),
+            NetworkStateChange::BulkUserQuit(detail) => {
+                for item in &detail.items {
+                    self.send_quit_snotice(item);
+                }
+            }
+            NetworkStateChange::UserNickChange(detail) => {
+                self.send_snotice(
+                    SnomaskFlag::Nick,
+                    &format!(
+                        "Nick change: From {} to {} [{}@{}]",
+                        detail.user.nickname,
+                        detail.new_nick,
+                        detail.user.user.user,
+                        detail.user.user.visible_host
+                    ),
+                );
+            }
+            NetworkStateChange::NewAuditLogEntry(detail) => {
+                let category = match detail.entry.category {
+                    AuditLogCategory::ServerKill => SnomaskFlag::Kill,
+                    AuditLogCategory::NetworkBan => SnomaskFlag::Ban,
+                    _ => return,
+                };
+                self.send_snotice(category, &crate::utils::format_audit_entry(&detail.entry));
+            }
+            _ => (),
+        }
+    }
+
+    fn send_quit_snotice(&self, detail: &update::UserQuit) {
+        self.send_snotice(
+            SnomaskFlag::Exit,
+            &format!(
+                "Client exiting: {} [{}]",
+                describe_user(&detail.user),
+                detail.message
+            ),
+        );
+    }
+}
+
+/// `nick (user@host)`, as used in server notices
+fn describe_user(user: &HistoricUser) -> String {
+    format!(
+        "{} ({}@{})",
+        user.nickname, user.user.user, user.user.visible_host
+    )
+}
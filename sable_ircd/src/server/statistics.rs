@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters describing the server's activity, preserved across upgrades
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub(super) struct ServerStatistics {
+    dns_cache_hits: AtomicU64,
+    dns_cache_misses: AtomicU64,
+}
+
+/// A snapshot of [`ServerStatistics`] and related state, as exported via the
+/// management interface
+#[derive(Debug, serde::Serialize)]
+pub(super) struct StatisticsReport {
+    pub connections: usize,
+    pub dns_cache: DnsCacheReport,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub(super) struct DnsCacheReport {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl ServerStatistics {
+    /// Record a completed reverse DNS lookup, and whether it was served from the cache
+    pub fn record_dns_lookup(&self, cached: bool) {
+        let counter = if cached {
+            &self.dns_cache_hits
+        } else {
+            &self.dns_cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn report(&self, connections: usize) -> StatisticsReport {
+        StatisticsReport {
+            connections,
+            dns_cache: DnsCacheReport {
+                hits: self.dns_cache_hits.load(Ordering::Relaxed),
+                misses: self.dns_cache_misses.load(Ordering::Relaxed),
+            },
+        }
+    }
+}
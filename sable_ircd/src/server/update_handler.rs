@@ -13,6 +13,8 @@ impl ClientServer {
             NetworkHistoryUpdate::NewEntry(entry_id) => {
                 let history = self.node.history();
                 if let Some(entry) = history.get(entry_id) {
+                    self.send_state_change_snotices(&entry.details);
+
                     match &entry.details {
                         NetworkStateChange::NewUser(detail) => {
                             detail.notify_monitors(self);
@@ -41,6 +43,11 @@ impl ClientServer {
                             drop(history);
                             self.handle_services_update(&update)?;
                         }
+                        NetworkStateChange::NewAuditLogEntry(detail) => {
+                            let entry = detail.entry.clone();
+                            drop(history);
+                            self.handle_new_audit_entry(&entry);
+                        }
                         NetworkStateChange::EventComplete(_) => {
                             // All
                             self.stored_response_sinks
@@ -59,6 +66,12 @@ impl ClientServer {
                     self.notify_user_update(user_id, entry_id)?;
                 }
             }
+            NetworkHistoryUpdate::Detached => {
+                self.handle_network_detached();
+            }
+            NetworkHistoryUpdate::Resynchronised(moved_connections) => {
+                self.handle_network_resync(&moved_connections);
+            }
         }
 
         Ok(())
@@ -137,6 +150,16 @@ impl ClientServer {
     }
 
     fn handle_new_user_connection(&self, detail: &update::NewUserConnection) -> HandleResult {
+        if self
+            .connections
+            .write()
+            .take_moved_user_connection(detail.connection.id)
+        {
+            // This is an existing client being re-attached after a resync, which has
+            // already been welcomed
+            return Ok(());
+        }
+
         let net = self.node.network();
         let user = net.user(detail.user.user.id)?;
 
@@ -200,6 +223,44 @@ impl ClientServer {
         Ok(())
     }
 
+    fn handle_network_detached(&self) {
+        for conn in self.connections.read().iter() {
+            conn.send(message::Notice::new(
+                self,
+                &UnknownTarget,
+                "*** This server has lost contact with the network. Changes will not take effect until it has reconnected.",
+            ));
+        }
+    }
+
+    fn handle_network_resync(
+        &self,
+        moved_connections: &[(UserConnectionId, Option<UserConnectionId>)],
+    ) {
+        let mut connections = self.connections.write();
+
+        for (old_id, new_id) in moved_connections {
+            match new_id {
+                Some(new_id) => {
+                    if let Some(conn) = connections.move_user_connection(*old_id, *new_id) {
+                        conn.send(message::Notice::new(
+                            self,
+                            &UnknownTarget,
+                            "*** This server has reconnected to the network.",
+                        ));
+                    }
+                }
+                None => {
+                    // The user didn't survive our absence from the network
+                    if let Ok(conn) = connections.get_user_connection(*old_id) {
+                        conn.error("This server lost contact with the network");
+                        connections.remove(conn.id());
+                    }
+                }
+            }
+        }
+    }
+
     fn handle_services_update(&self, detail: &update::ServicesUpdate) -> HandleResult {
         match &detail.new_state {
             Some(state) => {
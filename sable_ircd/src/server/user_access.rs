@@ -9,11 +9,32 @@ pub enum AccessError {
     Banned(String),
     /// User requires SASL but didn't use it
     SaslRequired(String),
+    /// User's connection class is full
+    ConnectionLimit(String),
     /// An internal error occurred while attempting to verify access
     InternalError,
 }
 
 impl ClientServer {
+    /// The username with which a pre-registration client will be registered. If ident
+    /// lookups are enabled, any username not confirmed by ident is prefixed with `~`,
+    /// except for local connections.
+    pub(super) fn registration_username(
+        &self,
+        client: &ClientConnection,
+        pre_client: &PreClient,
+    ) -> Option<Username> {
+        let user = *pre_client.user.get()?;
+
+        Some(match pre_client.ident.get() {
+            Some(ident) => *ident,
+            None if self.ident_lookups && !client.connection.is_local() => {
+                Username::new_coerce(&format!("~{}", user)).unwrap_or(user)
+            }
+            None => user,
+        })
+    }
+
     #[tracing::instrument(skip(self, net))]
     pub(super) fn check_user_access(
         &self,
@@ -25,7 +46,7 @@ impl ClientServer {
                 tracing::error!("PreClient nickname not set");
                 return Err(AccessError::InternalError);
             };
-            let Some(user) = pre_client.user.get().cloned() else {
+            let Some(user) = self.registration_username(client, &pre_client) else {
                 tracing::error!("PreClient username not set");
                 return Err(AccessError::InternalError);
             };
@@ -57,14 +78,26 @@ impl ClientServer {
                 tls,
             };
 
-            for ban in net.network_bans().find_pre_registration(&user_details) {
-                match ban.action {
+            // DNSBL listings are treated exactly like network bans with the equivalent action
+            let bans = net
+                .network_bans()
+                .find_pre_registration(&user_details)
+                .map(|ban| (ban.action, ban.reason.as_str()));
+            let dnsbl_hits = pre_client
+                .dnsbl_hits
+                .get()
+                .into_iter()
+                .flatten()
+                .map(|hit| (hit.action.into(), hit.reason.as_str()));
+
+            for (action, reason) in bans.chain(dnsbl_hits) {
+                match action {
                     NetworkBanAction::RefuseConnection(_) => {
-                        return Err(AccessError::Banned(ban.reason.clone()));
+                        return Err(AccessError::Banned(reason.to_owned()));
                     }
                     NetworkBanAction::RequireSasl(_) => {
                         if pre_client.sasl_account.get().is_none() {
-                            return Err(AccessError::SaslRequired(ban.reason.clone()));
+                            return Err(AccessError::SaslRequired(reason.to_owned()));
                         }
                     }
                     NetworkBanAction::DenySasl => {
@@ -1,5 +1,6 @@
 use std::sync::atomic::{AtomicI64, Ordering};
 
+use parking_lot::RwLock;
 use sable_network::utils::now;
 use serde::{Deserialize, Serialize};
 
@@ -16,11 +17,21 @@ pub struct ThrottleSettings {
     pub burst: i64,
 }
 
+impl Default for ThrottleSettings {
+    fn default() -> Self {
+        Self {
+            num: 1,
+            time: 1,
+            burst: 4,
+        }
+    }
+}
+
 /// A message queue that implements token bucket throttling on read, as well as
 /// enforcing a maximum number of pending messages.
 #[derive(Debug)]
 pub struct ThrottledQueue<T> {
-    settings: ThrottleSettings,
+    settings: RwLock<ThrottleSettings>,
     counter: AtomicI64,
 
     pending: ConcurrentQueue<T>,
@@ -43,25 +54,22 @@ impl<T: std::fmt::Debug> ThrottledQueue<T> {
     /// Construct a `ThrottledQueue` with the given throttle settings and maximum queue size
     pub fn new(settings: ThrottleSettings, max_len: usize) -> Self {
         Self {
-            settings,
+            settings: RwLock::new(settings),
             counter: AtomicI64::new(0),
             pending: ConcurrentQueue::bounded(max_len),
         }
     }
 
-    /*
-        /// Replace the current throttle settings with the provided new settings
-        pub fn change_settings(&mut self, new_settings: ThrottleSettings)
-        {
-            self.settings = new_settings;
-
-            // `self.counter` loses meaning if the multiplier in settings changes, so wipe it out
-            // and start again. The possible side effect here is that if the queue had previously used
-            // up its burst capacity, it will be reset and allowed to immediately burst again. Changing
-            // settings should be an infrequent enough operation that this doesn't matter.
-            self.counter = 0;
-        }
-    */
+    /// Replace the current throttle settings with the provided new settings
+    pub fn change_settings(&self, new_settings: ThrottleSettings) {
+        *self.settings.write() = new_settings;
+
+        // `self.counter` loses meaning if the multiplier in settings changes, so wipe it out
+        // and start again. The possible side effect here is that if the queue had previously used
+        // up its burst capacity, it will be reset and allowed to immediately burst again. Changing
+        // settings should be an infrequent enough operation that this doesn't matter.
+        self.counter.store(0, Ordering::Relaxed);
+    }
 
     /// Add an item to the queue, if doing so does not exceed the maximum capacity
     ///
@@ -77,18 +85,18 @@ impl<T: std::fmt::Debug> ThrottledQueue<T> {
         if self.pending.is_empty() {
             None
         } else {
-            let adjusted_now = now() * self.settings.num;
+            let settings = *self.settings.read();
+            let adjusted_now = now() * settings.num;
 
             // If the counter has fallen behind the adjusted 'now' value, update it to match
             self.counter.fetch_max(adjusted_now, Ordering::Relaxed);
 
-            if self.counter.load(Ordering::Relaxed) + self.settings.time
-                > adjusted_now + self.settings.burst * self.settings.num
+            if self.counter.load(Ordering::Relaxed) + settings.time
+                > adjusted_now + settings.burst * settings.num
             {
                 None
             } else {
-                self.counter
-                    .fetch_add(self.settings.time, Ordering::Relaxed);
+                self.counter.fetch_add(settings.time, Ordering::Relaxed);
                 self.pending.pop().ok()
             }
         }
@@ -108,7 +116,7 @@ impl<T: std::fmt::Debug> ThrottledQueue<T> {
         }
 
         SavedThrottledQueue {
-            settings: self.settings,
+            settings: self.settings.into_inner(),
             counter: self.counter.load(Ordering::Relaxed),
             capacity: self.pending.capacity().unwrap(), // We only construct this type with a bounded queue, so capacity will always be Some
             pending,
@@ -117,7 +125,7 @@ impl<T: std::fmt::Debug> ThrottledQueue<T> {
 
     pub fn restore_from(saved: SavedThrottledQueue<T>) -> Self {
         let ret = Self {
-            settings: saved.settings,
+            settings: RwLock::new(saved.settings),
             counter: AtomicI64::new(saved.counter),
             pending: ConcurrentQueue::bounded(saved.capacity),
         };
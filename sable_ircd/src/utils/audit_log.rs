@@ -0,0 +1,25 @@
+use super::format_timestamp;
+use sable_network::network::state::AuditLogEntry;
+
+use std::fmt::Write;
+
+/// Describe an audit log entry in a single line, for display to opers
+pub fn format_audit_entry(entry: &AuditLogEntry) -> String {
+    let mut line = format!(
+        "[{}] {} {} by {}",
+        format_timestamp(entry.timestamp),
+        entry.category,
+        entry.action,
+        entry.source_str
+    );
+    if let Some(target) = &entry.target_str {
+        write!(line, " on {}", target).expect("failed to write to string?");
+    }
+    if let Some(duration) = entry.target_duration {
+        write!(line, " for {}m", duration).expect("failed to write to string?");
+    }
+    if let Some(reason) = &entry.reason {
+        write!(line, ": {}", reason).expect("failed to write to string?");
+    }
+    line
+}
@@ -15,3 +15,9 @@ pub use serde_once_cell::*;
 
 mod line_wrapper;
 pub use line_wrapper::*;
+
+mod audit_log;
+pub use audit_log::*;
+
+mod server_info;
+pub use server_info::*;
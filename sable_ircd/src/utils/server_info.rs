@@ -0,0 +1,19 @@
+use sable_network::network::wrapper::Server;
+
+/// Describe a server's version, flags and the time since its last ping, as shown
+/// by `LINKS` and `MAP`
+pub fn describe_server(server: &Server, now: i64) -> String {
+    let flags = server.flags();
+    let flags = if flags.is_empty() {
+        "-".to_string()
+    } else {
+        format!("{:?}", flags).to_ascii_lowercase()
+    };
+
+    format!(
+        "{} flags={} last ping {}s ago",
+        server.version(),
+        flags,
+        now - server.last_ping()
+    )
+}
@@ -8,6 +8,9 @@ use crate::{
     node::NetworkNode,
 };
 
+mod query;
+pub use query::*;
+
 pub struct AuditLogger<'a> {
     node: &'a NetworkNode,
     user: Option<UserId>,
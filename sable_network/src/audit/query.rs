@@ -0,0 +1,194 @@
+use crate::{network::state::*, network::Network, types::Pattern};
+
+use chrono::DateTime;
+use thiserror::Error;
+
+/// An error encountered while building an [`AuditLogQuery`]
+#[derive(Debug, Error)]
+pub enum AuditLogQueryError {
+    #[error("Unknown query parameter {0:?}")]
+    UnknownParameter(String),
+    #[error("Invalid audit log category {0:?}")]
+    InvalidCategory(String),
+    #[error("Invalid timestamp {0:?}")]
+    InvalidTimestamp(String),
+    #[error("Invalid limit {0:?}")]
+    InvalidLimit(String),
+}
+
+/// A filter over audit log entries.
+///
+/// Each criterion which is set must match for an entry to be included; an empty
+/// query matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLogQuery {
+    pub category: Option<AuditLogCategory>,
+    /// Matched against the source description of each entry
+    pub source: Option<Pattern>,
+    /// Matched against the target description of each entry. Entries without a target
+    /// never match if this is set.
+    pub target: Option<Pattern>,
+    /// Only include entries at or after this timestamp
+    pub since: Option<i64>,
+    /// Only include entries at or before this timestamp
+    pub until: Option<i64>,
+    /// The maximum number of entries returned by [`run`](Self::run)
+    pub limit: Option<usize>,
+}
+
+impl AuditLogQuery {
+    /// Build a query from `key=value` style parameters. Recognised keys are `category`,
+    /// `source`, `target`, `since`, `until` and `limit`; timestamps may be given either
+    /// as seconds since the epoch or in RFC 3339 format.
+    pub fn from_params<'a>(
+        params: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self, AuditLogQueryError> {
+        let mut query = Self::default();
+
+        for (key, value) in params {
+            match key {
+                "category" => {
+                    query.category = Some(
+                        value
+                            .parse()
+                            .map_err(|_| AuditLogQueryError::InvalidCategory(value.to_owned()))?,
+                    )
+                }
+                "source" => query.source = Some(Pattern::new(value.to_owned())),
+                "target" => query.target = Some(Pattern::new(value.to_owned())),
+                "since" => query.since = Some(parse_timestamp(value)?),
+                "until" => query.until = Some(parse_timestamp(value)?),
+                "limit" => {
+                    query.limit = Some(
+                        value
+                            .parse()
+                            .map_err(|_| AuditLogQueryError::InvalidLimit(value.to_owned()))?,
+                    )
+                }
+                _ => return Err(AuditLogQueryError::UnknownParameter(key.to_owned())),
+            }
+        }
+
+        Ok(query)
+    }
+
+    /// Test whether `entry` satisfies this query. The limit is not considered.
+    pub fn matches(&self, entry: &AuditLogEntry) -> bool {
+        self.category.map_or(true, |c| c == entry.category)
+            && self
+                .source
+                .as_ref()
+                .map_or(true, |p| p.matches(&entry.source_str))
+            && self.target.as_ref().map_or(true, |p| {
+                entry.target_str.as_deref().is_some_and(|t| p.matches(t))
+            })
+            && self.since.map_or(true, |ts| entry.timestamp >= ts)
+            && self.until.map_or(true, |ts| entry.timestamp <= ts)
+    }
+
+    /// Find the entries in `net`'s audit log which match this query, oldest first. If
+    /// a limit is set, the most recent matching entries are returned.
+    pub fn run<'a>(&self, net: &'a Network) -> Vec<&'a AuditLogEntry> {
+        let mut entries: Vec<_> = net.audit_entries().filter(|e| self.matches(e)).collect();
+        entries.sort_by_key(|e| (e.timestamp, e.id));
+
+        if let Some(limit) = self.limit {
+            let excess = entries.len().saturating_sub(limit);
+            entries.drain(..excess);
+        }
+
+        entries
+    }
+}
+
+fn parse_timestamp(value: &str) -> Result<i64, AuditLogQueryError> {
+    if let Ok(ts) = value.parse() {
+        return Ok(ts);
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.timestamp())
+        .map_err(|_| AuditLogQueryError::InvalidTimestamp(value.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::*;
+
+    fn entry(
+        category: AuditLogCategory,
+        timestamp: i64,
+        source: &str,
+        target: Option<&str>,
+    ) -> AuditLogEntry {
+        AuditLogEntry {
+            id: AuditLogEntryId::new(ServerId::new(1), EpochId::new(1), timestamp),
+            timestamp,
+            category,
+            source_id: None,
+            source_addr: None,
+            source_str: source.to_owned(),
+            action: "TEST".to_owned(),
+            target_id: None,
+            target_str: target.map(ToOwned::to_owned),
+            target_duration: None,
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn parses_params() {
+        let query = AuditLogQuery::from_params([
+            ("category", "network-ban"),
+            ("source", "oper!*@*"),
+            ("since", "1000"),
+            ("until", "1970-01-01T00:33:20Z"),
+            ("limit", "5"),
+        ])
+        .unwrap();
+
+        assert_eq!(query.category, Some(AuditLogCategory::NetworkBan));
+        assert_eq!(
+            query.source.as_deref().map(String::as_str),
+            Some("oper!*@*")
+        );
+        assert_eq!(query.since, Some(1000));
+        assert_eq!(query.until, Some(2000));
+        assert_eq!(query.limit, Some(5));
+
+        assert!(matches!(
+            AuditLogQuery::from_params([("category", "nonsense")]),
+            Err(AuditLogQueryError::InvalidCategory(_))
+        ));
+        assert!(matches!(
+            AuditLogQuery::from_params([("colour", "blue")]),
+            Err(AuditLogQueryError::UnknownParameter(_))
+        ));
+    }
+
+    #[test]
+    fn matches_entries() {
+        let query = AuditLogQuery {
+            category: Some(AuditLogCategory::ServerKill),
+            target: Some(Pattern::new("spammer!*".to_owned())),
+            since: Some(100),
+            until: Some(200),
+            ..Default::default()
+        };
+
+        let kill = |ts, target| entry(AuditLogCategory::ServerKill, ts, "oper", target);
+
+        assert!(query.matches(&kill(150, Some("Spammer!user@host"))));
+        assert!(!query.matches(&kill(150, Some("someone!user@host"))));
+        assert!(!query.matches(&kill(150, None)));
+        assert!(!query.matches(&kill(50, Some("spammer!user@host"))));
+        assert!(!query.matches(&kill(250, Some("spammer!user@host"))));
+        assert!(!query.matches(&entry(
+            AuditLogCategory::General,
+            150,
+            "oper",
+            Some("spammer!user@host")
+        )));
+        assert!(AuditLogQuery::default().matches(&kill(0, None)));
+    }
+}
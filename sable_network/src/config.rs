@@ -16,6 +16,31 @@ pub struct TlsData {
     pub cert_chain: Vec<Vec<u8>>,
 }
 
+impl TlsData {
+    /// Check that the certificate chain and private key can be used to
+    /// construct a TLS server configuration
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.cert_chain.is_empty() {
+            anyhow::bail!("No certificates in certificate chain");
+        }
+
+        rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                self.cert_chain
+                    .iter()
+                    .cloned()
+                    .map(rustls::Certificate)
+                    .collect(),
+                rustls::PrivateKey(self.key.clone()),
+            )
+            .context("Invalid certificate or private key")?;
+
+        Ok(())
+    }
+}
+
 impl TlsConfig {
     pub fn load_from_disk(&self) -> Result<TlsData, anyhow::Error> {
         let cert_file = File::open(&self.cert_file)
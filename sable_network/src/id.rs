@@ -44,6 +44,39 @@ object_ids!(ObjectId (ObjectIdGenerator) {
     SaslSession: sequential;
 });
 
+/// Error type denoting that a string couldn't be parsed as a [`NetworkBanId`]
+#[derive(Debug, Error)]
+#[error("Invalid network ban ID")]
+pub struct InvalidNetworkBanId;
+
+/// Network ban IDs are shown to operators as `<server>.<epoch>.<local>`
+impl std::fmt::Display for NetworkBanId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.0 .0, self.1 .0, self.2)
+    }
+}
+
+impl std::str::FromStr for NetworkBanId {
+    type Err = InvalidNetworkBanId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '.').map(|p| p.parse::<LocalId>());
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(server)), Some(Ok(epoch)), Some(Ok(local))) => {
+                Ok(Self::new(ServerId::new(server), EpochId::new(epoch), local))
+            }
+            _ => Err(InvalidNetworkBanId),
+        }
+    }
+}
+
+impl EpochId {
+    /// The epoch ID immediately following this one
+    pub fn next(&self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
 impl NicknameId {
     pub fn nick(&self) -> &Nickname {
         &self.0
@@ -85,3 +118,18 @@ impl ChannelAccessId {
         self.1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_ban_id_round_trip() {
+        let id = NetworkBanId::new(ServerId::new(3), EpochId::new(2), 17);
+        assert_eq!(id.to_string(), "3.2.17");
+        assert_eq!("3.2.17".parse::<NetworkBanId>().unwrap(), id);
+
+        assert!("3.2".parse::<NetworkBanId>().is_err());
+        assert!("3.2.x".parse::<NetworkBanId>().is_err());
+    }
+}
@@ -25,6 +25,20 @@ mode_flags!(
         Invisible       (0x01, 'i'),
         Oper            (0x02, 'o'),
         TlsConnection   (0x04, 'Z'),
+        ServerNotices   (0x08, 's'),
+        OperOverride    (0x10, 'p'),
+    }
+);
+
+mode_flags!(
+    Snomask {
+        Connect (0x01, 'c'),
+        Exit    (0x02, 'q'),
+        Nick    (0x04, 'n'),
+        Kill    (0x08, 'k'),
+        Ban     (0x10, 'b'),
+        Flood   (0x20, 'f'),
+        Reject  (0x40, 'r'),
     }
 );
 
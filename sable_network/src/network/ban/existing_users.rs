@@ -0,0 +1,45 @@
+use super::*;
+use crate::network::{wrapper, Network};
+use crate::prelude::UserModeFlag;
+
+impl PreRegistrationBanSettings {
+    /// Build the ban settings for an existing connection of `user`, so that a pattern
+    /// can be tested against users who have already registered.
+    ///
+    /// The extra parameters to `USER` are not retained after registration, so are
+    /// always empty here.
+    pub fn for_connection(user: &wrapper::User, conn: &wrapper::UserConnection) -> Self {
+        Self {
+            nick: user.nick(),
+            user: *user.user(),
+            host: *conn.hostname(),
+            realname: *user.realname(),
+            ip: *conn.ip(),
+            user_param_1: String::new(),
+            user_param_2: String::new(),
+            tls: user.mode().has_mode(UserModeFlag::TlsConnection),
+        }
+    }
+}
+
+/// Find every user on the network with at least one connection matching `pattern`,
+/// which is interpreted with the fields of [`PreRegistrationBanSettings`].
+///
+/// Users with no current connections, such as detached persistent sessions, have no
+/// address to match against and are never included.
+pub fn users_matching<'a>(
+    net: &'a Network,
+    pattern: &crate::chert::NodeBoolean,
+) -> Vec<wrapper::User<'a>> {
+    let engine: chert::compile::Engine<PreRegistrationBanSettings, usize> =
+        chert::compile::compile_unsafe(std::iter::once((0, pattern)));
+
+    net.users()
+        .filter(|user| {
+            user.connections().any(|conn| {
+                let settings = PreRegistrationBanSettings::for_connection(user, &conn);
+                engine.eval(&settings).into_iter().next().is_some()
+            })
+        })
+        .collect()
+}
@@ -10,6 +10,9 @@ use thiserror::Error;
 mod repository;
 pub use repository::*;
 
+mod existing_users;
+pub use existing_users::*;
+
 /// Describes when a network ban will be matched, and which set of information is available to it
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -19,6 +19,9 @@ pub struct BanRepository {
     pre_registration_engine: Engine<PreRegistrationBanSettings>,
     new_connection_engine: Engine<NewConnectionBanSettings>,
     pre_sasl_engine: Engine<PreSaslBanSettings>,
+
+    // Order-independent hash of the bans held, maintained as they change
+    state_hash: u64,
 }
 
 impl BanRepository {
@@ -33,6 +36,7 @@ impl BanRepository {
             pre_registration_bans,
             new_connection_bans,
             pre_sasl_bans,
+            state_hash: 0,
         }
     }
 
@@ -40,8 +44,11 @@ impl BanRepository {
         let mut pre_registration_bans = HashMap::new();
         let mut new_connection_bans = HashMap::new();
         let mut pre_sasl_bans = HashMap::new();
+        let mut state_hash = 0u64;
 
         for ban in bans {
+            state_hash = state_hash.wrapping_add(object_hash(&ban.id, &ban));
+
             use BanMatchType::*;
             match ban.match_type {
                 PreRegistration => pre_registration_bans.insert(ban.id, ban),
@@ -57,10 +64,15 @@ impl BanRepository {
             pre_registration_bans,
             new_connection_bans,
             pre_sasl_bans,
+            state_hash,
         }
     }
 
     pub fn add(&mut self, ban: state::NetworkBan) {
+        // Replacing an existing ban must remove its contribution to the hash
+        self.remove(ban.id);
+        self.state_hash = self.state_hash.wrapping_add(object_hash(&ban.id, &ban));
+
         use BanMatchType::*;
         match ban.match_type {
             PreRegistration => {
@@ -79,6 +91,9 @@ impl BanRepository {
     }
 
     pub fn remove(&mut self, id: NetworkBanId) {
+        if let Some(ban) = self.get(&id) {
+            self.state_hash = self.state_hash.wrapping_sub(object_hash(&id, ban));
+        }
         if self.pre_registration_bans.remove(&id).is_some() {
             self.pre_registration_engine = Self::compile_engine(&self.pre_registration_bans);
         }
@@ -97,15 +112,39 @@ impl BanRepository {
             .or_else(|| self.pre_sasl_bans.get(id))
     }
 
+    /// The order-independent hash of all bans held, as used in
+    /// [`NetworkStateHash`](crate::network::NetworkStateHash)
+    pub fn content_hash(&self) -> u64 {
+        self.state_hash
+    }
+
+    /// Iterate over all bans, of any match type
+    pub fn iter(&self) -> impl Iterator<Item = &state::NetworkBan> {
+        self.pre_registration_bans
+            .values()
+            .chain(self.new_connection_bans.values())
+            .chain(self.pre_sasl_bans.values())
+    }
+
+    /// Iterate over bans which have expired as of `now` but not yet been removed
+    pub fn expired(&self, now: i64) -> impl Iterator<Item = &state::NetworkBan> {
+        self.iter().filter(move |ban| !ban.is_active_at(now))
+    }
+
+    // The `find_*` methods only return bans which are still in effect; expired
+    // bans remain in the repository until removed, but never match.
+
     pub fn find_pre_registration(
         &self,
         matching: &PreRegistrationBanSettings,
     ) -> impl Iterator<Item = &state::NetworkBan> {
         let matches = self.pre_registration_engine.eval(matching);
+        let now = crate::utils::now();
 
         matches
             .into_iter()
             .filter_map(move |id| self.pre_registration_bans.get(id))
+            .filter(move |ban| ban.is_active_at(now))
     }
 
     pub fn find_new_connection(
@@ -113,10 +152,12 @@ impl BanRepository {
         matching: &NewConnectionBanSettings,
     ) -> impl Iterator<Item = &state::NetworkBan> {
         let matches = self.new_connection_engine.eval(matching);
+        let now = crate::utils::now();
 
         matches
             .into_iter()
             .filter_map(move |id| self.new_connection_bans.get(id))
+            .filter(move |ban| ban.is_active_at(now))
     }
 
     pub fn find_pre_sasl(
@@ -124,10 +165,12 @@ impl BanRepository {
         matching: &PreSaslBanSettings,
     ) -> impl Iterator<Item = &state::NetworkBan> {
         let matches = self.pre_sasl_engine.eval(matching);
+        let now = crate::utils::now();
 
         matches
             .into_iter()
             .filter_map(move |id| self.pre_sasl_bans.get(id))
+            .filter(move |ban| ban.is_active_at(now))
     }
 
     fn compile_engine<V: ChertStructTrait>(
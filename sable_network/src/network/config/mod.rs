@@ -1,13 +1,17 @@
 use super::state;
+use crate::sync::ConfigError;
 use crate::validated::*;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkConfig {
     pub opers: Vec<OperConfig>,
+    /// Named sets of privileges which opers can be assigned
+    #[serde(default)]
+    pub oper_classes: Vec<OperClassConfig>,
     pub debug_mode: bool,
 
     #[serde_as(as = "HashMap<_, state::HumanReadableChannelAccessSet>")]
@@ -30,15 +34,117 @@ pub struct AliasUser {
 pub struct OperConfig {
     pub name: String,
     pub hash: String,
+    /// The class whose privileges this oper receives. Opers with no class
+    /// receive every privilege.
+    #[serde(default)]
+    pub class: Option<String>,
+    /// If not empty, the oper must be connected using a TLS client certificate
+    /// with one of these fingerprints
+    #[serde(default)]
+    pub fingerprints: Vec<String>,
+    /// If set, the oper must be logged in to this account
+    #[serde(default)]
+    pub account: Option<Nickname>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperClassConfig {
+    pub name: String,
+    pub privileges: BTreeSet<state::OperPrivilege>,
 }
 
 impl NetworkConfig {
     pub fn new() -> Self {
         Self {
             opers: Vec::new(),
+            oper_classes: Vec::new(),
             debug_mode: false,
             default_roles: HashMap::new(),
             alias_users: Vec::new(),
         }
     }
+
+    /// Look up an oper class by name
+    pub fn oper_class(&self, name: &str) -> Option<&OperClassConfig> {
+        self.oper_classes.iter().find(|class| class.name == name)
+    }
+
+    /// The privileges granted to the given oper
+    ///
+    /// Unknown class names are rejected by [`validate`](Self::validate), so an oper whose
+    /// class can't be found here is granted nothing rather than everything.
+    pub fn oper_privileges(&self, oper: &OperConfig) -> BTreeSet<state::OperPrivilege> {
+        match &oper.class {
+            Some(class_name) => self
+                .oper_class(class_name)
+                .map(|class| class.privileges.clone())
+                .unwrap_or_default(),
+            None => state::OperPrivilege::all(),
+        }
+    }
+
+    /// Check the network configuration for inconsistencies
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for oper in &self.opers {
+            if let Some(class_name) = &oper.class {
+                if self.oper_class(class_name).is_none() {
+                    return Err(ConfigError::Invalid(format!(
+                        "oper {} refers to unknown oper class {}",
+                        oper.name, class_name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oper(class: Option<&str>) -> OperConfig {
+        OperConfig {
+            name: "oper".to_string(),
+            hash: String::new(),
+            class: class.map(str::to_string),
+            fingerprints: Vec::new(),
+            account: None,
+        }
+    }
+
+    #[test]
+    fn unknown_oper_class_rejected() {
+        let mut config = NetworkConfig::new();
+        config.oper_classes.push(OperClassConfig {
+            name: "killer".to_string(),
+            privileges: [state::OperPrivilege::Kill].into(),
+        });
+
+        config.opers.push(oper(Some("killer")));
+        config.opers.push(oper(None));
+        assert!(config.validate().is_ok());
+
+        config.opers.push(oper(Some("missing")));
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn oper_privileges_from_class() {
+        let mut config = NetworkConfig::new();
+        config.oper_classes.push(OperClassConfig {
+            name: "killer".to_string(),
+            privileges: [state::OperPrivilege::Kill].into(),
+        });
+
+        assert_eq!(
+            config.oper_privileges(&oper(Some("killer"))),
+            [state::OperPrivilege::Kill].into()
+        );
+        assert_eq!(
+            config.oper_privileges(&oper(None)),
+            state::OperPrivilege::all()
+        );
+        assert!(config.oper_privileges(&oper(Some("missing"))).is_empty());
+    }
 }
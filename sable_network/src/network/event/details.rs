@@ -42,6 +42,13 @@ EventDetails => {
         pub message: String,
     }
 
+    /// Many users being removed at once. The target is the server which issued it.
+    #[target_type(ServerId)]
+    struct BulkUserQuit {
+        pub users: Vec<UserId>,
+        pub message: String,
+    }
+
     #[target_type(UserId)]
     struct UserModeChange {
         pub changed_by: ObjectId,
@@ -51,7 +58,11 @@ EventDetails => {
 
     #[target_type(UserId)]
     struct OperUp {
-        pub oper_name: String
+        pub oper_name: String,
+        #[serde(default)]
+        pub class: Option<String>,
+        #[serde(default = "state::OperPrivilege::all")]
+        pub privileges: std::collections::BTreeSet<state::OperPrivilege>,
     }
 
     #[target_type(ChannelId)]
@@ -80,6 +91,13 @@ EventDetails => {
         pub removed_by: UserId,
     }
 
+    /// Many list entries being removed from a channel at once. The target is the channel.
+    #[target_type(ChannelId)]
+    struct BulkDelListModeEntry {
+        pub entries: Vec<ListModeEntryId>,
+        pub removed_by: UserId,
+    }
+
     #[target_type(ChannelTopicId)]
     struct NewChannelTopic {
         pub channel: ChannelId,
@@ -107,6 +125,14 @@ EventDetails => {
         pub message: String,
     }
 
+    /// Many members being kicked from a channel at once. The target is the channel.
+    #[target_type(ChannelId)]
+    struct BulkChannelKick {
+        pub memberships: Vec<MembershipId>,
+        pub source: UserId,
+        pub message: String,
+    }
+
     #[target_type(MembershipId)]
     struct ChannelPart {
         pub message: String,
@@ -136,6 +162,8 @@ EventDetails => {
     struct NewNetworkBan {
         pub match_type: ban::BanMatchType,
         pub pattern: crate::chert::NodeBoolean,
+        #[serde(default)]
+        pub pattern_text: String,
         pub action: ban::NetworkBanAction,
 
         pub timestamp: i64,
@@ -151,6 +179,11 @@ EventDetails => {
         pub remover: UserId,
     }
 
+    /// Removes a ban which has passed its expiry time
+    #[target_type(NetworkBanId)]
+    struct ExpireNetworkBan {
+    }
+
     #[target_type(ServerId)]
     struct NewServer {
         pub epoch: EpochId,
@@ -163,6 +196,10 @@ EventDetails => {
     #[target_type(ServerId)]
     struct ServerPing {
         pub ts: i64,
+        /// The sending server's event clock and combined network state hash
+        /// at the time of the ping, for consistency checking
+        #[serde(default)]
+        pub state_hash: Option<(EventClock, u64)>,
     }
 
     #[target_type(ServerId)]
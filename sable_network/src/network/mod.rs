@@ -106,4 +106,5 @@ pub mod tests {
     mod event_application;
     pub mod fixtures;
     mod serialize;
+    mod state_hash;
 }
@@ -275,6 +275,11 @@ impl Network {
         self.audit_log.get(&id).ok_or(NoSuchAuditLogEntry(id))
     }
 
+    /// Iterate over the audit log, in no particular order
+    pub fn audit_entries(&self) -> impl Iterator<Item = &state::AuditLogEntry> {
+        self.audit_log.values()
+    }
+
     /// Retrieve an account
     pub fn account(&self, id: AccountId) -> LookupResult<wrapper::Account> {
         self.accounts.get(&id).ok_or(NoSuchAccount(id)).wrap(self)
@@ -16,6 +16,7 @@ impl Network {
             created_by: event.id,
             match_type: details.match_type,
             pattern: details.pattern.clone(),
+            pattern_text: details.pattern_text.clone(),
             action: details.action,
             timestamp: details.timestamp,
             expires: details.expires,
@@ -36,4 +37,14 @@ impl Network {
     ) {
         self.network_bans.remove(target);
     }
+
+    pub(super) fn expire_ban(
+        &mut self,
+        target: NetworkBanId,
+        _event: &Event,
+        _details: &details::ExpireNetworkBan,
+        _updates: &dyn NetworkUpdateReceiver,
+    ) {
+        self.network_bans.remove(target);
+    }
 }
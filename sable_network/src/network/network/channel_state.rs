@@ -243,6 +243,21 @@ impl Network {
         }
     }
 
+    pub(super) fn bulk_del_list_mode_entry(
+        &mut self,
+        _target: ChannelId,
+        event: &Event,
+        details: &details::BulkDelListModeEntry,
+        updates: &dyn NetworkUpdateReceiver,
+    ) {
+        let removal = details::DelListModeEntry {
+            removed_by: details.removed_by,
+        };
+        for entry in &details.entries {
+            self.del_list_mode_entry(*entry, event, &removal, updates);
+        }
+    }
+
     pub(super) fn channel_permission_change(
         &mut self,
         target: MembershipId,
@@ -333,6 +348,22 @@ impl Network {
         }
     }
 
+    pub(super) fn bulk_channel_kick(
+        &mut self,
+        _target: ChannelId,
+        event: &Event,
+        details: &details::BulkChannelKick,
+        updates: &dyn NetworkUpdateReceiver,
+    ) {
+        let kick = details::ChannelKick {
+            source: details.source,
+            message: details.message.clone(),
+        };
+        for membership in &details.memberships {
+            self.user_kicked_from_channel(*membership, event, &kick, updates);
+        }
+    }
+
     pub(super) fn user_left_channel(
         &mut self,
         target: MembershipId,
@@ -45,13 +45,11 @@ pub struct Network {
     nick_bindings: HashMap<Nickname, state::NickBinding>,
     #[serde_as(as = "Vec<(_,_)>")]
     historic_nick_users: HashMap<Nickname, VecDeque<HistoricUser>>,
-    #[serde_as(as = "Vec<(_,_)>")]
-    users: HashMap<UserId, state::User>,
+    users: HashedMap<UserId, state::User>,
     #[serde_as(as = "Vec<(_,_)>")]
     user_connections: HashMap<UserConnectionId, state::UserConnection>,
 
-    #[serde_as(as = "Vec<(_,_)>")]
-    channels: HashMap<ChannelId, state::Channel>,
+    channels: HashedMap<ChannelId, state::Channel>,
     #[serde_as(as = "Vec<(_,_)>")]
     list_mode_entries: HashMap<ListModeEntryId, state::ListModeEntry>,
     #[serde_as(as = "Vec<(_,_)>")]
@@ -59,8 +57,7 @@ pub struct Network {
     #[serde_as(as = "Vec<(_,_)>")]
     channel_invites: HashMap<InviteId, state::ChannelInvite>,
 
-    #[serde_as(as = "Vec<(_,_)>")]
-    memberships: HashMap<MembershipId, state::Membership>,
+    memberships: HashedMap<MembershipId, state::Membership>,
 
     #[serde_as(as = "Vec<(_,_)>")]
     messages: HashMap<MessageId, state::Message>,
@@ -73,20 +70,15 @@ pub struct Network {
     #[serde_as(as = "Vec<(_,_)>")]
     audit_log: HashMap<AuditLogEntryId, state::AuditLogEntry>,
 
-    #[serde_as(as = "Vec<(_,_)>")]
-    accounts: HashMap<AccountId, state::Account>,
+    accounts: HashedMap<AccountId, state::Account>,
 
-    #[serde_as(as = "Vec<(_,_)>")]
-    nick_registrations: HashMap<NickRegistrationId, state::NickRegistration>,
+    nick_registrations: HashedMap<NickRegistrationId, state::NickRegistration>,
 
-    #[serde_as(as = "Vec<(_,_)>")]
-    channel_registrations: HashMap<ChannelRegistrationId, state::ChannelRegistration>,
+    channel_registrations: HashedMap<ChannelRegistrationId, state::ChannelRegistration>,
 
-    #[serde_as(as = "Vec<(_,_)>")]
-    channel_accesses: HashMap<ChannelAccessId, state::ChannelAccess>,
+    channel_accesses: HashedMap<ChannelAccessId, state::ChannelAccess>,
 
-    #[serde_as(as = "Vec<(_,_)>")]
-    channel_roles: HashMap<ChannelRoleId, state::ChannelRole>,
+    channel_roles: HashedMap<ChannelRoleId, state::ChannelRole>,
 
     current_services: Option<state::ServicesData>,
     config: config::NetworkConfig,
@@ -107,13 +99,13 @@ impl Network {
         let net = Network {
             nick_bindings: HashMap::new(),
             historic_nick_users: HashMap::new(),
-            users: HashMap::new(),
+            users: HashedMap::new(),
             user_connections: HashMap::new(),
 
-            channels: HashMap::new(),
+            channels: HashedMap::new(),
             channel_topics: HashMap::new(),
             list_mode_entries: HashMap::new(),
-            memberships: HashMap::new(),
+            memberships: HashedMap::new(),
             channel_invites: HashMap::new(),
 
             messages: HashMap::new(),
@@ -122,11 +114,11 @@ impl Network {
 
             audit_log: HashMap::new(),
 
-            accounts: HashMap::new(),
-            nick_registrations: HashMap::new(),
-            channel_registrations: HashMap::new(),
-            channel_accesses: HashMap::new(),
-            channel_roles: HashMap::new(),
+            accounts: HashedMap::new(),
+            nick_registrations: HashedMap::new(),
+            channel_registrations: HashedMap::new(),
+            channel_accesses: HashedMap::new(),
+            channel_roles: HashedMap::new(),
 
             current_services: None,
             config,
@@ -183,22 +175,26 @@ impl Network {
             NewUserConnection => self.new_user_connection,
             UserDisconnect => self.user_disconnect,
             UserQuit => self.user_quit,
+            BulkUserQuit => self.bulk_user_quit,
             UserModeChange => self.user_mode_change,
             OperUp => self.oper_up,
             NewChannel => self.new_channel,
             ChannelModeChange => self.channel_mode_change,
             NewListModeEntry => self.new_list_mode_entry,
             DelListModeEntry => self.del_list_mode_entry,
+            BulkDelListModeEntry => self.bulk_del_list_mode_entry,
             NewChannelTopic => self.new_channel_topic,
             MembershipFlagChange => self.channel_permission_change,
             ChannelJoin => self.user_joined_channel,
             ChannelKick => self.user_kicked_from_channel,
+            BulkChannelKick => self.bulk_channel_kick,
             ChannelPart => self.user_left_channel,
             ChannelRename => self.user_renamed_channel,
             ChannelInvite => self.new_channel_invite,
             NewMessage => self.new_message,
             NewNetworkBan => self.new_ban,
             RemoveNetworkBan => self.remove_ban,
+            ExpireNetworkBan => self.expire_ban,
             NewServer => self.new_server,
             ServerPing => self.server_ping,
             ServerQuit => self.server_quit,
@@ -217,6 +213,7 @@ impl Network {
         })?;
 
         self.clock.update_with_id(event.id);
+        self.flush_state_hash();
         updates.notify(EventComplete {}, event);
 
         Ok(())
@@ -283,3 +280,6 @@ mod message_state;
 mod oper_state;
 mod server_state;
 mod user_state;
+
+mod state_hash;
+pub use state_hash::*;
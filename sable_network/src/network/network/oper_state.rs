@@ -14,6 +14,8 @@ impl Network {
 
             user.oper_privileges = Some(UserPrivileges {
                 oper_name: details.oper_name.clone(),
+                class: details.class.clone(),
+                privileges: details.privileges.clone(),
             });
 
             user.mode.modes |= UserModeFlag::Oper;
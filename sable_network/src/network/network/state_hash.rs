@@ -0,0 +1,277 @@
+use super::*;
+
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    hash::Hash,
+};
+
+/// The categories of network state covered by a [`NetworkStateHash`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateHashCategory {
+    Users,
+    Channels,
+    Memberships,
+    NetworkBans,
+    Accounts,
+    NickRegistrations,
+    ChannelRegistrations,
+    ChannelAccesses,
+    ChannelRoles,
+}
+
+/// A canonical hash of the contents of a [`Network`].
+///
+/// Each object is hashed individually, and the results for each category are
+/// summed, so the result does not depend on the order in which objects are
+/// stored or were created. Two nodes which have applied the same set of events
+/// should always produce the same hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkStateHash {
+    pub categories: BTreeMap<StateHashCategory, u64>,
+}
+
+impl NetworkStateHash {
+    /// Combine the per-category hashes into a single value
+    pub fn combined(&self) -> u64 {
+        self.categories.iter().fold(0u64, |acc, (category, hash)| {
+            acc.wrapping_add(object_hash(category, hash))
+        })
+    }
+
+    /// List the categories in which this hash differs from `other`
+    pub fn differing_categories(&self, other: &NetworkStateHash) -> Vec<StateHashCategory> {
+        self.categories
+            .keys()
+            .chain(other.categories.keys())
+            .filter(|c| self.categories.get(c) != other.categories.get(c))
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn add(&mut self, category: StateHashCategory, hash: u64) {
+        let entry = self.categories.entry(category).or_default();
+        *entry = entry.wrapping_add(hash);
+    }
+}
+
+/// Hash a single state object, identified by its key
+pub(crate) fn object_hash(key: &impl Serialize, value: &impl Serialize) -> u64 {
+    let mut hasher = Sha1::new();
+    hasher.update(serde_json::to_vec(key).expect("Couldn't serialise object key"));
+    hasher.update(serde_json::to_vec(value).expect("Couldn't serialise object"));
+    let digest = hasher.finalize();
+
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// A map which maintains an order-independent hash of its contents.
+///
+/// Read access is provided through `Deref`; the only permitted mutations are
+/// those defined here, each of which records the affected keys so that the
+/// hash can be brought up to date without rehashing the whole map.
+#[derive(Debug, Clone)]
+pub(crate) struct HashedMap<K, V> {
+    map: HashMap<K, V>,
+    hashes: HashMap<K, u64>,
+    total: u64,
+    dirty: HashSet<K>,
+}
+
+impl<K, V> HashedMap<K, V>
+where
+    K: Eq + Hash + Clone + Serialize,
+    V: Serialize,
+{
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            hashes: HashMap::new(),
+            total: 0,
+            dirty: HashSet::new(),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.dirty.insert(key.clone());
+        self.map.insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let ret = self.map.remove(key);
+        if ret.is_some() {
+            self.dirty.insert(key.clone());
+        }
+        ret
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let ret = self.map.get_mut(key);
+        if ret.is_some() {
+            self.dirty.insert(key.clone());
+        }
+        ret
+    }
+
+    pub fn extract_if<F>(&mut self, pred: F) -> impl Iterator<Item = (K, V)>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let extracted: Vec<_> = self.map.extract_if(pred).collect();
+        for (key, _) in extracted.iter() {
+            self.dirty.insert(key.clone());
+        }
+        extracted.into_iter()
+    }
+
+    /// The hash of the map's current contents
+    pub fn content_hash(&self) -> u64 {
+        // Anything not yet folded into the running total is accounted for here
+        self.dirty.iter().fold(self.total, |total, key| {
+            let old = self.hashes.get(key).copied().unwrap_or(0);
+            let new = self.map.get(key).map(|v| object_hash(key, v)).unwrap_or(0);
+            total.wrapping_sub(old).wrapping_add(new)
+        })
+    }
+
+    /// Fold any changes since the last call into the running hash
+    pub fn flush(&mut self) {
+        for key in self.dirty.drain() {
+            if let Some(old) = self.hashes.remove(&key) {
+                self.total = self.total.wrapping_sub(old);
+            }
+            if let Some(value) = self.map.get(&key) {
+                let new = object_hash(&key, value);
+                self.total = self.total.wrapping_add(new);
+                self.hashes.insert(key, new);
+            }
+        }
+    }
+}
+
+impl<K, V> std::ops::Deref for HashedMap<K, V> {
+    type Target = HashMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<K, V> Serialize for HashedMap<K, V>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // Same representation as the `Vec<(_,_)>` used for other maps in `Network`
+        serializer.collect_seq(self.map.iter())
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for HashedMap<K, V>
+where
+    K: Eq + Hash + Clone + Serialize + Deserialize<'de>,
+    V: Serialize + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let items = Vec::<(K, V)>::deserialize(deserializer)?;
+        let mut ret = Self::new();
+        for (k, v) in items {
+            ret.insert(k, v);
+        }
+        ret.flush();
+        Ok(ret)
+    }
+}
+
+impl Network {
+    /// Return the canonical hash of the current network state.
+    ///
+    /// This is maintained incrementally as events are applied, so is cheap to call.
+    pub fn state_hash(&self) -> NetworkStateHash {
+        use StateHashCategory::*;
+
+        let mut hash = NetworkStateHash::default();
+
+        hash.add(Users, self.users.content_hash());
+        hash.add(Channels, self.channels.content_hash());
+        hash.add(Memberships, self.memberships.content_hash());
+        hash.add(NetworkBans, self.network_bans.content_hash());
+        hash.add(Accounts, self.accounts.content_hash());
+        hash.add(NickRegistrations, self.nick_registrations.content_hash());
+        hash.add(
+            ChannelRegistrations,
+            self.channel_registrations.content_hash(),
+        );
+        hash.add(ChannelAccesses, self.channel_accesses.content_hash());
+        hash.add(ChannelRoles, self.channel_roles.content_hash());
+
+        hash
+    }
+
+    /// Compute the canonical hash of the current network state from scratch,
+    /// without relying on incrementally maintained values.
+    ///
+    /// The result should always equal that of [`state_hash`](Self::state_hash).
+    pub fn compute_state_hash(&self) -> NetworkStateHash {
+        use StateHashCategory::*;
+
+        let mut hash = NetworkStateHash::default();
+
+        fn add_all<'a, K: Serialize + 'a, V: Serialize + 'a>(
+            hash: &mut NetworkStateHash,
+            category: StateHashCategory,
+            items: impl Iterator<Item = (&'a K, &'a V)>,
+        ) {
+            // Make sure every category is present, even if empty
+            hash.add(category, 0);
+            for (k, v) in items {
+                hash.add(category, object_hash(k, v));
+            }
+        }
+
+        add_all(&mut hash, Users, self.users.iter());
+        add_all(&mut hash, Channels, self.channels.iter());
+        add_all(&mut hash, Memberships, self.memberships.iter());
+        add_all(
+            &mut hash,
+            NetworkBans,
+            self.network_bans.iter().map(|ban| (&ban.id, ban)),
+        );
+        add_all(&mut hash, Accounts, self.accounts.iter());
+        add_all(&mut hash, NickRegistrations, self.nick_registrations.iter());
+        add_all(
+            &mut hash,
+            ChannelRegistrations,
+            self.channel_registrations.iter(),
+        );
+        add_all(&mut hash, ChannelAccesses, self.channel_accesses.iter());
+        add_all(&mut hash, ChannelRoles, self.channel_roles.iter());
+
+        hash
+    }
+
+    /// Fold the changes made by the last applied event into the state hash
+    pub(super) fn flush_state_hash(&mut self) {
+        self.users.flush();
+        self.channels.flush();
+        self.memberships.flush();
+        self.accounts.flush();
+        self.nick_registrations.flush();
+        self.channel_registrations.flush();
+        self.channel_accesses.flush();
+        self.channel_roles.flush();
+    }
+}
@@ -4,6 +4,8 @@ use crate::{
     prelude::state::UserSessionKey,
 };
 
+use std::cell::RefCell;
+
 impl Network {
     pub(super) fn remove_user(
         &mut self,
@@ -300,6 +302,29 @@ impl Network {
         self.remove_user(target, quit.message.clone(), event, updates);
     }
 
+    pub(super) fn bulk_user_quit(
+        &mut self,
+        _source: ServerId,
+        event: &Event,
+        detail: &details::BulkUserQuit,
+        updates: &dyn NetworkUpdateReceiver,
+    ) {
+        // Gather the individual quits into a single update, so that each recipient
+        // can be notified once rather than for every user removed
+        let collector = BulkQuitCollector {
+            inner: updates,
+            quits: RefCell::new(Vec::new()),
+        };
+        for user in &detail.users {
+            self.remove_user(*user, detail.message.clone(), event, &collector);
+        }
+
+        let items = collector.quits.into_inner();
+        if !items.is_empty() {
+            updates.notify(update::BulkUserQuit { items }, event);
+        }
+    }
+
     pub(super) fn enable_persistent_session(
         &mut self,
         target: UserId,
@@ -393,3 +418,19 @@ impl Network {
         }
     }
 }
+
+/// Passes through every update except user quits, which are retained to be sent
+/// together as a [`BulkUserQuit`](update::BulkUserQuit)
+struct BulkQuitCollector<'a> {
+    inner: &'a dyn NetworkUpdateReceiver,
+    quits: RefCell<Vec<update::UserQuit>>,
+}
+
+impl NetworkUpdateReceiver for BulkQuitCollector<'_> {
+    fn notify_update(&self, update: NetworkStateChange, source_event: &Event) {
+        match update {
+            NetworkStateChange::UserQuit(quit) => self.quits.borrow_mut().push(quit),
+            other => self.inner.notify_update(other, source_event),
+        }
+    }
+}
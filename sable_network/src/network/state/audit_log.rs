@@ -3,11 +3,15 @@ use crate::prelude::*;
 use serde::{Deserialize, Serialize};
 
 /// An audit log category
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::Display, strum::EnumString,
+)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
 pub enum AuditLogCategory {
     General,
     NetworkBan,
     ServerKill,
+    StateDivergence,
 }
 
 /// An audit log entry
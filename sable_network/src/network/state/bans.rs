@@ -12,6 +12,10 @@ pub struct NetworkBan {
 
     pub match_type: BanMatchType,
     pub pattern: crate::chert::NodeBoolean,
+    /// The pattern as originally written, for display. Empty for bans created
+    /// before this was recorded.
+    #[serde(default)]
+    pub pattern_text: String,
     pub action: NetworkBanAction,
 
     pub timestamp: i64,
@@ -20,3 +24,13 @@ pub struct NetworkBan {
     pub oper_reason: Option<String>,
     pub setter_info: String,
 }
+
+impl NetworkBan {
+    /// Whether this ban is still in effect at the given time.
+    ///
+    /// Expired bans are ignored wherever bans are matched, so that every server agrees
+    /// on when a ban stops applying regardless of when the ban is actually removed.
+    pub fn is_active_at(&self, now: i64) -> bool {
+        now < self.expires
+    }
+}
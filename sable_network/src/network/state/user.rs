@@ -1,7 +1,8 @@
 use crate::prelude::*;
-use std::net::IpAddr;
+use std::{collections::BTreeSet, net::IpAddr};
 
 use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
 
 /// A nickname binding.
 ///
@@ -72,10 +73,67 @@ pub struct UserMode {
     pub modes: UserModeSet,
 }
 
+/// A privilege which can be granted to operators by their oper class
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumIter,
+    strum::Display,
+)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum OperPrivilege {
+    /// Disconnect other users
+    Kill,
+    /// Add and remove klines
+    Kline,
+    /// Add, remove and list network bans of any type
+    Ban,
+    /// See the hosts, addresses and servers of other users
+    SeeConnectionInfo,
+    /// Act in channels regardless of channel modes and access, once override (user
+    /// mode `+p`) is enabled
+    OverrideChannelModes,
+    /// Use services administration commands
+    ManageServices,
+    /// Query and subscribe to the audit log
+    ViewAuditLog,
+    /// Kill, ban or kick many users at once by pattern
+    MassAction,
+}
+
+impl OperPrivilege {
+    /// The set of every privilege
+    pub fn all() -> BTreeSet<Self> {
+        Self::iter().collect()
+    }
+}
+
 /// A user's operator privileges
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserPrivileges {
     pub oper_name: String,
+    /// The oper class from which `privileges` were taken, if any
+    #[serde(default)]
+    pub class: Option<String>,
+    /// Opers who predate privileges being recorded were permitted everything
+    #[serde(default = "OperPrivilege::all")]
+    pub privileges: BTreeSet<OperPrivilege>,
+}
+
+impl UserPrivileges {
+    /// Whether these privileges include `privilege`
+    pub fn has(&self, privilege: OperPrivilege) -> bool {
+        self.privileges.contains(&privilege)
+    }
 }
 
 impl NickBinding {
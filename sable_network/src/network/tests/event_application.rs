@@ -20,3 +20,101 @@ fn add_and_remove_user() {
 
     assert_eq!(empty_net, modified_net);
 }
+
+#[test]
+fn bulk_remove_users() {
+    let mut builder = NetworkBuilder::new();
+    let nicks: Vec<_> = ["aaa", "bbb", "ccc"]
+        .iter()
+        .map(|n| Nickname::from_str(n).unwrap())
+        .collect();
+    for nick in &nicks {
+        builder.add_user(*nick);
+    }
+    let ids: Vec<_> = nicks[..2]
+        .iter()
+        .map(|nick| builder.net.user_by_nick(nick).unwrap().id())
+        .collect();
+
+    builder.bulk_remove_users(ids);
+
+    assert!(builder.net.user_by_nick(&nicks[0]).is_err());
+    assert!(builder.net.user_by_nick(&nicks[1]).is_err());
+    assert!(builder.net.user_by_nick(&nicks[2]).is_ok());
+    assert_eq!(builder.net.users().count(), 1);
+}
+
+#[test]
+fn bulk_kick_and_clear_list() {
+    let mut builder = NetworkBuilder::new();
+    let nicks: Vec<_> = ["aaa", "bbb", "ccc"]
+        .iter()
+        .map(|n| Nickname::from_str(n).unwrap())
+        .collect();
+    for nick in &nicks {
+        builder.add_user(*nick);
+    }
+    let ids: Vec<_> = nicks
+        .iter()
+        .map(|nick| builder.net.user_by_nick(nick).unwrap().id())
+        .collect();
+    let chan_name = ChannelName::from_str("#chan").unwrap();
+    builder.add_channel(chan_name);
+    let channel = builder.net.channel_by_name(&chan_name).unwrap().id();
+
+    let memberships: Vec<_> = ids.iter().map(|id| builder.join(*id, channel)).collect();
+    let entries: Vec<_> = ["a!*@*", "b!*@*"]
+        .iter()
+        .map(|p| builder.add_list_entry(channel, ListModeType::Ban, p, ids[0]))
+        .collect();
+    let kept = builder.add_list_entry(channel, ListModeType::Quiet, "c!*@*", ids[0]);
+
+    builder.bulk_kick(channel, memberships[1..].to_vec(), ids[0]);
+    builder.bulk_remove_list_entries(channel, entries, ids[0]);
+
+    let chan = builder.net.channel_by_name(&chan_name).unwrap();
+    let members: Vec<_> = chan.members().map(|m| m.user_id()).collect();
+    assert_eq!(members, vec![ids[0]]);
+    assert_eq!(chan.list(ListModeType::Ban).entries().count(), 0);
+    let quiets: Vec<_> = chan
+        .list(ListModeType::Quiet)
+        .entries()
+        .map(|e| e.id())
+        .collect();
+    assert_eq!(quiets, vec![kept]);
+}
+
+#[test]
+fn remove_and_expire_network_bans() {
+    let mut builder = NetworkBuilder::new();
+    let removed = builder.add_network_ban("ip in 192.0.2.0/24", i64::MAX);
+    let expired = builder.add_network_ban("ip in 198.51.100.0/24", 100);
+    let kept = builder.add_network_ban("ip in 203.0.113.0/24", i64::MAX);
+
+    let bans = builder.net.network_bans();
+    let expired_ids: Vec<_> = bans.expired(150).map(|ban| ban.id).collect();
+    assert_eq!(expired_ids, vec![expired]);
+
+    // Expired bans stay in place until removed, but no longer match
+    let matching = |ip: &str| {
+        let settings = ban::NewConnectionBanSettings {
+            ip: ip.parse().unwrap(),
+            tls: false,
+        };
+        bans.find_new_connection(&settings).count()
+    };
+    assert_eq!(matching("192.0.2.1"), 1);
+    assert_eq!(matching("198.51.100.1"), 0);
+
+    builder.remove_network_ban(removed, UserId::new(ServerId::new(1), EpochId::new(1), 1));
+    builder.expire_network_ban(expired);
+
+    let remaining: Vec<_> = builder
+        .net
+        .network_bans()
+        .iter()
+        .map(|ban| ban.id)
+        .collect();
+    assert_eq!(remaining, vec![kept]);
+    assert_eq!(builder.net.state_hash(), builder.net.compute_state_hash());
+}
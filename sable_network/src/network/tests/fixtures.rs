@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use event::*;
+use std::collections::BTreeSet;
 use std::str::FromStr;
 
 pub struct NetworkBuilder {
@@ -48,6 +49,70 @@ impl NetworkBuilder {
         );
     }
 
+    pub fn join(&mut self, user: UserId, channel: ChannelId) -> MembershipId {
+        let id = MembershipId::new(user, channel);
+        self.apply(
+            id,
+            details::ChannelJoin {
+                channel,
+                user,
+                permissions: MembershipFlagSet::new(),
+            },
+        );
+        id
+    }
+
+    pub fn bulk_kick(
+        &mut self,
+        channel: ChannelId,
+        memberships: Vec<MembershipId>,
+        source: UserId,
+    ) {
+        self.apply(
+            channel,
+            details::BulkChannelKick {
+                memberships,
+                source,
+                message: "kick".to_string(),
+            },
+        )
+    }
+
+    /// Add an entry to one of a channel's lists, returning its ID
+    pub fn add_list_entry(
+        &mut self,
+        channel: ChannelId,
+        list_type: ListModeType,
+        pattern: &str,
+        setter: UserId,
+    ) -> ListModeEntryId {
+        let id = self.id_gen.next_list_mode_entry();
+        self.apply(
+            id,
+            details::NewListModeEntry {
+                list: ListModeId::new(channel, list_type),
+                pattern: Pattern::new(pattern.to_string()),
+                setter,
+            },
+        );
+        id
+    }
+
+    pub fn bulk_remove_list_entries(
+        &mut self,
+        channel: ChannelId,
+        entries: Vec<ListModeEntryId>,
+        removed_by: UserId,
+    ) {
+        self.apply(
+            channel,
+            details::BulkDelListModeEntry {
+                entries,
+                removed_by,
+            },
+        )
+    }
+
     pub fn add_user(&mut self, nick: Nickname) {
         self.apply(
             self.id_gen.next_user(),
@@ -64,6 +129,42 @@ impl NetworkBuilder {
         );
     }
 
+    pub fn oper_up(&mut self, id: UserId, privileges: BTreeSet<state::OperPrivilege>) {
+        self.apply(
+            id,
+            details::OperUp {
+                oper_name: "oper".to_string(),
+                class: None,
+                privileges,
+            },
+        )
+    }
+
+    /// Register an account with the given name, returning its ID
+    pub fn add_account(&mut self, name: Nickname) -> AccountId {
+        let id = self.id_gen.next_account();
+        self.apply(
+            id,
+            details::AccountUpdate {
+                data: Some(state::Account {
+                    id,
+                    name,
+                    authorised_fingerprints: Vec::new(),
+                }),
+            },
+        );
+        id
+    }
+
+    pub fn log_in(&mut self, id: UserId, account: AccountId) {
+        self.apply(
+            id,
+            details::UserLogin {
+                account: Some(account),
+            },
+        )
+    }
+
     pub fn remove_user(&mut self, id: UserId) {
         self.apply(
             id,
@@ -72,4 +173,45 @@ impl NetworkBuilder {
             },
         )
     }
+
+    pub fn bulk_remove_users(&mut self, users: Vec<UserId>) {
+        self.apply(
+            ServerId::new(1),
+            details::BulkUserQuit {
+                users,
+                message: "quit".to_string(),
+            },
+        )
+    }
+
+    /// Add a new-connection ban with the given pattern, returning its ID
+    pub fn add_network_ban(&mut self, pattern_text: &str, expires: i64) -> NetworkBanId {
+        let id = self.id_gen.next_network_ban();
+        let pattern = crate::chert::parse::<ban::NewConnectionBanSettings>(pattern_text)
+            .unwrap()
+            .into_root();
+        self.apply(
+            id,
+            details::NewNetworkBan {
+                match_type: ban::BanMatchType::NewConnection,
+                pattern,
+                pattern_text: pattern_text.to_string(),
+                action: ban::NetworkBanAction::RefuseConnection(false),
+                timestamp: 0,
+                expires,
+                reason: "banned".to_string(),
+                oper_reason: None,
+                setter_info: "test".to_string(),
+            },
+        );
+        id
+    }
+
+    pub fn remove_network_ban(&mut self, id: NetworkBanId, remover: UserId) {
+        self.apply(id, details::RemoveNetworkBan { remover })
+    }
+
+    pub fn expire_network_ban(&mut self, id: NetworkBanId) {
+        self.apply(id, details::ExpireNetworkBan {})
+    }
 }
@@ -0,0 +1,58 @@
+use super::fixtures::*;
+use crate::prelude::*;
+use std::str::FromStr;
+
+#[test]
+fn state_hash_survives_serialization() {
+    let mut builder = NetworkBuilder::new();
+    builder.add_user(Nickname::from_str("a").unwrap());
+    builder.add_user(Nickname::from_str("b").unwrap());
+    builder.add_channel(ChannelName::from_str("#a").unwrap());
+    builder.add_channel(ChannelName::from_str("#b").unwrap());
+
+    let str = serde_json::to_string(&builder.net).unwrap();
+    let net: Network = serde_json::from_str(&str).unwrap();
+
+    assert_eq!(builder.net.state_hash(), net.state_hash());
+    assert_eq!(
+        builder.net.state_hash().combined(),
+        net.state_hash().combined()
+    );
+}
+
+#[test]
+fn state_hash_reports_differing_categories() {
+    let mut builder1 = NetworkBuilder::new();
+    builder1.add_user(Nickname::from_str("a").unwrap());
+    builder1.add_channel(ChannelName::from_str("#a").unwrap());
+
+    let mut builder2 = NetworkBuilder::new();
+    builder2.add_user(Nickname::from_str("b").unwrap());
+    builder2.add_channel(ChannelName::from_str("#a").unwrap());
+
+    let hash1 = builder1.net.state_hash();
+    let hash2 = builder2.net.state_hash();
+
+    assert_ne!(hash1.combined(), hash2.combined());
+    assert_eq!(
+        hash1.differing_categories(&hash2),
+        vec![StateHashCategory::Users]
+    );
+}
+
+#[test]
+fn incremental_state_hash_matches_full_computation() {
+    let mut builder = NetworkBuilder::new();
+    builder.add_user(Nickname::from_str("a").unwrap());
+    builder.add_user(Nickname::from_str("b").unwrap());
+    builder.add_channel(ChannelName::from_str("#a").unwrap());
+
+    assert_eq!(builder.net.state_hash(), builder.net.compute_state_hash());
+
+    let before_quit = builder.net.state_hash();
+    let user_id = builder.net.users().next().unwrap().id();
+    builder.remove_user(user_id);
+
+    assert_ne!(before_quit, builder.net.state_hash());
+    assert_eq!(builder.net.state_hash(), builder.net.compute_state_hash());
+}
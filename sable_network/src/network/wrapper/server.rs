@@ -27,6 +27,16 @@ impl Server<'_> {
     pub fn last_ping(&self) -> i64 {
         self.data.last_ping
     }
+
+    /// The flags this server announced when it joined the network
+    pub fn flags(&self) -> state::ServerFlags {
+        self.data.flags
+    }
+
+    /// The software version this server is running
+    pub fn version(&self) -> &str {
+        &self.data.version
+    }
 }
 
 impl<'a> super::ObjectWrapper<'a> for Server<'a> {
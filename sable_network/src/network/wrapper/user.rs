@@ -86,6 +86,11 @@ impl<'a> User<'a> {
         self.data.oper_privileges.as_ref()
     }
 
+    /// Test whether this user is an operator with the given privilege
+    pub fn has_oper_privilege(&self, privilege: state::OperPrivilege) -> bool {
+        self.oper_privileges().is_some_and(|p| p.has(privilege))
+    }
+
     /// Returns the user's reason for being away, or the empty string if they are not
     pub fn away_reason(&self) -> Option<&AwayReason> {
         self.data.away_reason.as_ref()
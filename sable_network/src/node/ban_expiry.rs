@@ -0,0 +1,41 @@
+use super::*;
+
+impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
+    /// Remove network bans which have expired.
+    ///
+    /// Expired bans already have no effect, so this only keeps them from accumulating.
+    /// To avoid every server emitting the same event, each ban is removed by the
+    /// server which created it, or by the server with the lowest ID if that one is
+    /// no longer part of the network.
+    pub(super) fn check_ban_expiry(&self) {
+        if self.is_detached() {
+            return;
+        }
+
+        let now = utils::now();
+
+        let expired: Vec<_> = {
+            let net = self.net.read();
+            let fallback = net.servers().map(|s| s.id()).min();
+
+            net.network_bans()
+                .expired(now)
+                .filter(|ban| {
+                    let creator = ban.created_by.server();
+                    let responsible = if net.server(creator).is_ok() {
+                        Some(creator)
+                    } else {
+                        fallback
+                    };
+                    responsible == Some(self.my_id)
+                })
+                .map(|ban| ban.id)
+                .collect()
+        };
+
+        for id in expired {
+            tracing::debug!(?id, "Removing expired network ban");
+            self.submit_event(id, details::ExpireNetworkBan {});
+        }
+    }
+}
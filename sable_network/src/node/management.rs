@@ -1,9 +1,20 @@
 use super::*;
+use crate::sync::inspect::*;
 
-/// Statistics to be exported via the management interface
+/// How long a server must have been silent before it can be removed via the
+/// management interface. Pings are sent every 60 seconds, so this allows for at least
+/// one to have been missed.
+const MIN_UNRESPONSIVE_DURATION: i64 = 120;
+
+/// How many times to query each server's state hash while waiting for its clock to
+/// match ours, and how long to wait between attempts
+const DIVERGENCE_CHECK_ATTEMPTS: usize = 5;
+const DIVERGENCE_CHECK_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Statistics to be exported via the management interface. The application
+/// may add its own to these.
 #[derive(serde::Serialize)]
 struct ServerStatistics {
-    connected_clients: usize,
     event_stats: crate::sync::EventLogStats,
 }
 
@@ -11,24 +22,184 @@ impl NetworkNode {
     pub async fn handle_management_command(&self, cmd: ServerManagementCommand) {
         use ServerManagementCommandType::*;
         let resp = match cmd.cmd {
-            ServerStatistics => self.export_server_statistics(),
-            DumpNetwork => self.dump_network_state(),
-            DumpEvents => self.dump_events(),
+            ServerStatistics => Ok(self.export_server_statistics()),
+            DumpNetwork => Ok(self.dump_network_state()),
+            DumpEvents => Ok(self.dump_events()),
+            EventGraph => Ok(self.export_event_graph()),
+            PendingEvents => Ok(self.export_pending_events()),
+            CheckDivergence => Ok(self.check_divergence().await),
+            RemoveServer {
+                server,
+                requested_by,
+            } => self.remove_server(server, &requested_by),
         };
         tracing::debug!(?cmd.cmd, ?resp, "Handled management command");
         let _ = cmd.response.send(resp);
     }
 
     fn export_server_statistics(&self) -> String {
-        unimplemented!("server stats");
-        /*
-                let stats = ServerStatistics {
-                    connected_clients: self.connections.len(),
-                    event_stats: self.server.event_log().get_stats(),
-                };
+        let stats = ServerStatistics {
+            event_stats: self.event_log().get_stats(),
+        };
+
+        serde_json::to_string(&stats).expect("Failed to serialise statistics")
+    }
+
+    fn export_event_graph(&self) -> String {
+        let graph = self.event_log().dependency_graph();
+        serde_json::to_string(&graph).expect("Failed to serialise event graph")
+    }
 
-                serde_json::to_string(&stats).expect("Failed to serialise statistics")
-        */
+    fn export_pending_events(&self) -> String {
+        let pending = self.event_log().pending_report();
+        serde_json::to_string(&pending).expect("Failed to serialise pending events")
+    }
+
+    /// Emit a [`ServerQuit`](details::ServerQuit) for `name`, provided it isn't us and
+    /// has missed at least one ping
+    fn remove_server(
+        &self,
+        name: ServerName,
+        requested_by: &str,
+    ) -> Result<String, ServerManagementError> {
+        let now = utils::now();
+        let (id, epoch, last_ping) = {
+            let net = self.network();
+            let Some(server) = net.servers().find(|s| s.name() == &name) else {
+                return Err(ServerManagementError::NotFound(format!(
+                    "No such server {}",
+                    name
+                )));
+            };
+            (server.id(), server.epoch(), server.last_ping())
+        };
+
+        if id == self.my_id {
+            return Err(ServerManagementError::BadRequest(
+                "Refusing to remove this server".to_string(),
+            ));
+        }
+        if now - last_ping < MIN_UNRESPONSIVE_DURATION {
+            return Err(ServerManagementError::BadRequest(format!(
+                "{} was last heard from {}s ago",
+                name,
+                now - last_ping
+            )));
+        }
+
+        tracing::warn!(server=?name, ?requested_by, "Removing unresponsive server");
+
+        let entry = state::AuditLogEntry {
+            id: self.ids().next_audit_log_entry(),
+            timestamp: now,
+            category: state::AuditLogCategory::General,
+            source_id: None,
+            source_addr: None,
+            source_str: format!("{} (management)", requested_by),
+            action: "REMOVE_SERVER".to_string(),
+            target_id: None,
+            target_str: Some(name.to_string()),
+            target_duration: None,
+            reason: Some(format!("last ping {}s ago", now - last_ping)),
+        };
+        self.submit_event(entry.id, details::NewAuditLogEntry { entry });
+        self.submit_event(id, details::ServerQuit { epoch });
+
+        Ok(serde_json::json!({
+            "removed": name,
+            "epoch": epoch,
+            "last_ping": last_ping,
+        })
+        .to_string())
+    }
+
+    /// Ask every other server in the network for its state hash, and compare
+    /// each against our own
+    async fn check_divergence(&self) -> String {
+        let servers: Vec<_> = self
+            .network()
+            .servers()
+            .map(|s| *s.name())
+            .filter(|name| name != &self.name)
+            .collect();
+
+        let peers =
+            futures::future::join_all(servers.into_iter().map(|s| self.compare_state_with(s)))
+                .await;
+
+        let report = DivergenceReport {
+            local: self.local_state_hash(),
+            peers,
+        };
+        if report.has_divergence() {
+            tracing::warn!(?report, "Network state divergence detected");
+        }
+        serde_json::to_string(&report).expect("Failed to serialise divergence report")
+    }
+
+    fn local_state_hash(&self) -> StateHashReport {
+        let net = self.network();
+        StateHashReport {
+            server: self.name,
+            clock: net.clock().clone(),
+            hash: net.state_hash(),
+        }
+    }
+
+    /// Compare our state with that of `server`.
+    ///
+    /// States can only be compared once both servers have applied the same events,
+    /// which is rarely the case for a single pair of queries on a busy network, so
+    /// the comparison is retried a few times while the clocks differ.
+    async fn compare_state_with(&self, server: ServerName) -> PeerStateComparison {
+        let mut attempts = 0;
+        loop {
+            let response = self
+                .event_log
+                .send_remote_request(server, RemoteServerRequestType::StateHash)
+                .await;
+
+            let (clock, hash) = match response {
+                Ok(RemoteServerResponse::StateHash(clock, hash)) => (clock, hash),
+                Ok(other) => {
+                    return PeerStateComparison {
+                        server,
+                        clock: None,
+                        result: StateComparison::Unavailable {
+                            reason: format!("Unexpected response: {:?}", other),
+                        },
+                    }
+                }
+                Err(e) => {
+                    return PeerStateComparison {
+                        server,
+                        clock: None,
+                        result: StateComparison::Unavailable {
+                            reason: e.to_string(),
+                        },
+                    }
+                }
+            };
+
+            let remote = StateHashReport {
+                server,
+                clock: clock.clone(),
+                hash,
+            };
+            let result = compare_state_hashes(&self.local_state_hash(), &remote);
+
+            attempts += 1;
+            if attempts >= DIVERGENCE_CHECK_ATTEMPTS
+                || !matches!(result, StateComparison::ClockMismatch { .. })
+            {
+                return PeerStateComparison {
+                    server,
+                    clock: Some(clock),
+                    result,
+                };
+            }
+            tokio::time::sleep(DIVERGENCE_CHECK_RETRY_DELAY).await;
+        }
     }
 }
 
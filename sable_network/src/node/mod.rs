@@ -9,6 +9,7 @@ use crate::utils::*;
 use crate::policy::PolicyService;
 use crate::saveable::Saveable;
 
+use futures::FutureExt;
 use parking_lot::RwLockReadGuard;
 use tokio::{
     select,
@@ -20,10 +21,17 @@ use tokio::{
     time,
 };
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use parking_lot::RwLock;
 
+mod ban_expiry;
 mod history;
 mod pings;
 mod update_receiver;
@@ -33,6 +41,11 @@ pub use upgrade::NetworkNodeState;
 
 mod management;
 
+mod state_check;
+use state_check::StateHashTracker;
+
+mod resync;
+
 /// A network server.
 pub struct NetworkNode<Policy = crate::policy::StandardPolicyService>
 where
@@ -43,14 +56,19 @@ where
     version: String,
     net: RwLock<Arc<Network>>,
     event_log: Arc<ReplicatedEventLog>,
-    epoch: EpochId,
-    id_generator: ObjectIdGenerator,
+    // The epoch and ID generator are replaced if we have to resynchronise with the network
+    epoch: RwLock<EpochId>,
+    id_generator: RwLock<Arc<ObjectIdGenerator>>,
     // This needs to be a tokio mutex because we hold it for the duration of `run()`, which awaits a lot
     rpc_receiver: tokio::sync::Mutex<UnboundedReceiver<NetworkMessage>>,
     history_log: RwLock<NetworkHistoryLog>,
     subscriber: UnboundedSender<NetworkHistoryUpdate>,
     remote_server_commands: Option<UnboundedSender<RemoteServerRequest>>,
     policy_service: Policy,
+    state_hashes: parking_lot::Mutex<StateHashTracker>,
+    detached: AtomicBool,
+    detach_notify: tokio::sync::Notify,
+    abandoned_epochs: RwLock<Vec<EpochId>>,
 }
 
 impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
@@ -91,21 +109,32 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
             name,
             version: Self::build_version(),
             net: RwLock::new(Arc::new(net)),
-            epoch,
+            epoch: RwLock::new(epoch),
             event_log,
-            id_generator: ObjectIdGenerator::new(id, epoch),
+            id_generator: RwLock::new(Arc::new(ObjectIdGenerator::new(id, epoch))),
             rpc_receiver: Mutex::new(rpc_receiver),
             history_log: RwLock::new(NetworkHistoryLog::new()),
             subscriber,
             remote_server_commands,
             policy_service,
+            state_hashes: parking_lot::Mutex::new(StateHashTracker::new()),
+            detached: AtomicBool::new(false),
+            detach_notify: tokio::sync::Notify::new(),
+            abandoned_epochs: RwLock::new(Vec::new()),
         }
     }
 
     /// Submit a new event to be added to the log
+    ///
+    /// Events submitted while this node is [detached](Self::is_detached) are discarded,
+    /// so callers acting on behalf of clients should refuse to do so while detached.
     pub fn submit_event(&self, id: impl Into<ObjectId>, detail: impl Into<EventDetails>) {
         let id = id.into();
         let detail = detail.into();
+        if self.is_detached() {
+            tracing::warn!("Discarding event {:?} {:?} while detached", id, detail);
+            return;
+        }
         tracing::trace!("Submitting new event {:?} {:?}", id, detail);
         self.event_log.create_event(id, detail);
     }
@@ -119,13 +148,17 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
     ) {
         let id = id.into();
         let detail = detail.into();
+        if self.is_detached() {
+            tracing::warn!("Discarding event {:?} {:?} while detached", id, detail);
+            return;
+        }
         tracing::trace!("Submitting new event {:?} {:?}", id, detail);
         self.event_log.create_event_and(id, detail, f).await
     }
 
     /// Retrieve the [`ObjectIdGenerator`] used to generate object identifiers
-    pub fn ids(&self) -> &ObjectIdGenerator {
-        &self.id_generator
+    pub fn ids(&self) -> Arc<ObjectIdGenerator> {
+        Arc::clone(&self.id_generator.read())
     }
 
     /// Access the IRC network state
@@ -169,7 +202,7 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
 
     /// The server's epoch
     pub fn epoch(&self) -> EpochId {
-        self.epoch
+        *self.epoch.read()
     }
 
     /// The server's build version
@@ -207,34 +240,64 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
     fn apply_event(&self, event: Event) {
         tracing::trace!("Applying inbound event");
 
+        if let EventDetails::ServerQuit(quit) = &event.details {
+            if event.target == ObjectId::from(self.my_id) && quit.epoch == self.epoch() {
+                // The rest of the network thinks we're gone. Applying this would remove
+                // our own connections, which we need to know about to resynchronise.
+                self.detach("the network reported this server as having quit");
+                return;
+            }
+        }
+
         // We need to queue up the emitted updates and process them after `apply()` returns and we've released
         // the write lock on `net`. The handlers for various network updates require read access to `net`.
         let mut update_queue = crate::network::SavedUpdateReceiver::new();
 
-        Arc::make_mut(&mut *self.net.write())
-            .apply(&event, &update_queue)
-            .unwrap_or_else(|_| panic!("Event {:?} failed to apply", event));
+        {
+            let mut net = self.net.write();
+            Arc::make_mut(&mut *net)
+                .apply(&event, &update_queue)
+                .unwrap_or_else(|_| panic!("Event {:?} failed to apply", event));
+
+            self.state_hashes
+                .lock()
+                .record(net.clock().clone(), net.state_hash().combined());
+        }
+
+        if let EventDetails::ServerPing(ping) = &event.details {
+            self.check_remote_state_hash(event.id.server(), ping);
+        }
 
         update_queue.playback(self);
     }
 
-    #[tracing::instrument(skip_all)]
-    pub async fn run(
-        self: Arc<Self>,
-        mut shutdown_channel: broadcast::Receiver<ShutdownAction>,
-    ) -> ShutdownAction {
+    /// Announce this server, under its current epoch, to the network
+    fn announce_server(&self) {
         self.submit_event(
             self.my_id,
             details::NewServer {
-                epoch: self.epoch,
+                epoch: self.epoch(),
                 name: self.name,
                 ts: crate::utils::now(),
                 flags: self.server_flags(),
                 version: self.version().to_string(),
             },
         );
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn run(
+        self: Arc<Self>,
+        mut shutdown_channel: broadcast::Receiver<ShutdownAction>,
+    ) -> ShutdownAction {
+        self.announce_server();
 
         let mut check_ping_timer = time::interval(Duration::from_secs(60));
+        let mut check_ban_expiry_timer = time::interval(Duration::from_secs(30));
+
+        // Runs alongside the main loop while we resynchronise after becoming detached
+        let resync = futures::future::Fuse::terminated();
+        tokio::pin!(resync);
 
         let mut rpc_receiver = self.rpc_receiver.lock().await;
 
@@ -248,7 +311,12 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
                     match res {
                         Some(NetworkMessage::NewEvent(event)) =>
                         {
-                            self.apply_event(event);
+                            if event.id.server() == self.my_id && self.abandoned_epochs.read().contains(&event.id.epoch()) {
+                                // Emitted before we discarded our state to resynchronise
+                                tracing::debug!("Discarding stale event {:?}", event.id);
+                            } else {
+                                self.apply_event(event);
+                            }
                         },
                         Some(NetworkMessage::ImportNetworkState(new_net)) =>
                         {
@@ -257,6 +325,12 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
                             // we can't assign directly to something held by RwLock
                             let _ = std::mem::replace(&mut *self.net.write(), Arc::new(*new_net));
                         },
+                        Some(NetworkMessage::ExportNetworkState(_)) if self.is_detached() =>
+                        {
+                            // Our state can't be trusted until we've resynchronised; dropping the
+                            // channel tells the requester there's nothing to export
+                            tracing::debug!("Refusing state export while detached");
+                        },
                         Some(NetworkMessage::ExportNetworkState(channel)) =>
                         {
                             tracing::debug!("Server got state export request; sending");
@@ -265,6 +339,11 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
                             };
                             channel.send(Box::new(copied_net)).await.or_log("Error sending network state for export");
                         },
+                        Some(NetworkMessage::RemoteServerRequest(request)) if matches!(request.req, RemoteServerRequestType::StateHash) =>
+                        {
+                            let net = self.network();
+                            let _ = request.response.send(RemoteServerResponse::StateHash(net.clock().clone(), net.state_hash()));
+                        },
                         Some(NetworkMessage::RemoteServerRequest(request)) =>
                         {
                             if let Some(remote_server_commands) = self.remote_server_commands.as_ref()
@@ -285,6 +364,19 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
                     tracing::trace!("...from check_ping_timer");
                     self.check_pings();
                 },
+                _ = check_ban_expiry_timer.tick() =>
+                {
+                    tracing::trace!("...from check_ban_expiry_timer");
+                    self.check_ban_expiry();
+                },
+                _ = self.detach_notify.notified() =>
+                {
+                    resync.set(Arc::clone(&self).resync_to_network().fuse());
+                },
+                _ = &mut resync =>
+                {
+                    tracing::trace!("...from resync");
+                },
                 shutdown = shutdown_channel.recv() =>
                 {
                     match shutdown
@@ -313,12 +405,14 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
             }
         };
 
-        let net = self.net.read();
-        let me = net
-            .server(self.my_id)
-            .expect("Couldn't say I quit as I have no record of myself");
+        if !self.is_detached() {
+            let net = self.net.read();
+            let me = net
+                .server(self.my_id)
+                .expect("Couldn't say I quit as I have no record of myself");
 
-        self.submit_event(self.my_id, details::ServerQuit { epoch: me.epoch() });
+            self.submit_event(self.my_id, details::ServerQuit { epoch: me.epoch() });
+        }
 
         shutdown_action
     }
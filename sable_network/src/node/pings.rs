@@ -4,18 +4,42 @@ const PINGOUT_DURATION: i64 = 240;
 
 impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
     pub(super) fn check_pings(&self) {
+        if self.is_detached() {
+            return;
+        }
+
         let now = utils::now();
 
-        let ping_detail = details::ServerPing { ts: now };
+        let timed_out: Vec<_> = self
+            .net
+            .read()
+            .servers()
+            .filter(|s| now - s.last_ping() > PINGOUT_DURATION)
+            .map(|s| (s.id(), s.epoch()))
+            .collect();
+
+        // Servers we haven't heard from have usually gone away, and are removed below.
+        // Only if our own connections to most of the network are failing to reach their
+        // destinations is it more likely that we're the one who has been cut off, in
+        // which case resynchronise rather than declaring everyone else dead.
+        if !timed_out.is_empty() && self.event_log.cut_off_from_network() {
+            self.detach("unable to reach most other servers");
+            return;
+        }
+
+        let state_hash = {
+            let net = self.net.read();
+            (net.clock().clone(), net.state_hash().combined())
+        };
+
+        let ping_detail = details::ServerPing {
+            ts: now,
+            state_hash: Some(state_hash),
+        };
         self.submit_event(self.my_id, ping_detail);
 
-        for server in self.net.read().servers() {
-            if now - server.last_ping() > PINGOUT_DURATION {
-                let quit_detail = details::ServerQuit {
-                    epoch: server.epoch(),
-                };
-                self.submit_event(server.id(), quit_detail);
-            }
+        for (id, epoch) in timed_out {
+            self.submit_event(id, details::ServerQuit { epoch });
         }
     }
 }
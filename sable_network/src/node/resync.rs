@@ -0,0 +1,110 @@
+//! Recovery for a node which has become detached from the rest of the network
+
+use super::*;
+
+/// How many abandoned epochs to remember, for discarding our own stale events
+/// which are still circulating. Events are only gossiped until they expire, so
+/// only the most recent few can still be in flight.
+const MAX_ABANDONED_EPOCHS: usize = 16;
+
+impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
+    /// Whether this node has lost contact with the network and is waiting to
+    /// resynchronise. Events submitted while detached are discarded.
+    pub fn is_detached(&self) -> bool {
+        self.detached.load(Ordering::SeqCst)
+    }
+
+    /// Mark this node as detached from the network, and start resynchronising
+    pub(super) fn detach(&self, reason: &str) {
+        if self.detached.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        tracing::error!("Detached from the network ({}); resynchronising", reason);
+
+        if self
+            .subscriber
+            .send(NetworkHistoryUpdate::Detached)
+            .is_err()
+        {
+            tracing::error!("Couldn't notify subscriber of network detachment");
+        }
+        self.detach_notify.notify_one();
+    }
+
+    /// Discard our current state and rejoin the network under a new epoch.
+    ///
+    /// The rest of the network will already have removed, or will remove when
+    /// we announce ourselves again, everything associated with our previous
+    /// epoch. Local connections belonging to users with persistent sessions are
+    /// re-created under the new epoch; the subscriber is told which connections
+    /// were moved and which should be closed.
+    pub(super) async fn resync_to_network(self: Arc<Self>) {
+        let old_epoch = self.epoch();
+        let new_epoch = EpochId::new(utils::now()).max(old_epoch.next());
+
+        let new_net = self.event_log.resync_to_network(new_epoch).await;
+
+        let old_net = {
+            let mut net = self.net.write();
+            {
+                let mut abandoned_epochs = self.abandoned_epochs.write();
+                if abandoned_epochs.len() >= MAX_ABANDONED_EPOCHS {
+                    abandoned_epochs.remove(0);
+                }
+                abandoned_epochs.push(old_epoch);
+            }
+            *self.epoch.write() = new_epoch;
+            *self.id_generator.write() = Arc::new(ObjectIdGenerator::new(self.my_id, new_epoch));
+            *self.state_hashes.lock() = StateHashTracker::new();
+            std::mem::replace(&mut *net, Arc::new(*new_net))
+        };
+
+        self.detached.store(false, Ordering::SeqCst);
+        tracing::info!(?old_epoch, ?new_epoch, "Resynchronised with the network");
+
+        self.announce_server();
+
+        let net = self.network();
+        let mut moved_connections = Vec::new();
+        let mut new_connections = Vec::new();
+
+        for conn in old_net
+            .raw_user_connections()
+            .filter(|c| c.id.server() == self.my_id)
+        {
+            let persistent =
+                matches!(net.user(conn.user), Ok(user) if user.session_key().is_some());
+
+            if persistent {
+                let new_id = self.ids().next_user_connection();
+                moved_connections.push((conn.id, Some(new_id)));
+                new_connections.push((
+                    new_id,
+                    details::NewUserConnection {
+                        user: conn.user,
+                        hostname: conn.hostname,
+                        ip: conn.ip,
+                        connection_time: conn.connection_time,
+                    },
+                ));
+            } else {
+                moved_connections.push((conn.id, None));
+            }
+        }
+
+        // The subscriber needs to know about the new IDs before the events creating them
+        // are processed
+        if self
+            .subscriber
+            .send(NetworkHistoryUpdate::Resynchronised(moved_connections))
+            .is_err()
+        {
+            tracing::error!("Couldn't notify subscriber of network resynchronisation");
+        }
+
+        for (id, detail) in new_connections {
+            self.submit_event(id, detail);
+        }
+    }
+}
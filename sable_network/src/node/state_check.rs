@@ -0,0 +1,170 @@
+use super::*;
+
+use std::collections::{HashSet, VecDeque};
+
+/// How many recent local state hashes to retain for comparison against
+/// those reported by other servers
+const STATE_HASH_HISTORY: usize = 256;
+
+/// Recent network state hashes computed by this node, and the servers
+/// currently believed to have diverged from it
+pub(super) struct StateHashTracker {
+    history: VecDeque<(EventClock, u64)>,
+    diverged: HashSet<ServerId>,
+}
+
+/// The result of comparing a remote server's state hash with our own
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum StateHashCheck {
+    /// We held the same state at the same clock
+    Match,
+    /// We held a different state at the same clock
+    Mismatch { local: u64 },
+    /// We don't have a record of our state at the given clock
+    Unknown,
+}
+
+impl StateHashTracker {
+    pub fn new() -> Self {
+        Self {
+            history: VecDeque::with_capacity(STATE_HASH_HISTORY),
+            diverged: HashSet::new(),
+        }
+    }
+
+    /// Record the state hash held after applying an event
+    pub fn record(&mut self, clock: EventClock, hash: u64) {
+        if self.history.len() >= STATE_HASH_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back((clock, hash));
+    }
+
+    /// Compare a remote state hash against the one we held at the same clock
+    pub fn check(&self, clock: &EventClock, hash: u64) -> StateHashCheck {
+        match self.history.iter().rev().find(|(c, _)| c == clock) {
+            Some((_, local)) if *local == hash => StateHashCheck::Match,
+            Some((_, local)) => StateHashCheck::Mismatch { local: *local },
+            None => StateHashCheck::Unknown,
+        }
+    }
+
+    /// Note whether `server` is currently diverged. Returns true if this is a change.
+    pub fn set_diverged(&mut self, server: ServerId, diverged: bool) -> bool {
+        if diverged {
+            self.diverged.insert(server)
+        } else {
+            self.diverged.remove(&server)
+        }
+    }
+}
+
+impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
+    /// Check the state hash carried by another server's ping against our own
+    /// state at the same clock, and raise an alert if they differ
+    pub(super) fn check_remote_state_hash(&self, server: ServerId, ping: &details::ServerPing) {
+        if server == self.my_id {
+            return;
+        }
+        let Some((clock, hash)) = &ping.state_hash else {
+            return;
+        };
+
+        let mut tracker = self.state_hashes.lock();
+
+        match tracker.check(clock, *hash) {
+            StateHashCheck::Match => {
+                if tracker.set_diverged(server, false) {
+                    tracing::info!(?server, "Network state consistent with remote server again");
+                }
+            }
+            StateHashCheck::Mismatch { local } => {
+                // Only alert once until the states agree again
+                if !tracker.set_diverged(server, true) {
+                    return;
+                }
+                drop(tracker);
+
+                let server_name = self
+                    .net
+                    .read()
+                    .server(server)
+                    .map(|s| s.name().to_string())
+                    .unwrap_or_else(|_| format!("{:?}", server));
+
+                tracing::error!(
+                    ?server,
+                    ?clock,
+                    local_hash = local,
+                    remote_hash = hash,
+                    "Network state diverged from {}",
+                    server_name
+                );
+
+                let entry = state::AuditLogEntry {
+                    id: self.ids().next_audit_log_entry(),
+                    timestamp: utils::now(),
+                    category: state::AuditLogCategory::StateDivergence,
+                    source_id: None,
+                    source_addr: None,
+                    source_str: self.name.to_string(),
+                    action: "STATE_DIVERGENCE".to_string(),
+                    target_id: None,
+                    target_str: Some(server_name),
+                    target_duration: None,
+                    reason: Some(format!(
+                        "state hash {:016x} differs from local {:016x} at {:?}",
+                        hash, local, clock
+                    )),
+                };
+                self.submit_event(entry.id, details::NewAuditLogEntry { entry });
+            }
+            StateHashCheck::Unknown => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(entries: &[(i64, i64)]) -> EventClock {
+        let mut clock = EventClock::new();
+        for (server, local) in entries {
+            clock.update_with_id(EventId::new(
+                ServerId::new(*server),
+                EpochId::new(1),
+                *local,
+            ));
+        }
+        clock
+    }
+
+    #[test]
+    fn check_against_history() {
+        let mut tracker = StateHashTracker::new();
+        tracker.record(clock(&[(1, 1)]), 10);
+        tracker.record(clock(&[(1, 1), (2, 1)]), 20);
+
+        assert_eq!(tracker.check(&clock(&[(1, 1)]), 10), StateHashCheck::Match);
+        assert_eq!(
+            tracker.check(&clock(&[(1, 1), (2, 1)]), 21),
+            StateHashCheck::Mismatch { local: 20 }
+        );
+        assert_eq!(
+            tracker.check(&clock(&[(1, 2)]), 10),
+            StateHashCheck::Unknown
+        );
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut tracker = StateHashTracker::new();
+        for i in 0..(STATE_HASH_HISTORY as i64 + 1) {
+            tracker.record(clock(&[(1, i)]), i as u64);
+        }
+
+        assert_eq!(tracker.check(&clock(&[(1, 0)]), 0), StateHashCheck::Unknown);
+        assert_eq!(tracker.check(&clock(&[(1, 1)]), 1), StateHashCheck::Match);
+    }
+}
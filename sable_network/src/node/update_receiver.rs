@@ -290,10 +290,8 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
     ) -> HandleResult {
         tracing::trace!("Got server quit");
 
-        if detail.server.id == self.id() && detail.server.epoch == self.epoch() {
-            // The network thinks we're no longer alive. Shut down to avoid desyncs
-            panic!("Network thinks we're dead. Making it so");
-        }
+        // A quit for our own server and epoch never gets this far; `apply_event` detaches
+        // and resynchronises instead of applying it.
 
         self.sync_log()
             .disable_server(detail.server.name, detail.server.id, detail.server.epoch);
@@ -22,8 +22,9 @@ impl<Policy: PolicyService + Saveable> NetworkNode<Policy> {
             id: self.my_id,
             name: self.name,
             net: Arc::try_unwrap(self.net.into_inner()).unwrap(),
-            epoch: self.epoch,
-            id_generator: self.id_generator,
+            epoch: self.epoch.into_inner(),
+            id_generator: Arc::try_unwrap(self.id_generator.into_inner())
+                .unwrap_or_else(|_| panic!("Couldn't unwrap ID generator")),
             history_log: self.history_log.into_inner(),
             policy_state: self.policy_service.save(),
         }
@@ -45,14 +46,18 @@ impl<Policy: PolicyService + Saveable> NetworkNode<Policy> {
             name: state.name,
             version: Self::build_version(),
             net: RwLock::new(Arc::new(state.net)),
-            epoch: state.epoch,
-            id_generator: state.id_generator,
+            epoch: RwLock::new(state.epoch),
+            id_generator: RwLock::new(Arc::new(state.id_generator)),
             event_log,
             rpc_receiver: Mutex::new(rpc_receiver),
             history_log: RwLock::new(state.history_log),
             subscriber,
             policy_service: Policy::restore(state.policy_state),
             remote_server_commands,
+            state_hashes: parking_lot::Mutex::new(StateHashTracker::new()),
+            detached: AtomicBool::new(false),
+            detach_notify: tokio::sync::Notify::new(),
+            abandoned_epochs: RwLock::new(Vec::new()),
         })
     }
 }
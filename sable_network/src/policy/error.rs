@@ -28,6 +28,8 @@ pub enum ChannelPermissionError {
 pub enum UserPermissionError {
     /// User is not an oper
     NotOper,
+    /// User is an oper, but lacks the required privilege
+    MissingPrivilege(state::OperPrivilege),
     /// That user mode can't be set directly
     ReadOnlyUmode,
     /// User isn't logged in (and needs to be)
@@ -5,11 +5,16 @@ use super::*;
 /// Makes authentication decisions for users attempting to gain oper access
 #[delegatable_trait]
 pub trait OperAuthenticationService {
+    /// Determine whether `user` may oper up using the given oper block. As well as the
+    /// name and password, this checks any certificate fingerprint or account required by
+    /// the block; `tls_fingerprint` is that of the connection the attempt was made on.
     fn authenticate(
         &self,
         oper_config: &crate::network::config::OperConfig,
-        user: &str,
+        user: &wrapper::User,
+        oper_name: &str,
         pass: &str,
+        tls_fingerprint: Option<&str>,
     ) -> bool;
 }
 
@@ -22,6 +27,13 @@ pub trait OperPolicyService {
     /// Utility function to determine whether the given user is opered (regardless of privileges)
     fn require_oper(&self, user: &wrapper::User) -> PermissionResult;
 
+    /// Utility function to determine whether the given user is opered with the given privilege
+    fn require_privilege(
+        &self,
+        user: &wrapper::User,
+        privilege: state::OperPrivilege,
+    ) -> PermissionResult;
+
     /// Determine whether the given oper can set a kline
     fn can_set_kline(
         &self,
@@ -31,9 +43,31 @@ pub trait OperPolicyService {
         duration: i64,
     ) -> PermissionResult;
 
+    /// Determine whether the given oper can remove a kline
+    fn can_remove_kline(&self, oper: &wrapper::User) -> PermissionResult;
+
+    /// Determine whether the given oper can add, remove or list network bans
+    fn can_manage_bans(&self, oper: &wrapper::User) -> PermissionResult;
+
     /// Determine whether the given oper can disconnect the given target user
     fn can_kill(&self, oper: &wrapper::User, target: &wrapper::User) -> PermissionResult;
 
     /// Determine whether the given user can see detailed connection information about the target user
     fn can_see_connection_info(&self, source: &wrapper::User, target: &wrapper::User) -> bool;
+
+    /// Determine whether the given oper can override channel modes and access restrictions
+    fn can_override_channel_modes(&self, oper: &wrapper::User) -> PermissionResult;
+
+    /// Determine whether the given oper can use services administration commands
+    fn can_manage_services(&self, oper: &wrapper::User) -> PermissionResult;
+
+    /// Determine whether the given oper can query and subscribe to the audit log
+    fn can_view_audit_log(&self, oper: &wrapper::User) -> PermissionResult;
+
+    /// Determine whether the given oper can act on many users at once. The privilege
+    /// for each individual action (killing, banning) is checked separately.
+    fn can_take_mass_action(&self, oper: &wrapper::User) -> PermissionResult;
+
+    /// Determine whether the given oper can receive server notices of the given category
+    fn can_receive_snomask(&self, oper: &wrapper::User, snomask: SnomaskFlag) -> PermissionResult;
 }
@@ -1,5 +1,6 @@
 use super::*;
 use crate::network::config::OperConfig;
+use state::OperPrivilege;
 
 use UserPermissionError::*;
 
@@ -27,6 +28,19 @@ impl OperPolicyService for StandardOperPolicy {
         }
     }
 
+    fn require_privilege(
+        &self,
+        user: &wrapper::User,
+        privilege: OperPrivilege,
+    ) -> PermissionResult {
+        self.require_oper(user)?;
+        if user.has_oper_privilege(privilege) {
+            Ok(())
+        } else {
+            Err(PermissionError::User(MissingPrivilege(privilege)))
+        }
+    }
+
     fn can_set_kline(
         &self,
         oper: &wrapper::User,
@@ -34,20 +48,239 @@ impl OperPolicyService for StandardOperPolicy {
         _host: &Pattern,
         _duration: i64,
     ) -> PermissionResult {
-        self.require_oper(oper)
+        self.require_privilege(oper, OperPrivilege::Kline)
+    }
+
+    fn can_remove_kline(&self, oper: &wrapper::User) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::Kline)
+    }
+
+    fn can_manage_bans(&self, oper: &wrapper::User) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::Ban)
     }
 
     fn can_kill(&self, oper: &wrapper::User, _target: &wrapper::User) -> PermissionResult {
-        self.require_oper(oper)
+        self.require_privilege(oper, OperPrivilege::Kill)
     }
 
     fn can_see_connection_info(&self, source: &wrapper::User, target: &wrapper::User) -> bool {
-        self.require_oper(source).is_ok() || source.id() == target.id()
+        source.has_oper_privilege(OperPrivilege::SeeConnectionInfo) || source.id() == target.id()
+    }
+
+    fn can_override_channel_modes(&self, oper: &wrapper::User) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::OverrideChannelModes)
+    }
+
+    fn can_manage_services(&self, oper: &wrapper::User) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::ManageServices)
+    }
+
+    fn can_view_audit_log(&self, oper: &wrapper::User) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::ViewAuditLog)
+    }
+
+    fn can_take_mass_action(&self, oper: &wrapper::User) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::MassAction)
+    }
+
+    fn can_receive_snomask(&self, oper: &wrapper::User, snomask: SnomaskFlag) -> PermissionResult {
+        match snomask {
+            // These reveal the addresses of connecting and disconnecting clients
+            SnomaskFlag::Connect | SnomaskFlag::Exit | SnomaskFlag::Reject => {
+                self.require_privilege(oper, OperPrivilege::SeeConnectionInfo)
+            }
+            // These are taken from the audit log
+            SnomaskFlag::Kill | SnomaskFlag::Ban => {
+                self.require_privilege(oper, OperPrivilege::ViewAuditLog)
+            }
+            SnomaskFlag::Nick | SnomaskFlag::Flood => self.require_oper(oper),
+        }
     }
 }
 
 impl OperAuthenticationService for StandardOperPolicy {
-    fn authenticate(&self, oper_config: &OperConfig, user: &str, pass: &str) -> bool {
-        user == oper_config.name && unix::verify(pass, &oper_config.hash)
+    fn authenticate(
+        &self,
+        oper_config: &OperConfig,
+        user: &wrapper::User,
+        oper_name: &str,
+        pass: &str,
+        tls_fingerprint: Option<&str>,
+    ) -> bool {
+        if oper_name != oper_config.name || !unix::verify(pass, &oper_config.hash) {
+            return false;
+        }
+
+        if !oper_config.fingerprints.is_empty() {
+            let Some(fingerprint) = tls_fingerprint.map(normalise_fingerprint) else {
+                return false;
+            };
+            if !oper_config
+                .fingerprints
+                .iter()
+                .any(|fp| normalise_fingerprint(fp) == fingerprint)
+            {
+                return false;
+            }
+        }
+
+        if let Some(required_account) = &oper_config.account {
+            match user.account() {
+                Ok(Some(account)) if account.name() == *required_account => (),
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// Fingerprints may be configured with or without separating colons, and in either case
+fn normalise_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| *c != ':')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tests::fixtures::NetworkBuilder;
+    use std::str::FromStr;
+
+    fn nick(n: &str) -> Nickname {
+        Nickname::from_str(n).unwrap()
+    }
+
+    #[test]
+    fn privileges_limited_to_class() {
+        let mut builder = NetworkBuilder::new();
+        builder.add_user(nick("oper"));
+        builder.add_user(nick("target"));
+        let oper_id = builder.net.user_by_nick(&nick("oper")).unwrap().id();
+        builder.oper_up(oper_id, [OperPrivilege::Kill].into());
+
+        let policy = StandardOperPolicy::new();
+        let oper = builder.net.user_by_nick(&nick("oper")).unwrap();
+        let target = builder.net.user_by_nick(&nick("target")).unwrap();
+
+        assert!(policy.can_kill(&oper, &target).is_ok());
+        assert!(matches!(
+            policy.can_manage_services(&oper),
+            Err(PermissionError::User(MissingPrivilege(
+                OperPrivilege::ManageServices
+            )))
+        ));
+        assert!(policy.can_manage_bans(&oper).is_err());
+        assert!(!policy.can_see_connection_info(&oper, &target));
+    }
+
+    #[test]
+    fn non_opers_have_no_privileges() {
+        let mut builder = NetworkBuilder::new();
+        builder.add_user(nick("user"));
+        builder.add_user(nick("target"));
+
+        let policy = StandardOperPolicy::new();
+        let user = builder.net.user_by_nick(&nick("user")).unwrap();
+        let target = builder.net.user_by_nick(&nick("target")).unwrap();
+
+        assert!(matches!(
+            policy.can_manage_services(&user),
+            Err(PermissionError::User(NotOper))
+        ));
+        assert!(policy.can_kill(&user, &target).is_err());
+        assert!(policy.can_see_connection_info(&user, &user));
+        assert!(!policy.can_see_connection_info(&user, &target));
+    }
+
+    #[test]
+    fn classless_opers_have_every_privilege() {
+        let mut builder = NetworkBuilder::new();
+        builder.add_user(nick("oper"));
+        let oper_id = builder.net.user_by_nick(&nick("oper")).unwrap().id();
+        builder.oper_up(oper_id, OperPrivilege::all());
+
+        let policy = StandardOperPolicy::new();
+        let oper = builder.net.user_by_nick(&nick("oper")).unwrap();
+
+        for privilege in OperPrivilege::all() {
+            assert!(policy.require_privilege(&oper, privilege).is_ok());
+        }
+    }
+
+    fn oper_config(fingerprints: &[&str], account: Option<&str>) -> OperConfig {
+        OperConfig {
+            name: "oper".to_string(),
+            hash: pwhash::sha512_crypt::hash("secret").unwrap(),
+            class: None,
+            fingerprints: fingerprints.iter().map(|fp| fp.to_string()).collect(),
+            account: account.map(nick),
+        }
+    }
+
+    #[test]
+    fn fingerprints_normalised() {
+        assert_eq!(normalise_fingerprint("AB:cd:EF:01"), "abcdef01");
+        assert_eq!(normalise_fingerprint("abcdef01"), "abcdef01");
+    }
+
+    #[test]
+    fn password_checked() {
+        let mut builder = NetworkBuilder::new();
+        builder.add_user(nick("user"));
+        let user = builder.net.user_by_nick(&nick("user")).unwrap();
+
+        let policy = StandardOperPolicy::new();
+        let conf = oper_config(&[], None);
+
+        assert!(policy.authenticate(&conf, &user, "oper", "secret", None));
+        assert!(!policy.authenticate(&conf, &user, "oper", "wrong", None));
+        assert!(!policy.authenticate(&conf, &user, "other", "secret", None));
+    }
+
+    #[test]
+    fn fingerprint_required() {
+        let mut builder = NetworkBuilder::new();
+        builder.add_user(nick("user"));
+        let user = builder.net.user_by_nick(&nick("user")).unwrap();
+
+        let policy = StandardOperPolicy::new();
+        let conf = oper_config(&["AB:CD:EF", "012345"], None);
+
+        // No client certificate
+        assert!(!policy.authenticate(&conf, &user, "oper", "secret", None));
+        // Wrong certificate
+        assert!(!policy.authenticate(&conf, &user, "oper", "secret", Some("abcd")));
+        // Matches regardless of case and separators
+        assert!(policy.authenticate(&conf, &user, "oper", "secret", Some("abcdef")));
+        assert!(policy.authenticate(&conf, &user, "oper", "secret", Some("01:23:45")));
+    }
+
+    #[test]
+    fn account_required() {
+        let mut builder = NetworkBuilder::new();
+        builder.add_user(nick("user"));
+        let user_id = builder.net.user_by_nick(&nick("user")).unwrap().id();
+
+        let policy = StandardOperPolicy::new();
+        let conf = oper_config(&[], Some("operacct"));
+
+        // Not logged in
+        let user = builder.net.user_by_nick(&nick("user")).unwrap();
+        assert!(!policy.authenticate(&conf, &user, "oper", "secret", None));
+
+        // Logged in to the wrong account
+        let other = builder.add_account(nick("otheracct"));
+        builder.log_in(user_id, other);
+        let user = builder.net.user_by_nick(&nick("user")).unwrap();
+        assert!(!policy.authenticate(&conf, &user, "oper", "secret", None));
+
+        let right = builder.add_account(nick("operacct"));
+        builder.log_in(user_id, right);
+        let user = builder.net.user_by_nick(&nick("user")).unwrap();
+        assert!(policy.authenticate(&conf, &user, "oper", "secret", None));
     }
 }
@@ -14,11 +14,19 @@ impl StandardUserPolicy {
 }
 
 impl UserPolicyService for StandardUserPolicy {
-    fn can_set_umode(&self, _user: &wrapper::User, mode: UserModeFlag) -> PermissionResult {
+    fn can_set_umode(&self, user: &wrapper::User, mode: UserModeFlag) -> PermissionResult {
         match mode {
             UserModeFlag::Oper | UserModeFlag::TlsConnection => {
                 Err(PermissionError::User(ReadOnlyUmode))
             }
+            UserModeFlag::ServerNotices if !user.is_oper() => Err(PermissionError::User(NotOper)),
+            UserModeFlag::OperOverride
+                if !user.has_oper_privilege(state::OperPrivilege::OverrideChannelModes) =>
+            {
+                Err(PermissionError::User(MissingPrivilege(
+                    state::OperPrivilege::OverrideChannelModes,
+                )))
+            }
             _ => Ok(()),
         }
     }
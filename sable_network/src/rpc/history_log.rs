@@ -1,11 +1,18 @@
 /// A message indicating that something has been added to the network history log,
 /// which a history subscriber may want to do something about.
 use crate::history::LogEntryId;
-use crate::id::UserId;
+use crate::id::{UserConnectionId, UserId};
 
 #[derive(Debug)]
 pub enum NetworkHistoryUpdate {
     NewEntry(LogEntryId),
     NotifyUser(UserId, LogEntryId),
     NotifyUsers(Vec<UserId>, LogEntryId),
+    /// The node has lost contact with the network, and will discard any changes
+    /// until it has resynchronised
+    Detached,
+    /// The node has resynchronised with the network under a new epoch. Each of
+    /// this server's previous user connections is listed with the ID under which
+    /// it has been re-created, or `None` if its user no longer exists.
+    Resynchronised(Vec<(UserConnectionId, Option<UserConnectionId>)>),
 }
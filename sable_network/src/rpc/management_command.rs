@@ -1,9 +1,20 @@
+use crate::validated::ServerName;
+
 use tokio::sync::oneshot::Sender;
 
 /// A management command
 pub struct ServerManagementCommand {
     pub cmd: ServerManagementCommandType,
-    pub response: Sender<String>,
+    pub response: Sender<Result<String, ServerManagementError>>,
+}
+
+/// Reasons a management command can be refused
+#[derive(Debug)]
+pub enum ServerManagementError {
+    /// The object named by the command doesn't exist
+    NotFound(String),
+    /// The command can't be carried out in the current network state
+    BadRequest(String),
 }
 
 #[derive(Debug)]
@@ -14,4 +25,17 @@ pub enum ServerManagementCommandType {
     DumpNetwork,
     /// Dump event log (for debugging)
     DumpEvents,
+    /// Export the dependency graph of the events currently held in the log
+    EventGraph,
+    /// List events which are waiting for missing dependencies
+    PendingEvents,
+    /// Compare this node's network state against the other nodes in the network
+    CheckDivergence,
+    /// Emit a quit on behalf of a server which has stopped responding, so that its
+    /// users are removed from the network
+    RemoveServer {
+        server: ServerName,
+        /// The name of the management user making the request, for the audit log
+        requested_by: String,
+    },
 }
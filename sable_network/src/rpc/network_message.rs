@@ -1,6 +1,6 @@
 use crate::{
     id::*,
-    network::{event::*, state::ChannelAccessSet, Network},
+    network::{event::*, state::ChannelAccessSet, Network, NetworkStateHash},
     validated::*,
 };
 use tokio::sync::{mpsc::Sender, oneshot};
@@ -38,6 +38,8 @@ pub struct RemoteServerRequest {
 pub enum RemoteServerRequestType {
     /// Simple ping for communication tests
     Ping,
+    /// Request the target node's current network state hash
+    StateHash,
     /// User attempting registration
     /// Parameters: account name being registered, password provided
     RegisterUser(Nickname, String),
@@ -111,6 +113,8 @@ pub enum RemoteServerResponse {
     NoAccount,
     /// Channel isn't registered
     ChannelNotRegistered,
+    /// The responding node's current event clock and network state hash
+    StateHash(EventClock, NetworkStateHash),
     /// Operation failed, with error message
     Error(String),
 }
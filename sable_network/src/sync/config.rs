@@ -28,7 +28,7 @@ pub struct SyncConfig {
 }
 
 /// Configuration for this server's node in the gossip network
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NodeConfig {
     pub(crate) listen_addr: SocketAddr,
     pub(crate) cert_file: PathBuf,
@@ -39,6 +39,44 @@ pub struct NodeConfig {
 #[derive(Debug, Deserialize)]
 pub struct EventLogConfig {
     pub(crate) event_expiry: i64,
+    pub(crate) snapshot: Option<SnapshotConfig>,
+    pub(crate) wal: Option<WalConfig>,
+}
+
+/// Configuration for periodic local snapshots of network state
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// File to which snapshots are written, and from which they are loaded on startup
+    pub(crate) path: PathBuf,
+    /// Interval between snapshots, in seconds
+    pub(crate) interval: u64,
+}
+
+/// When to flush the write-ahead log to stable storage
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// Sync after every event written
+    Always,
+    /// Sync at most once every given number of seconds
+    Interval(u64),
+    /// Never explicitly sync; leave it to the operating system
+    Never,
+}
+
+impl Default for FsyncPolicy {
+    fn default() -> Self {
+        Self::Interval(1)
+    }
+}
+
+/// Configuration for the on-disk event log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WalConfig {
+    /// File to which events are appended
+    pub(crate) path: PathBuf,
+    #[serde(default)]
+    pub(crate) fsync: FsyncPolicy,
 }
 
 /// Errors that could happen when loading or processing a config
@@ -54,6 +92,22 @@ pub enum ConfigError {
     FormatError(String, PathBuf),
     #[error("Missing field {0} in {1}")]
     MissingField(String, PathBuf),
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+}
+
+impl EventLogConfig {
+    /// Check the event log settings for inconsistencies
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // Events in the on-disk log can only be recovered by replaying them on top of a
+        // snapshot; without one, they would be written but never read back
+        if self.wal.is_some() && self.snapshot.is_none() {
+            return Err(ConfigError::Invalid(
+                "event_log.wal requires event_log.snapshot to be configured".to_string(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl SyncConfig {
@@ -71,23 +125,24 @@ impl SyncConfig {
     /// Load and return the CA certificate for the network from the referenced
     /// file path
     pub fn load_ca_cert(&self) -> Result<Certificate, ConfigError> {
-        let ca_file =
-            File::open(&self.ca_file).map_err(|e| ConfigError::IoError(e, self.ca_file.clone()))?;
-        let mut ca_reader = BufReader::new(ca_file);
-        let ca_data = rustls_pemfile::certs(&mut ca_reader)
-            .map_err(|e| ConfigError::IoError(e, self.ca_file.clone()))?
-            .pop()
-            .ok_or_else(|| {
-                ConfigError::FormatError(
-                    "No certificate in CA file".to_string(),
-                    self.ca_file.clone(),
-                )
-            })?;
-
-        Ok(Certificate(ca_data))
+        load_ca_cert_file(&self.ca_file)
     }
 }
 
+/// Load the network CA certificate from the given file path
+pub(crate) fn load_ca_cert_file(path: &Path) -> Result<Certificate, ConfigError> {
+    let ca_file = File::open(path).map_err(|e| ConfigError::IoError(e, path.to_owned()))?;
+    let mut ca_reader = BufReader::new(ca_file);
+    let ca_data = rustls_pemfile::certs(&mut ca_reader)
+        .map_err(|e| ConfigError::IoError(e, path.to_owned()))?
+        .pop()
+        .ok_or_else(|| {
+            ConfigError::FormatError("No certificate in CA file".to_string(), path.to_owned())
+        })?;
+
+    Ok(Certificate(ca_data))
+}
+
 impl NodeConfig {
     /// Load the node configuration from a given file path
     pub fn load_file<P: AsRef<Path> + Copy>(filename: P) -> Result<Self, ConfigError> {
@@ -3,9 +3,11 @@
 use crate::network::event::*;
 use crate::prelude::*;
 
+use super::inspect::*;
+
 use chrono::prelude::*;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     ops::Bound::*,
 };
 use tokio::sync::mpsc::UnboundedSender;
@@ -120,6 +122,70 @@ impl EventLog {
         self.pending.values()
     }
 
+    /// Build the dependency graph of all events currently held in the log,
+    /// including those still pending
+    pub fn dependency_graph(&self) -> EventDependencyGraph {
+        let mut graph = EventDependencyGraph::default();
+        let mut missing = BTreeSet::new();
+
+        let held = self
+            .history
+            .values()
+            .flat_map(|m| m.values())
+            .map(|e| (e, false))
+            .chain(self.pending.values().map(|e| (e, true)));
+
+        for (event, pending) in held {
+            let mut dependencies: Vec<_> = event.clock.0.values().copied().collect();
+            dependencies.sort();
+
+            let held_dependencies = dependencies
+                .iter()
+                .filter(|id| self.get(id).is_some() || self.pending.contains_key(id))
+                .count();
+            if held_dependencies == 0 {
+                graph.roots.push(event.id);
+            }
+            if pending {
+                missing.extend(self.missing_ids_for(&event.clock));
+            }
+
+            graph.nodes.push(EventGraphNode {
+                id: event.id,
+                timestamp: event.timestamp,
+                target: event.target,
+                dependencies,
+                pending,
+            });
+        }
+
+        graph.nodes.sort_by_key(|n| n.id);
+        graph.roots.sort();
+        // Pending events can depend on each other; only report those we don't have at all
+        graph.missing = missing
+            .into_iter()
+            .filter(|id| !self.pending.contains_key(id))
+            .collect();
+        graph
+    }
+
+    /// List the events which are waiting for dependencies, along with the
+    /// dependencies that have not yet been received
+    pub fn pending_report(&self) -> Vec<PendingEventReport> {
+        let mut ret: Vec<_> = self
+            .pending
+            .values()
+            .map(|e| PendingEventReport {
+                id: e.id,
+                timestamp: e.timestamp,
+                clock: e.clock.clone(),
+                missing: self.missing_ids_for(&e.clock),
+            })
+            .collect();
+        ret.sort_by_key(|r| r.id);
+        ret
+    }
+
     /// Set the clock for this log.
     ///
     /// This should only be used when importing a serialized
@@ -129,6 +195,17 @@ impl EventLog {
         self.last_event_clock = new_clock;
     }
 
+    /// Discard all history, pending events and clock state, and generate
+    /// future event IDs from `idgen`.
+    ///
+    /// This is used when abandoning the local state to resynchronise with the network.
+    pub(crate) fn reset(&mut self, idgen: EventIdGenerator) {
+        self.history.clear();
+        self.pending.clear();
+        self.id_gen = idgen;
+        self.last_event_clock = EventClock::new();
+    }
+
     /// Add an event to the log.
     ///
     /// - If the event ID already exists within the log, do nothing.
@@ -181,7 +258,22 @@ impl EventLog {
     /// Remove events older than the provided timestamp
     pub fn prune_events_before(&mut self, threshold_timestamp: i64) {
         for (_server_id, server_events) in self.history.iter_mut() {
-            server_events.retain(|_id, event| event.timestamp < threshold_timestamp);
+            server_events.retain(|_id, event| event.timestamp >= threshold_timestamp);
+        }
+    }
+
+    /// Insert previously processed events into the log's history, without
+    /// updating the clock or notifying the event channel.
+    ///
+    /// This is used when recovering from local storage, to make events that
+    /// are already reflected in a restored [`Network`] state available to
+    /// other servers again.
+    pub(crate) fn restore_history(&mut self, events: impl IntoIterator<Item = Event>) {
+        for e in events {
+            self.history
+                .entry(e.id.server())
+                .or_default()
+                .insert(e.id, e);
         }
     }
 
@@ -0,0 +1,126 @@
+//! Tools for inspecting the causal structure of the event log, and for
+//! detecting divergence between the network states held by different nodes
+
+use crate::prelude::*;
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// A single event in an [`EventDependencyGraph`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventGraphNode {
+    pub id: EventId,
+    pub timestamp: i64,
+    pub target: ObjectId,
+    /// The events this event directly depends on, taken from its clock
+    pub dependencies: Vec<EventId>,
+    /// Whether this event is still waiting for some of its dependencies
+    pub pending: bool,
+}
+
+/// The dependency graph of the events currently held in an [`EventLog`].
+///
+/// Edges run from each event to the events named in its clock. Because each
+/// event's clock is the origin server's log clock at the time it was emitted,
+/// the graph is acyclic.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventDependencyGraph {
+    pub nodes: Vec<EventGraphNode>,
+    /// Events in the graph none of whose dependencies are held in the log,
+    /// either because they have been pruned or because there are none
+    pub roots: Vec<EventId>,
+    /// Dependencies of pending events which are not held in the log
+    pub missing: Vec<EventId>,
+}
+
+/// An event which is waiting for its dependencies to arrive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingEventReport {
+    pub id: EventId,
+    pub timestamp: i64,
+    pub clock: EventClock,
+    pub missing: Vec<EventId>,
+}
+
+/// The state hash reported by one node, together with the event clock at
+/// which it was computed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateHashReport {
+    pub server: ServerName,
+    pub clock: EventClock,
+    pub hash: NetworkStateHash,
+}
+
+/// How one node's event clock relates to another's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockRelation {
+    /// The other node has seen events that this one has not
+    Behind,
+    /// This node has seen events that the other has not
+    Ahead,
+    /// Each node has seen events that the other has not
+    Concurrent,
+}
+
+/// The outcome of comparing two nodes' network states
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateComparison {
+    /// Both nodes had applied the same events and hold the same state
+    Match,
+    /// Both nodes had applied the same events, but their states differ
+    Diverged { categories: Vec<StateHashCategory> },
+    /// The nodes had applied different sets of events, so their states can't
+    /// be compared. The relation is that of the remote node to the local one.
+    ClockMismatch { relation: ClockRelation },
+    /// The remote node couldn't be queried
+    Unavailable { reason: String },
+}
+
+/// The result of comparing one remote node's state with the local one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerStateComparison {
+    pub server: ServerName,
+    pub clock: Option<EventClock>,
+    pub result: StateComparison,
+}
+
+/// A structured report of state divergence across the network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DivergenceReport {
+    pub local: StateHashReport,
+    pub peers: Vec<PeerStateComparison>,
+}
+
+impl DivergenceReport {
+    /// Whether any node was found to hold a different state at the same clock
+    pub fn has_divergence(&self) -> bool {
+        self.peers
+            .iter()
+            .any(|p| matches!(p.result, StateComparison::Diverged { .. }))
+    }
+}
+
+/// Compare a remote node's reported state against the local one
+pub fn compare_state_hashes(local: &StateHashReport, remote: &StateHashReport) -> StateComparison {
+    match remote.clock.partial_cmp(&local.clock) {
+        Some(Ordering::Equal) => {
+            let categories = local.hash.differing_categories(&remote.hash);
+            if categories.is_empty() {
+                StateComparison::Match
+            } else {
+                StateComparison::Diverged { categories }
+            }
+        }
+        Some(Ordering::Less) => StateComparison::ClockMismatch {
+            relation: ClockRelation::Behind,
+        },
+        Some(Ordering::Greater) => StateComparison::ClockMismatch {
+            relation: ClockRelation::Ahead,
+        },
+        None => StateComparison::ClockMismatch {
+            relation: ClockRelation::Concurrent,
+        },
+    }
+}
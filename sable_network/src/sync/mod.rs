@@ -3,16 +3,22 @@
 
 mod config;
 mod eventlog;
+pub mod inspect;
 mod message;
 mod network;
+mod snapshot;
+mod wal;
 
 mod replicated_log;
 
 pub use config::ConfigError;
 pub use config::EventLogConfig;
+pub use config::FsyncPolicy;
 pub use config::NodeConfig;
 pub use config::PeerConfig;
+pub use config::SnapshotConfig;
 pub use config::SyncConfig;
+pub use config::WalConfig;
 pub use eventlog::EventLog;
 pub use eventlog::EventLogState;
 pub use eventlog::EventLogStats;
@@ -21,7 +27,12 @@ pub use message::MessageDetail;
 pub use message::Request;
 pub use network::GossipNetwork;
 pub use network::GossipNetworkState;
+pub use network::GossipTlsConfig;
 pub use network::NetworkError;
+pub use snapshot::NetworkSnapshot;
+pub use snapshot::SnapshotError;
+pub use wal::EventWal;
+pub use wal::WalWriter;
 
 pub use replicated_log::EventLogSaveError;
 pub use replicated_log::ReplicatedEventLog;
@@ -1,5 +1,6 @@
 //! Networking code for the sync protocol
 
+use super::config::load_ca_cert_file;
 use super::message::Message;
 use super::*;
 use crate::validated::{ServerName, Validated};
@@ -8,9 +9,10 @@ use futures::future;
 use std::{
     convert::TryInto,
     net::{IpAddr, SocketAddr, SocketAddrV6},
-    sync::atomic::{AtomicBool, Ordering},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicI64, Ordering},
     sync::Arc,
-    sync::Mutex,
+    sync::{Mutex, RwLock},
 };
 use tokio::{
     io,
@@ -36,25 +38,39 @@ use tracing::instrument;
 /// An interface to the gossip network used to synchronise state.
 pub struct GossipNetwork {
     fanout: usize,
-    tls_client_config: Arc<ClientConfig>,
+    tls_client_config: RwLock<Arc<ClientConfig>>,
+    ca_file: PathBuf,
+    node_config: NodeConfig,
     shutdown_send: Mutex<Option<oneshot::Sender<()>>>,
     task_state: Arc<NetworkTaskState>,
     me: PeerConfig,
 }
 
+/// TLS configuration for the gossip network, built by [`GossipNetwork::prepare_tls`]
+/// and not yet in use.
+pub struct GossipTlsConfig {
+    client_config: Arc<ClientConfig>,
+    server_config: Arc<ServerConfig>,
+}
+
 /// State that's shared between the listener task and client code
 ///
 /// Note that all additions to this struct must keep it `Send` and `Sync`.
 struct NetworkTaskState {
     peers: Vec<Peer>,
     listen_addr: SocketAddr,
-    tls_server_config: Arc<ServerConfig>,
+    tls_server_config: RwLock<Arc<ServerConfig>>,
     message_sender: UnboundedSender<Request>,
 }
 
 struct Peer {
     conf: PeerConfig,
     enabled: AtomicBool,
+    /// When an outbound connection last reached this peer's host, even if the
+    /// connection was refused
+    last_reached: AtomicI64,
+    /// When an outbound connection last failed to reach this peer's host at all
+    last_unreachable: AtomicI64,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -99,28 +115,9 @@ impl GossipNetwork {
         node_config: NodeConfig,
         message_sender: UnboundedSender<Request>,
     ) -> Self {
-        let ca_cert = net_config.load_ca_cert().expect("Error loading CA");
-
-        let (client_cert, client_key) = node_config
-            .load_cert_and_keys()
-            .expect("Error loading client cert");
-
-        let mut root_store = rustls::RootCertStore::empty();
-        root_store
-            .add(&ca_cert)
-            .expect("Error adding certificate to store");
-
-        let client_config = ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(root_store.clone())
-            .with_single_cert(client_cert.clone(), client_key.clone())
-            .expect("Bad TLS client config");
-
-        let server_config = ServerConfig::builder()
-            .with_safe_defaults()
-            .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(root_store.clone()))
-            .with_single_cert(client_cert, client_key)
-            .expect("Bad TLS server config");
+        let (client_config, server_config) =
+            Self::build_tls_configs(&net_config.ca_file, &node_config)
+                .expect("Error loading TLS configuration");
 
         let mut peers = net_config.peers;
         let my_index = peers
@@ -131,7 +128,8 @@ impl GossipNetwork {
 
         Self {
             fanout: net_config.fanout,
-            tls_client_config: Arc::new(client_config),
+            tls_client_config: RwLock::new(client_config),
+            ca_file: net_config.ca_file,
             shutdown_send: Mutex::new(None),
             me,
             task_state: Arc::new(NetworkTaskState {
@@ -141,14 +139,70 @@ impl GossipNetwork {
                     .map(|c| Peer {
                         conf: c,
                         enabled: AtomicBool::new(false),
+                        last_reached: AtomicI64::new(0),
+                        last_unreachable: AtomicI64::new(0),
                     })
                     .collect(),
-                tls_server_config: Arc::new(server_config),
+                tls_server_config: RwLock::new(server_config),
                 message_sender,
             }),
+            node_config,
         }
     }
 
+    fn build_tls_configs(
+        ca_file: &Path,
+        node_config: &NodeConfig,
+    ) -> Result<(Arc<ClientConfig>, Arc<ServerConfig>), ConfigError> {
+        let ca_cert = load_ca_cert_file(ca_file)?;
+
+        let (client_cert, client_key) = node_config.load_cert_and_keys()?;
+
+        let mut root_store = rustls::RootCertStore::empty();
+        root_store
+            .add(&ca_cert)
+            .map_err(|e| ConfigError::FormatError(e.to_string(), ca_file.to_owned()))?;
+
+        let bad_cert = |e: rustls::Error| {
+            ConfigError::FormatError(e.to_string(), node_config.cert_file.clone())
+        };
+
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_store.clone())
+            .with_single_cert(client_cert.clone(), client_key.clone())
+            .map_err(bad_cert)?;
+
+        let server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(root_store))
+            .with_single_cert(client_cert, client_key)
+            .map_err(bad_cert)?;
+
+        Ok((Arc::new(client_config), Arc::new(server_config)))
+    }
+
+    /// Re-read the CA, certificate and key files, and build the configuration for
+    /// connections to or from other servers, without applying it. Pass the result
+    /// to [`apply_tls`](Self::apply_tls) to use it.
+    pub fn prepare_tls(&self) -> Result<GossipTlsConfig, ConfigError> {
+        let (client_config, server_config) =
+            Self::build_tls_configs(&self.ca_file, &self.node_config)?;
+        Ok(GossipTlsConfig {
+            client_config,
+            server_config,
+        })
+    }
+
+    /// Use a configuration built by [`prepare_tls`](Self::prepare_tls) for any new
+    /// connections to or from other servers.
+    pub fn apply_tls(&self, config: GossipTlsConfig) {
+        *self.tls_client_config.write().unwrap() = config.client_config;
+        *self.task_state.tls_server_config.write().unwrap() = config.server_config;
+
+        tracing::info!("Reloaded gossip network TLS configuration");
+    }
+
     pub fn restore(
         state: GossipNetworkState,
         net_config: SyncConfig,
@@ -195,6 +249,7 @@ impl GossipNetwork {
         for p in self.task_state.peers.iter() {
             if &p.conf.name == name {
                 p.enabled.store(true, Ordering::SeqCst);
+                p.last_unreachable.store(0, Ordering::SeqCst);
             }
         }
     }
@@ -209,6 +264,42 @@ impl GossipNetwork {
         }
     }
 
+    /// Whether the most recent outbound connections to a majority of the enabled peers
+    /// failed without reaching the peer's host. A refused connection shows that the
+    /// network path is working and only that peer is down, so this is evidence that
+    /// it is this server that has been cut off from the rest of the network.
+    pub fn cut_off_from_network(&self) -> bool {
+        let (enabled, unreachable) = self
+            .task_state
+            .peers
+            .iter()
+            .filter(|p| p.enabled.load(Ordering::SeqCst))
+            .fold((0, 0), |(enabled, unreachable), p| {
+                let is_unreachable = p.last_unreachable.load(Ordering::SeqCst)
+                    > p.last_reached.load(Ordering::SeqCst);
+                (enabled + 1, unreachable + usize::from(is_unreachable))
+            });
+
+        enabled > 0 && unreachable * 2 > enabled
+    }
+
+    fn note_connect_result(&self, name: &ServerName, result: &Result<TcpStream, NetworkError>) {
+        let Some(peer) = self.task_state.peers.iter().find(|p| &p.conf.name == name) else {
+            return;
+        };
+        let reached = match result {
+            Ok(_) => true,
+            Err(NetworkError::Io(e)) => e.kind() == io::ErrorKind::ConnectionRefused,
+            Err(_) => false,
+        };
+        let now = crate::utils::now();
+        if reached {
+            peer.last_reached.store(now, Ordering::SeqCst);
+        } else {
+            peer.last_unreachable.store(now, Ordering::SeqCst);
+        }
+    }
+
     #[instrument(skip_all)]
     pub fn choose_peer(&self) -> Option<&PeerConfig> {
         let ret = self
@@ -372,8 +463,10 @@ impl GossipNetwork {
     ) -> Result<JoinHandle<NetworkResult>, NetworkError> {
         let mut local_addr = self.task_state.listen_addr;
         local_addr.set_port(0);
-        let connector = TlsConnector::from(Arc::clone(&self.tls_client_config));
-        let conn = Self::connect(&local_addr, &peer.address).await?;
+        let connector = TlsConnector::from(Arc::clone(&self.tls_client_config.read().unwrap()));
+        let conn = Self::connect(&local_addr, &peer.address).await;
+        self.note_connect_result(&peer.name, &conn);
+        let conn = conn?;
         let server_name = (peer.name.value() as &str)
             .try_into()
             .expect("Invalid server name");
@@ -424,15 +517,13 @@ impl NetworkTaskState {
         listener: TcpListener,
         mut shutdown: oneshot::Receiver<()>,
     ) -> Result<(), io::Error> {
-        let tls_acceptor = TlsAcceptor::from(Arc::clone(&self.tls_server_config));
-
         loop {
             select! {
                 res = listener.accept() =>
                 {
                     if let Ok((conn, _)) = res
                     {
-                        let tls_acceptor = tls_acceptor.clone();
+                        let tls_acceptor = TlsAcceptor::from(Arc::clone(&self.tls_server_config.read().unwrap()));
                         let sender = self.message_sender.clone();
                         let self_copy = Arc::clone(&self);
                         tokio::spawn(async move {
@@ -20,6 +20,7 @@ use thiserror::Error;
 
 use super::message::TargetedMessage;
 use super::network::NetworkResult;
+use super::snapshot::ResumePoint;
 
 #[derive(Debug, Error)]
 pub enum EventLogSaveError {
@@ -39,6 +40,10 @@ pub struct ReplicatedEventLogState {
     server_tombstones: HashMap<ServerId, (ServerName, EpochId)>,
     network_state: GossipNetworkState,
     event_expiry: i64,
+    #[serde(default)]
+    snapshot_config: Option<SnapshotConfig>,
+    #[serde(default)]
+    wal_config: Option<WalConfig>,
 }
 
 /// A replicated event log.
@@ -53,9 +58,10 @@ pub struct ReplicatedEventLog {
 }
 
 struct SharedState {
-    server: (ServerId, EpochId),
+    server: RwLock<(ServerId, EpochId)>,
     server_tombstones: RwLock<HashMap<ServerId, (ServerName, EpochId)>>,
     log: RwLock<EventLog>,
+    snapshot_config: Option<SnapshotConfig>,
 }
 
 struct TaskState {
@@ -66,6 +72,7 @@ struct TaskState {
     server_send: UnboundedSender<NetworkMessage>,
     shared_state: Arc<SharedState>,
     event_expiry: i64,
+    wal: Option<WalWriter>,
 }
 
 #[derive(Debug)]
@@ -77,6 +84,13 @@ enum EventLogMessage {
         Option<Arc<Notify>>,
     ),
     TargetedMessage(TargetedMessage, oneshot::Sender<RemoteServerResponse>),
+    DiscardLocalState,
+}
+
+impl SharedState {
+    fn server(&self) -> (ServerId, EpochId) {
+        *self.server.read().expect("server id lock is poisoned?")
+    }
 }
 
 impl ReplicatedEventLog {
@@ -116,12 +130,13 @@ impl ReplicatedEventLog {
         ));
 
         let shared_state = Arc::new(SharedState {
-            server: (server_id, epoch),
+            server: RwLock::new((server_id, epoch)),
             server_tombstones: RwLock::new(HashMap::new()),
             log: RwLock::new(EventLog::new(
                 EventIdGenerator::new(server_id, epoch, 0),
                 Some(log_send),
             )),
+            snapshot_config: eventlog_config.snapshot,
         });
 
         let task_state = Arc::new(Mutex::new(TaskState {
@@ -132,6 +147,7 @@ impl ReplicatedEventLog {
             server_send,
             shared_state: Arc::clone(&shared_state),
             event_expiry: eventlog_config.event_expiry,
+            wal: Self::open_wal(eventlog_config.wal),
         }));
 
         Self {
@@ -166,9 +182,10 @@ impl ReplicatedEventLog {
         ));
 
         let shared_state = Arc::new(SharedState {
-            server: state.server,
+            server: RwLock::new(state.server),
             server_tombstones: RwLock::new(state.server_tombstones),
             log: RwLock::new(EventLog::restore(state.log_state, Some(log_send))),
+            snapshot_config: state.snapshot_config,
         });
 
         let task_state = Arc::new(Mutex::new(TaskState {
@@ -179,6 +196,7 @@ impl ReplicatedEventLog {
             server_send,
             shared_state: Arc::clone(&shared_state),
             event_expiry: state.event_expiry,
+            wal: Self::open_wal(state.wal_config),
         }));
 
         Self {
@@ -189,6 +207,17 @@ impl ReplicatedEventLog {
         }
     }
 
+    fn open_wal(config: Option<WalConfig>) -> Option<WalWriter> {
+        let config = config?;
+        match WalWriter::open(config) {
+            Ok(wal) => Some(wal),
+            Err(e) => {
+                tracing::error!("Couldn't open event log file; continuing without it: {}", e);
+                None
+            }
+        }
+    }
+
     /// Create and propagate a new event.
     ///
     /// Arguments are the target object ID, and the event detail.
@@ -267,6 +296,24 @@ impl ReplicatedEventLog {
         self.net.enable_peer(&name);
     }
 
+    /// Whether our recent attempts to contact most of the other servers have failed
+    /// to reach them at all. See [`GossipNetwork::cut_off_from_network`].
+    pub fn cut_off_from_network(&self) -> bool {
+        self.net.cut_off_from_network()
+    }
+
+    /// Re-read the TLS certificates used to talk to other servers, without
+    /// applying them. See [`GossipNetwork::prepare_tls`].
+    pub fn prepare_tls(&self) -> Result<GossipTlsConfig, ConfigError> {
+        self.net.prepare_tls()
+    }
+
+    /// Use TLS certificates loaded by [`prepare_tls`](Self::prepare_tls) to talk to
+    /// other servers. Connections already established are unaffected.
+    pub fn apply_tls(&self, config: GossipTlsConfig) {
+        self.net.apply_tls(config)
+    }
+
     /// Send a request to another server in the network, and wait for the response
     pub async fn send_remote_request(
         &self,
@@ -294,12 +341,60 @@ impl ReplicatedEventLog {
 
     /// Run and wait for the initial synchronisation to the network.
     ///
-    /// This will choose a peer from the provided network configuration,
+    /// If a local snapshot is configured and recent enough, this will first
+    /// attempt to resume from it, requesting only the events that have been
+    /// emitted since the snapshot was taken.
+    ///
+    /// Otherwise, this will choose a peer from the provided network configuration,
     /// request a copy of the current network state from that peer, return
     /// it, and update the log's event clock to the current value from
     /// the imported state.
     #[tracing::instrument(skip(self))]
     pub async fn sync_to_network(&self) -> Box<crate::network::Network> {
+        if let Some(net) = self.resume_from_snapshot().await {
+            return net;
+        }
+
+        self.fetch_network_state().await
+    }
+
+    /// Discard the local event log and resynchronise with the network under a
+    /// new epoch.
+    ///
+    /// This is used to recover after this server has been detached from the rest
+    /// of the network. Any events we emitted under the previous epoch are
+    /// abandoned, along with any local snapshot or on-disk event log, and the
+    /// current network state is requested from a peer as for a fresh start.
+    #[tracing::instrument(skip(self))]
+    pub async fn resync_to_network(&self, epoch: EpochId) -> Box<crate::network::Network> {
+        let server_id = self.shared_state.server().0;
+
+        *self
+            .shared_state
+            .server
+            .write()
+            .expect("server id lock is poisoned?") = (server_id, epoch);
+        self.shared_state
+            .server_tombstones
+            .write()
+            .expect("tombstone lock is poisoned?")
+            .clear();
+        self.shared_state
+            .log
+            .write()
+            .expect("event log lock is poisoned?")
+            .reset(EventIdGenerator::new(server_id, epoch, 0));
+
+        self.new_event_send
+            .send(EventLogMessage::DiscardLocalState)
+            .expect("Couldn't send to event log task");
+
+        self.fetch_network_state().await
+    }
+
+    /// Request a copy of the current network state from a peer, and update the
+    /// log's clock to match it
+    async fn fetch_network_state(&self) -> Box<crate::network::Network> {
         let net = 'outer: loop {
             let (send, mut recv) = unbounded_channel();
             let handle = self.start_sync_to_network(send).await;
@@ -333,6 +428,116 @@ impl ReplicatedEventLog {
         net
     }
 
+    /// Attempt to load the configured network snapshot and replay any events
+    /// recorded in the local event log since it was taken, then fetch the events
+    /// emitted elsewhere since then.
+    ///
+    /// If no peer can be reached, as when the whole network is restarting, the
+    /// local state is used as it is. Returns `None` if there is no usable snapshot,
+    /// or if the peer no longer holds every event needed to bring it up to date,
+    /// in which case the caller should fall back to a full state export.
+    async fn resume_from_snapshot(&self) -> Option<Box<crate::network::Network>> {
+        let snapshot_config = self.shared_state.snapshot_config.as_ref()?;
+
+        let (event_expiry, wal_path) = {
+            let task_state = self.task_state.lock().await;
+            (
+                task_state.event_expiry,
+                task_state.wal.as_ref().map(|wal| wal.config().path.clone()),
+            )
+        };
+
+        let mut resume_point = ResumePoint::load(
+            &snapshot_config.path,
+            wal_path.as_deref(),
+            self.shared_state.server(),
+            event_expiry,
+            crate::utils::now(),
+        )?;
+        tracing::info!(
+            "Resuming from network snapshot at {:?} with {} local events",
+            resume_point.snapshot.clock,
+            resume_point.local_events.len(),
+        );
+
+        let newer_events = match self.fetch_events_since(resume_point.clock().clone()).await {
+            Some(events) => {
+                // Check that the events we received are complete before committing to
+                // them; if the peer has already pruned some of them, we'll be left with
+                // pending events whose dependencies can't be satisfied.
+                if !resume_point.catch_up(&events) {
+                    tracing::info!(
+                        "Peer no longer holds all events since snapshot; falling back to full sync"
+                    );
+                    return None;
+                }
+                events
+            }
+            None => {
+                tracing::warn!("No peer available; resuming from local state only");
+                Vec::new()
+            }
+        };
+
+        let net = {
+            let mut log = self
+                .shared_state
+                .log
+                .write()
+                .expect("event log lock is poisoned?");
+
+            // The events will be passed to the node for processing, in dependency
+            // order, once the sync task starts
+            resume_point.restore_into(&mut log, newer_events)
+        };
+
+        for server in net.servers() {
+            self.enable_server(*server.name(), server.id());
+        }
+
+        Some(net)
+    }
+
+    /// Ask a peer for every event not included in `clock`. Returns `None` if there
+    /// is no peer to ask, or it couldn't be reached.
+    async fn fetch_events_since(&self, clock: EventClock) -> Option<Vec<Event>> {
+        let peer = self.net.choose_any_peer()?;
+        tracing::info!("Requesting events since snapshot from {:?}", peer);
+
+        let (send, mut recv) = unbounded_channel();
+        let msg = Message {
+            source_server: self.shared_state.server(),
+            content: MessageDetail::SyncRequest(clock),
+        };
+        let handle = match self.net.send_and_process(peer, msg, send).await {
+            Ok(handle) => handle,
+            Err(e) => {
+                tracing::warn!("Couldn't request events since snapshot: {}", e);
+                return None;
+            }
+        };
+
+        let mut events = None;
+        while let Some(req) = recv.recv().await {
+            if let MessageDetail::BulkEvents(new_events) = req.message.content {
+                let _ = req
+                    .response
+                    .send(Message {
+                        source_server: self.shared_state.server(),
+                        content: MessageDetail::Done,
+                    })
+                    .await;
+                events = Some(new_events);
+                break;
+            }
+        }
+        drop(recv);
+        if let Err(e) = handle.await {
+            tracing::warn!("Error in snapshot sync connection: {}", e);
+        }
+        events
+    }
+
     #[allow(clippy::await_holding_refcell_ref)] // don't care about 'attempts' being borrowed too
                                                 // long, the closure can't be running more than
                                                 // once at a time.
@@ -365,7 +570,7 @@ impl ReplicatedEventLog {
                 tracing::debug!("Requesting network state from {:?}", peer);
             }
             let msg = Message {
-                source_server: self.shared_state.server,
+                source_server: self.shared_state.server(),
                 content: MessageDetail::GetNetworkState,
             };
             Ok(self.net.send_and_process(peer, msg, sender.clone()).await?)
@@ -389,10 +594,13 @@ impl ReplicatedEventLog {
         // First, extract the task state from the mutex. If this fails (because there's
         // another reference to the Arc), it'll be because the sync task is still running,
         // so the Err return can indicate that.
-        let task_state = Arc::try_unwrap(self.task_state)
+        let mut task_state = Arc::try_unwrap(self.task_state)
             .map_err(|_| EventLogSaveError::TaskStillRunning)?
             .into_inner();
 
+        // Make sure everything we've seen is on disk before handing over
+        let wal_config = task_state.wal.take().map(WalWriter::close);
+
         // Now drop the task_state's reference to the shared_state, so that we hold the only one
         drop(task_state.shared_state);
         // ...and unwrap it.
@@ -405,11 +613,13 @@ impl ReplicatedEventLog {
         let log = shared_state.log.into_inner().unwrap();
 
         Ok(ReplicatedEventLogState {
-            server: shared_state.server,
+            server: shared_state.server.into_inner().unwrap(),
             log_state: log.save_state(),
             server_tombstones,
             network_state: self.net.save_state(),
             event_expiry: task_state.event_expiry,
+            snapshot_config: shared_state.snapshot_config,
+            wal_config,
         })
     }
 }
@@ -425,6 +635,18 @@ impl TaskState {
 
         let mut log_prune_timer = tokio::time::interval(Duration::from_secs(60));
 
+        let snapshot_config = self.shared_state.snapshot_config.clone();
+        let snapshot_period = Duration::from_secs(
+            snapshot_config
+                .as_ref()
+                .map(|conf| conf.interval.max(1))
+                .unwrap_or(3600),
+        );
+        let mut snapshot_timer = tokio::time::interval_at(
+            tokio::time::Instant::now() + snapshot_period,
+            snapshot_period,
+        );
+
         loop {
             tracing::trace!("sync_task loop");
             select! {
@@ -433,6 +655,17 @@ impl TaskState {
                     let threshold_timestamp = crate::utils::now() - self.event_expiry;
                     let mut log = self.shared_state.log.write().unwrap();
                     log.prune_events_before(threshold_timestamp);
+                    drop(log);
+
+                    if let Some(wal) = &self.wal {
+                        wal.prune_events_before(threshold_timestamp);
+                    }
+                },
+                _ = snapshot_timer.tick(), if snapshot_config.is_some() => {
+                    tracing::trace!("...from snapshot_timer");
+                    if let Some(conf) = &snapshot_config {
+                        self.save_snapshot(conf).await;
+                    }
                 },
                 evt = self.log_recv.recv() => {
                     tracing::trace!("...from log_recv");
@@ -440,6 +673,10 @@ impl TaskState {
                         Some(evt) => {
                             tracing::trace!("Log emitted event: {:?}", evt);
 
+                            if let Some(wal) = &self.wal {
+                                wal.append(evt.clone());
+                            }
+
                             if self.server_send.send(NetworkMessage::NewEvent(evt)).is_err()
                             {
                                 break;
@@ -497,6 +734,10 @@ impl TaskState {
                                 }
                             }
                         }
+                        Some(EventLogMessage::DiscardLocalState) =>
+                        {
+                            self.discard_local_state();
+                        }
                         None => break
                     }
                 },
@@ -520,10 +761,51 @@ impl TaskState {
         Ok(())
     }
 
+    /// Request a copy of the current network state from the server, and save it
+    /// as a local snapshot
+    async fn save_snapshot(&self, conf: &SnapshotConfig) {
+        let (send, mut recv) = channel(1);
+        if let Err(e) = self
+            .server_send
+            .send(NetworkMessage::ExportNetworkState(send))
+        {
+            tracing::error!("Error sending network request to server: {}", e);
+            return;
+        }
+
+        let Some(net) = recv.recv().await else {
+            return;
+        };
+
+        let snapshot = NetworkSnapshot::new(net);
+        let path = conf.path.clone();
+
+        match tokio::task::spawn_blocking(move || snapshot.save_file(path)).await {
+            Ok(Ok(())) => tracing::debug!("Saved network snapshot"),
+            Ok(Err(e)) => tracing::error!("Error saving network snapshot: {}", e),
+            Err(e) => tracing::error!("Network snapshot task failed: {}", e),
+        }
+    }
+
+    /// Remove the on-disk event log and snapshot, which describe a state we've abandoned
+    fn discard_local_state(&self) {
+        if let Some(wal) = &self.wal {
+            wal.clear();
+        }
+
+        if let Some(conf) = &self.shared_state.snapshot_config {
+            match std::fs::remove_file(&conf.path) {
+                Ok(()) => (),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => tracing::error!("Error removing network snapshot: {}", e),
+            }
+        }
+    }
+
     /// Make a [`Message`] originating from this server, for submission to the network
     fn message(&self, content: MessageDetail) -> Message {
         Message {
-            source_server: self.shared_state.server,
+            source_server: self.shared_state.server(),
             content,
         }
     }
@@ -0,0 +1,168 @@
+//! Local snapshots of network state, used to speed up resynchronisation
+//! after a restart
+
+use crate::prelude::*;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use std::{fs::File, io::BufReader, io::BufWriter, io::Write, path::Path};
+
+/// Errors that can occur while saving or loading a snapshot
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Serialisation error: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// A copy of the network state at a given point in time, tagged with the
+/// event clock it reflects.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetworkSnapshot {
+    /// The event clock at which the snapshot was taken
+    pub clock: EventClock,
+    /// The time at which the snapshot was taken
+    pub timestamp: i64,
+    /// The network state
+    pub network: Box<Network>,
+}
+
+impl NetworkSnapshot {
+    /// Create a snapshot of the given network state
+    pub fn new(network: Box<Network>) -> Self {
+        Self {
+            clock: network.clock().clone(),
+            timestamp: crate::utils::now(),
+            network,
+        }
+    }
+
+    /// Load a snapshot from the given file path
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Save this snapshot to the given file path.
+    ///
+    /// The snapshot is written to a temporary file alongside the target and
+    /// then renamed into place, so that an interrupted write never replaces
+    /// a previously good snapshot.
+    pub fn save_file(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+
+        {
+            let file = File::create(&temp_path)?;
+            let mut writer = BufWriter::new(file);
+            serde_json::to_writer(&mut writer, self)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+
+        std::fs::rename(temp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Local state from which a restarting server can try to resume: a snapshot,
+/// plus any events recorded in the local event log since it was taken.
+pub(crate) struct ResumePoint {
+    pub snapshot: NetworkSnapshot,
+    /// Local events already reflected in the snapshot
+    pub applied_events: Vec<Event>,
+    /// Local events newer than the snapshot, which need to be replayed on top of it
+    pub local_events: Vec<Event>,
+    /// The snapshot's clock plus the local events, used to check that events
+    /// received from a peer are enough to bring the state up to date
+    scratch_log: EventLog,
+}
+
+impl ResumePoint {
+    /// Load the snapshot at `snapshot_path`, and any later events from the event log
+    /// at `wal_path`.
+    ///
+    /// Returns `None` if there is no usable snapshot, or if our latest local state is
+    /// older than `event_expiry`, in which case the rest of the network will have
+    /// pruned some of the events we'd need to catch up.
+    pub fn load(
+        snapshot_path: &Path,
+        wal_path: Option<&Path>,
+        server: (ServerId, EpochId),
+        event_expiry: i64,
+        now: i64,
+    ) -> Option<Self> {
+        let snapshot = match NetworkSnapshot::load_file(snapshot_path) {
+            Ok(snapshot) => snapshot,
+            Err(SnapshotError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::debug!("No network snapshot found");
+                return None;
+            }
+            Err(e) => {
+                tracing::warn!("Couldn't load network snapshot: {}", e);
+                return None;
+            }
+        };
+
+        let events = match wal_path {
+            Some(path) => EventWal::read_events(path).unwrap_or_else(|e| {
+                tracing::warn!("Couldn't read local event log: {}", e);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        let (applied_events, local_events): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .partition(|e| snapshot.clock.contains(e.id));
+
+        let latest_timestamp = local_events
+            .iter()
+            .map(|e| e.timestamp)
+            .fold(snapshot.timestamp, i64::max);
+        if latest_timestamp < now - event_expiry {
+            tracing::info!("Network snapshot is too old to resume from");
+            return None;
+        }
+
+        let mut scratch_log = EventLog::new(EventIdGenerator::new(server.0, server.1, 0), None);
+        scratch_log.set_clock(snapshot.clock.clone());
+        for event in local_events.iter() {
+            scratch_log.add(event.clone());
+        }
+
+        Some(Self {
+            snapshot,
+            applied_events,
+            local_events,
+            scratch_log,
+        })
+    }
+
+    /// The event clock reflecting the snapshot and the local events
+    pub fn clock(&self) -> &EventClock {
+        self.scratch_log.clock()
+    }
+
+    /// Add the events received from a peer, and check whether every event's
+    /// dependencies are now satisfied. If the peer has already pruned some of the
+    /// events we need, this returns false.
+    pub fn catch_up(&mut self, events: &[Event]) -> bool {
+        for event in events {
+            self.scratch_log.add(event.clone());
+        }
+        self.scratch_log.get_stats().pending_events == 0
+    }
+    /// Commit to this resume point: set up `log` with the snapshot's clock and the
+    /// local events, followed by any `newer_events` received from a peer, and return
+    /// the snapshot's network state. The events are emitted by `log` for processing.
+    pub fn restore_into(self, log: &mut EventLog, newer_events: Vec<Event>) -> Box<Network> {
+        log.set_clock(self.snapshot.clock);
+        log.restore_history(self.applied_events);
+        for event in self.local_events.into_iter().chain(newer_events) {
+            log.add(event);
+        }
+        self.snapshot.network
+    }
+}
@@ -1,3 +1,4 @@
+use super::snapshot::ResumePoint;
 use super::*;
 use crate::id::*;
 use crate::network::event::*;
@@ -160,3 +161,360 @@ fn get_since() {
     assert_eq!(entries[0].id, e2.id);
     assert_eq!(entries[1].id, e3.id);
 }
+
+#[test]
+fn snapshot_round_trip() {
+    let net = crate::network::Network::new(crate::network::config::NetworkConfig::new());
+    let snapshot = NetworkSnapshot::new(Box::new(net));
+
+    let path = std::env::temp_dir().join(format!("sable-snapshot-test-{}", std::process::id()));
+    snapshot.save_file(&path).unwrap();
+    let loaded = NetworkSnapshot::load_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.timestamp, snapshot.timestamp);
+    assert_eq!(&loaded.clock, loaded.network.clock());
+}
+
+#[test]
+fn wal_round_trip_and_prune() {
+    let server_id = ServerId::new(1);
+    let epoch_id = EpochId::new(1);
+    let idgen = EventIdGenerator::new(server_id, epoch_id, 1);
+    let mut log = EventLog::new(idgen, None);
+
+    let uid = UserId::new(server_id, epoch_id, 1);
+
+    let mut e1 = log.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+    e1.timestamp = 100;
+    log.add(e1.clone());
+
+    let mut e2 = log.create(
+        uid,
+        details::UserQuit {
+            message: "bbb".to_string(),
+        },
+    );
+    e2.timestamp = 200;
+    log.add(e2.clone());
+
+    let path = std::env::temp_dir().join(format!("sable-wal-test-{}", std::process::id()));
+    let mut wal = EventWal::open(WalConfig {
+        path: path.clone(),
+        fsync: FsyncPolicy::Always,
+    })
+    .unwrap();
+
+    wal.append(&e1).unwrap();
+    wal.append(&e2).unwrap();
+    // Duplicates should only be read back once
+    wal.append(&e2).unwrap();
+
+    let entries = EventWal::read_events(&path).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].id, e1.id);
+    assert_eq!(entries[1].id, e2.id);
+
+    wal.prune_events_before(150).unwrap();
+
+    let entries = EventWal::read_events(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id, e2.id);
+}
+
+#[test]
+fn dependency_graph_and_pending() {
+    let server_id = ServerId::new(1);
+    let epoch_id = EpochId::new(1);
+    let idgen = EventIdGenerator::new(server_id, epoch_id, 1);
+    let mut log = EventLog::new(idgen, None);
+
+    let uid = UserId::new(server_id, epoch_id, 1);
+
+    let e1 = log.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+    let mut e2 = log.create(
+        uid,
+        details::UserQuit {
+            message: "bbb".to_string(),
+        },
+    );
+    e2.clock.update_with_id(e1.id);
+
+    // e2 depends on e1, which hasn't arrived yet
+    log.add(e2.clone());
+
+    let pending = log.pending_report();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, e2.id);
+    assert_eq!(pending[0].missing, vec![e1.id]);
+
+    let graph = log.dependency_graph();
+    assert_eq!(graph.nodes.len(), 1);
+    assert!(graph.nodes[0].pending);
+    assert_eq!(graph.missing, vec![e1.id]);
+
+    log.add(e1.clone());
+
+    assert!(log.pending_report().is_empty());
+
+    let graph = log.dependency_graph();
+    assert_eq!(graph.nodes.len(), 2);
+    assert_eq!(graph.roots, vec![e1.id]);
+    assert!(graph.missing.is_empty());
+    assert_eq!(graph.nodes[1].dependencies, vec![e1.id]);
+}
+
+#[test]
+fn reset_for_new_epoch() {
+    let server_id = ServerId::new(1);
+    let old_epoch = EpochId::new(1);
+    let (sender, mut receiver) = unbounded_channel::<Event>();
+    let mut log = EventLog::new(EventIdGenerator::new(server_id, old_epoch, 1), Some(sender));
+
+    let uid = UserId::new(server_id, old_epoch, 1);
+
+    let e1 = log.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+    log.add(e1.clone());
+    drain_from(&mut receiver);
+
+    let new_epoch = old_epoch.next();
+    log.reset(EventIdGenerator::new(server_id, new_epoch, 1));
+
+    assert!(log.get(&e1.id).is_none());
+    assert!(!log.clock().contains(e1.id));
+
+    let e2 = log.create(
+        uid,
+        details::UserQuit {
+            message: "bbb".to_string(),
+        },
+    );
+    assert_eq!(e2.id.epoch(), new_epoch);
+    assert!(e2.clock.get(server_id).is_none());
+}
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("sable-{}-test-{}", name, std::process::id()))
+}
+
+fn save_empty_snapshot(path: &std::path::Path) -> NetworkSnapshot {
+    let net = crate::network::Network::new(crate::network::config::NetworkConfig::new());
+    let snapshot = NetworkSnapshot::new(Box::new(net));
+    snapshot.save_file(path).unwrap();
+    snapshot
+}
+
+#[test]
+fn resume_from_snapshot_and_local_events() {
+    let server = (ServerId::new(1), EpochId::new(1));
+    let log = EventLog::new(EventIdGenerator::new(server.0, server.1, 1), None);
+    let uid = UserId::new(server.0, server.1, 1);
+
+    let snapshot_path = temp_path("resume-snapshot");
+    let wal_path = temp_path("resume-wal");
+    let snapshot = save_empty_snapshot(&snapshot_path);
+
+    let e1 = log.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+    let mut wal = EventWal::open(WalConfig {
+        path: wal_path.clone(),
+        fsync: FsyncPolicy::Always,
+    })
+    .unwrap();
+    wal.append(&e1).unwrap();
+
+    let resume_point = ResumePoint::load(
+        &snapshot_path,
+        Some(&wal_path),
+        server,
+        300,
+        snapshot.timestamp + 10,
+    );
+    std::fs::remove_file(&snapshot_path).unwrap();
+    std::fs::remove_file(&wal_path).unwrap();
+
+    let mut resume_point = resume_point.expect("snapshot should be usable");
+    assert!(resume_point.applied_events.is_empty());
+    assert_eq!(resume_point.local_events.len(), 1);
+    assert!(resume_point.clock().contains(e1.id));
+
+    // A peer with nothing newer to send is enough to catch up
+    assert!(resume_point.catch_up(&[]));
+}
+
+#[test]
+fn resume_from_local_state_without_peers() {
+    let server = (ServerId::new(1), EpochId::new(1));
+    let old_log = EventLog::new(EventIdGenerator::new(server.0, server.1, 1), None);
+    let uid = UserId::new(server.0, server.1, 1);
+
+    let snapshot_path = temp_path("local-resume-snapshot");
+    let wal_path = temp_path("local-resume-wal");
+    let snapshot = save_empty_snapshot(&snapshot_path);
+
+    let e1 = old_log.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+    let wal = WalWriter::open(WalConfig {
+        path: wal_path.clone(),
+        fsync: FsyncPolicy::Interval(60),
+    })
+    .unwrap();
+    wal.append(e1.clone());
+    // Closing the writer syncs the event, as a crash-free shutdown would
+    wal.close();
+
+    let resume_point = ResumePoint::load(
+        &snapshot_path,
+        Some(&wal_path),
+        server,
+        300,
+        snapshot.timestamp + 10,
+    );
+    std::fs::remove_file(&snapshot_path).unwrap();
+    std::fs::remove_file(&wal_path).unwrap();
+
+    // With no peer to ask, nothing newer is added to the local events
+    let (sender, mut receiver) = unbounded_channel::<Event>();
+    let mut log = EventLog::new(EventIdGenerator::new(server.0, server.1, 1), Some(sender));
+    resume_point
+        .expect("snapshot should be usable")
+        .restore_into(&mut log, Vec::new());
+
+    assert!(log.clock().contains(e1.id));
+    let replayed = drain_from(&mut receiver);
+    assert_eq!(replayed.len(), 1);
+    assert_eq!(replayed[0].id, e1.id);
+}
+
+#[test]
+fn wal_writer_syncs_on_interval() {
+    let server_id = ServerId::new(1);
+    let epoch_id = EpochId::new(1);
+    let log = EventLog::new(EventIdGenerator::new(server_id, epoch_id, 1), None);
+    let uid = UserId::new(server_id, epoch_id, 1);
+
+    let path = temp_path("interval-wal");
+    let mut wal = EventWal::open(WalConfig {
+        path: path.clone(),
+        fsync: FsyncPolicy::Interval(60),
+    })
+    .unwrap();
+
+    assert!(wal.sync_deadline().is_none());
+    wal.append(&log.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    ))
+    .unwrap();
+    // The write is left for the writer thread to sync once the interval passes
+    assert!(wal.sync_deadline().is_some());
+    wal.sync().unwrap();
+    assert!(wal.sync_deadline().is_none());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn resume_falls_back_without_usable_snapshot() {
+    let server = (ServerId::new(1), EpochId::new(1));
+    let now = crate::utils::now();
+
+    let missing = temp_path("missing-snapshot");
+    assert!(ResumePoint::load(&missing, None, server, 300, now).is_none());
+
+    let corrupt = temp_path("corrupt-snapshot");
+    std::fs::write(&corrupt, b"{ not a snapshot").unwrap();
+    let resume_point = ResumePoint::load(&corrupt, None, server, 300, now);
+    std::fs::remove_file(&corrupt).unwrap();
+    assert!(resume_point.is_none());
+
+    let old = temp_path("old-snapshot");
+    let snapshot = save_empty_snapshot(&old);
+    let resume_point = ResumePoint::load(&old, None, server, 300, snapshot.timestamp + 301);
+    std::fs::remove_file(&old).unwrap();
+    assert!(resume_point.is_none());
+}
+
+#[test]
+fn resume_falls_back_when_peer_events_incomplete() {
+    let server = (ServerId::new(1), EpochId::new(1));
+    let other = EventLog::new(EventIdGenerator::new(ServerId::new(2), server.1, 1), None);
+    let uid = UserId::new(server.0, server.1, 1);
+
+    let snapshot_path = temp_path("incomplete-snapshot");
+    let snapshot = save_empty_snapshot(&snapshot_path);
+    let resume_point = ResumePoint::load(&snapshot_path, None, server, 300, snapshot.timestamp);
+    std::fs::remove_file(&snapshot_path).unwrap();
+    let mut resume_point = resume_point.unwrap();
+
+    let e1 = other.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+    let mut e2 = other.create(
+        uid,
+        details::UserQuit {
+            message: "bbb".to_string(),
+        },
+    );
+    e2.clock.update_with_id(e1.id);
+
+    // The peer has pruned e1, so e2 can never be applied
+    assert!(!resume_point.catch_up(&[e2.clone()]));
+    assert!(resume_point.catch_up(&[e1]));
+}
+
+#[test]
+fn wal_requires_snapshot() {
+    let wal = WalConfig {
+        path: temp_path("config-wal"),
+        fsync: FsyncPolicy::Never,
+    };
+    let snapshot = SnapshotConfig {
+        path: temp_path("config-snapshot"),
+        interval: 60,
+    };
+
+    let wal_only = EventLogConfig {
+        event_expiry: 300,
+        snapshot: None,
+        wal: Some(wal.clone()),
+    };
+    assert!(wal_only.validate().is_err());
+
+    let both = EventLogConfig {
+        event_expiry: 300,
+        snapshot: Some(snapshot),
+        wal: Some(wal),
+    };
+    assert!(both.validate().is_ok());
+}
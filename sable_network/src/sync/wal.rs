@@ -0,0 +1,273 @@
+//! An on-disk write-ahead log of events, used to recover the event log after
+//! a crash or restart
+
+use crate::prelude::*;
+
+use super::config::{FsyncPolicy, WalConfig};
+
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// An append-only file of events, one JSON-encoded event per line.
+pub struct EventWal {
+    config: WalConfig,
+    file: File,
+    last_sync: Instant,
+    dirty: bool,
+}
+
+impl EventWal {
+    /// Open (or create) the log file described by `config`
+    pub fn open(config: WalConfig) -> std::io::Result<Self> {
+        let file = Self::open_append(&config.path)?;
+
+        Ok(Self {
+            config,
+            file,
+            last_sync: Instant::now(),
+            dirty: false,
+        })
+    }
+
+    fn open_append(path: &Path) -> std::io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// Read all events stored in the log file at `path`, in the order in
+    /// which they were written.
+    ///
+    /// A truncated or corrupt final line, as may be left behind by a crash
+    /// mid-write, is ignored. Duplicate events are returned only once.
+    pub fn read_events(path: impl AsRef<Path>) -> std::io::Result<Vec<Event>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut seen = HashSet::new();
+        let mut events = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            match serde_json::from_str::<Event>(&line) {
+                Ok(event) => {
+                    if seen.insert(event.id) {
+                        events.push(event);
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Ignoring unreadable event log entry: {}", e);
+                }
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// The configuration this log was opened with
+    pub fn config(&self) -> &WalConfig {
+        &self.config
+    }
+
+    /// Append an event to the log, syncing according to the configured policy
+    pub fn append(&mut self, event: &Event) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.dirty = true;
+
+        match self.config.fsync {
+            FsyncPolicy::Always => self.sync(),
+            FsyncPolicy::Interval(secs) => {
+                if self.last_sync.elapsed() >= Duration::from_secs(secs) {
+                    self.sync()
+                } else {
+                    Ok(())
+                }
+            }
+            FsyncPolicy::Never => Ok(()),
+        }
+    }
+
+    /// When any outstanding writes next need to be synced, if the fsync policy calls
+    /// for it
+    pub(crate) fn sync_deadline(&self) -> Option<Instant> {
+        match self.config.fsync {
+            FsyncPolicy::Interval(secs) if self.dirty => {
+                Some(self.last_sync + Duration::from_secs(secs))
+            }
+            _ => None,
+        }
+    }
+
+    /// Flush any outstanding writes to stable storage
+    pub fn sync(&mut self) -> std::io::Result<()> {
+        if self.dirty {
+            self.file.sync_data()?;
+            self.dirty = false;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Discard every event in the log
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        File::create(&self.config.path)?.sync_all()?;
+        self.file = Self::open_append(&self.config.path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Remove events older than the provided timestamp.
+    ///
+    /// The retained events are written to a new file which then replaces the
+    /// existing one, so an interruption leaves the previous log intact.
+    pub fn prune_events_before(&mut self, threshold_timestamp: i64) -> std::io::Result<()> {
+        self.sync()?;
+
+        let events = Self::read_events(&self.config.path)?;
+        let temp_path = self.config.path.with_extension("tmp");
+
+        {
+            let mut writer = BufWriter::new(File::create(&temp_path)?);
+            for event in events.iter().filter(|e| e.timestamp >= threshold_timestamp) {
+                serde_json::to_writer(&mut writer, event)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+
+        std::fs::rename(&temp_path, &self.config.path)?;
+        self.file = Self::open_append(&self.config.path)?;
+        Ok(())
+    }
+}
+
+/// A request to the thread which owns an [`EventWal`]
+enum WalRequest {
+    Append(Event),
+    PruneBefore(i64),
+    Clear,
+}
+
+/// Handle to an [`EventWal`] which is written by a dedicated thread, so that
+/// writing, syncing and pruning the file don't hold up the sync task.
+///
+/// Requests are carried out in the order in which they were made.
+pub struct WalWriter {
+    config: WalConfig,
+    sender: Sender<WalRequest>,
+    thread: JoinHandle<()>,
+}
+
+impl WalWriter {
+    /// Open (or create) the log file described by `config`, and start its writer thread
+    pub fn open(config: WalConfig) -> std::io::Result<Self> {
+        let wal = EventWal::open(config.clone())?;
+        let (sender, receiver) = channel();
+        let thread = std::thread::Builder::new()
+            .name("event-log-writer".to_string())
+            .spawn(move || wal.run(receiver))?;
+
+        Ok(Self {
+            config,
+            sender,
+            thread,
+        })
+    }
+
+    /// The configuration this log was opened with
+    pub fn config(&self) -> &WalConfig {
+        &self.config
+    }
+
+    /// Append an event to the log
+    pub fn append(&self, event: Event) {
+        self.send(WalRequest::Append(event));
+    }
+
+    /// Remove events older than the provided timestamp
+    pub fn prune_events_before(&self, threshold_timestamp: i64) {
+        self.send(WalRequest::PruneBefore(threshold_timestamp));
+    }
+
+    /// Discard every event in the log
+    pub fn clear(&self) {
+        self.send(WalRequest::Clear);
+    }
+
+    fn send(&self, request: WalRequest) {
+        if self.sender.send(request).is_err() {
+            tracing::error!("Event log writer thread has stopped");
+        }
+    }
+
+    /// Wait for every outstanding request to be written and synced, and stop the
+    /// writer thread
+    pub fn close(self) -> WalConfig {
+        drop(self.sender);
+        if self.thread.join().is_err() {
+            tracing::error!("Event log writer thread panicked");
+        }
+        self.config
+    }
+}
+
+impl EventWal {
+    /// Carry out requests from `receiver` until it is closed, syncing outstanding
+    /// writes whenever the fsync interval elapses, even if nothing more is written
+    fn run(mut self, receiver: Receiver<WalRequest>) {
+        loop {
+            let request = match self.sync_deadline() {
+                Some(deadline) => {
+                    match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    {
+                        Ok(request) => request,
+                        Err(RecvTimeoutError::Timeout) => {
+                            if let Err(e) = self.sync() {
+                                tracing::error!("Error syncing event log file: {}", e);
+                            }
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match receiver.recv() {
+                    Ok(request) => request,
+                    Err(_) => break,
+                },
+            };
+
+            match request {
+                WalRequest::Append(event) => {
+                    if let Err(e) = self.append(&event) {
+                        tracing::error!("Error writing to event log file: {}", e);
+                    }
+                }
+                WalRequest::PruneBefore(threshold_timestamp) => {
+                    if let Err(e) = self.prune_events_before(threshold_timestamp) {
+                        tracing::error!("Error pruning event log file: {}", e);
+                    }
+                }
+                WalRequest::Clear => {
+                    if let Err(e) = self.clear() {
+                        tracing::error!("Error clearing event log file: {}", e);
+                    }
+                }
+            }
+        }
+
+        if let Err(e) = self.sync() {
+            tracing::error!("Error syncing event log file: {}", e);
+        }
+    }
+}
@@ -7,6 +7,7 @@ pub mod receiver;
 pub fn empty_network_config() -> NetworkConfig {
     NetworkConfig {
         opers: Vec::new(),
+        oper_classes: Vec::new(),
         debug_mode: false,
         default_roles: HashMap::new(),
         alias_users: Vec::new(),
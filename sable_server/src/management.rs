@@ -1,10 +1,20 @@
 use crate::config::*;
+use crate::{
+    ApplicationManagementCommand, ApplicationManagementRequest, ApplicationManagementResponse,
+};
 use sable_network::{
     config::TlsData,
-    rpc::{ServerManagementCommand, ServerManagementCommandType, ShutdownAction},
+    rpc::{
+        ServerManagementCommand, ServerManagementCommandType, ServerManagementError, ShutdownAction,
+    },
 };
 
-use hyper::{Body, Method, Request, Response, StatusCode};
+use hyper::{
+    body::HttpBody,
+    header::{HeaderMap, HeaderValue, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE},
+    Body, Method, Request, Response, StatusCode, Uri,
+};
+use parking_lot::RwLock;
 use sha1::{Digest, Sha1};
 use std::{
     future::Future,
@@ -27,12 +37,19 @@ use tracing::Instrument;
 
 pub enum ManagementCommand {
     ServerCommand(ServerManagementCommand),
+    ReloadTls(oneshot::Sender<Result<(), String>>),
+    ApplicationCommand(
+        ApplicationManagementCommand,
+        oneshot::Sender<ApplicationManagementResponse>,
+    ),
     Shutdown(ShutdownAction),
 }
 
 pub struct ManagementServer {
     command_receiver: Receiver<ManagementCommand>,
     server_task: task::JoinHandle<Result<(), hyper::Error>>,
+    client_ca: Vec<u8>,
+    tls_config: Arc<RwLock<Arc<rustls::ServerConfig>>>,
     //    service_data: Arc<ManagementServiceData>,
 }
 
@@ -60,6 +77,54 @@ fn internal_error() -> hyper::Result<Response<Body>> {
     Ok(response)
 }
 
+/// Largest request body accepted for requests passed through to the application
+const MAX_REQUEST_BODY: usize = 4096;
+
+fn payload_too_large() -> hyper::Result<Response<Body>> {
+    let mut response = Response::new(Body::from(
+        serde_json::json!({
+            "error": format!("Request body larger than {} bytes", MAX_REQUEST_BODY)
+        })
+        .to_string(),
+    ));
+    *response.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+    Ok(response)
+}
+
+/// Read a request body of at most [`MAX_REQUEST_BODY`] bytes, returning `None`
+/// if it's longer than that. The `Content-Length` header is checked first, but
+/// isn't relied upon, in case it's missing or wrong.
+async fn read_limited_body(headers: &HeaderMap, mut body: Body) -> hyper::Result<Option<Vec<u8>>> {
+    let declared_length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared_length.is_some_and(|len| len > MAX_REQUEST_BODY as u64) {
+        return Ok(None);
+    }
+
+    let mut ret = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if ret.len() + chunk.len() > MAX_REQUEST_BODY {
+            return Ok(None);
+        }
+        ret.extend_from_slice(&chunk);
+    }
+    Ok(Some(ret))
+}
+
+/// Decode the query string of `uri`, if any, into key/value pairs
+fn query_params(uri: &Uri) -> Vec<(String, String)> {
+    uri.query()
+        .map(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 impl ManagementService {
     async fn server_management_command(
         command_sender: Sender<ManagementCommand>,
@@ -76,11 +141,154 @@ impl ManagementService {
             .is_err()
         {
             internal_error()
-        } else if let Ok(response) = recv.await {
-            Ok(Response::new(Body::from(response)))
         } else {
-            internal_error()
+            let (status, body) = match recv.await {
+                Ok(Ok(body)) => (StatusCode::OK, body),
+                Ok(Err(ServerManagementError::NotFound(message))) => (
+                    StatusCode::NOT_FOUND,
+                    serde_json::json!({ "error": message }).to_string(),
+                ),
+                Ok(Err(ServerManagementError::BadRequest(message))) => (
+                    StatusCode::BAD_REQUEST,
+                    serde_json::json!({ "error": message }).to_string(),
+                ),
+                Err(_) => return internal_error(),
+            };
+            let mut response = Response::new(Body::from(body));
+            *response.status_mut() = status;
+            Ok(response)
+        }
+    }
+
+    /// Collect the network node's statistics and any reported by the application
+    /// into a single object
+    async fn statistics_command(
+        command_sender: Sender<ManagementCommand>,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let (send, recv) = oneshot::channel();
+        let cmd = ServerManagementCommand {
+            cmd: ServerManagementCommandType::ServerStatistics,
+            response: send,
+        };
+        if command_sender
+            .send(ManagementCommand::ServerCommand(cmd))
+            .await
+            .is_err()
+        {
+            return internal_error();
+        }
+        let Ok(Ok(server_stats)) = recv.await else {
+            return internal_error();
+        };
+
+        let (send, recv) = oneshot::channel();
+        if command_sender
+            .send(ManagementCommand::ApplicationCommand(
+                ApplicationManagementCommand::Statistics,
+                send,
+            ))
+            .await
+            .is_err()
+        {
+            return internal_error();
+        }
+        let application_stats = match recv.await {
+            Ok(ApplicationManagementResponse::Success(json)) => Some(json),
+            Ok(ApplicationManagementResponse::NotSupported) => None,
+            _ => return internal_error(),
+        };
+
+        let mut stats: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&server_stats).expect("Invalid server statistics");
+        if let Some(json) = application_stats {
+            let application: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_str(&json).expect("Invalid application statistics");
+            stats.extend(application);
+        }
+
+        Ok(Response::new(Body::from(
+            serde_json::to_string_pretty(&stats).expect("Failed to serialise statistics"),
+        )))
+    }
+
+    async fn reload_tls_command(
+        command_sender: Sender<ManagementCommand>,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let (send, recv) = oneshot::channel();
+        if command_sender
+            .send(ManagementCommand::ReloadTls(send))
+            .await
+            .is_err()
+        {
+            return internal_error();
+        }
+
+        match recv.await {
+            Ok(Ok(())) => Ok(Response::new(Body::empty())),
+            Ok(Err(message)) => {
+                let mut response = Response::new(Body::from(message));
+                *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+                Ok(response)
+            }
+            Err(_) => internal_error(),
+        }
+    }
+
+    async fn application_command(
+        command_sender: Sender<ManagementCommand>,
+        cmd: ApplicationManagementCommand,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let (send, recv) = oneshot::channel();
+        if command_sender
+            .send(ManagementCommand::ApplicationCommand(cmd, send))
+            .await
+            .is_err()
+        {
+            return internal_error();
         }
+
+        let (status, body) = match recv.await {
+            Ok(ApplicationManagementResponse::Success(body)) => (StatusCode::OK, Body::from(body)),
+            Ok(ApplicationManagementResponse::Stream(receiver)) => {
+                return Ok(Self::event_stream(receiver))
+            }
+            Ok(ApplicationManagementResponse::BadRequest(message)) => {
+                (StatusCode::BAD_REQUEST, Body::from(message))
+            }
+            Ok(ApplicationManagementResponse::Failed(message)) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, Body::from(message))
+            }
+            Ok(ApplicationManagementResponse::NotFound) => (StatusCode::NOT_FOUND, Body::empty()),
+            Ok(ApplicationManagementResponse::NotSupported) => {
+                (StatusCode::NOT_IMPLEMENTED, Body::empty())
+            }
+            Err(_) => return internal_error(),
+        };
+
+        let mut response = Response::new(body);
+        *response.status_mut() = status;
+        Ok(response)
+    }
+
+    /// Build a `text/event-stream` response which sends each message from `receiver` as an event
+    fn event_stream(mut receiver: Receiver<String>) -> Response<Body> {
+        let (mut sender, body) = Body::channel();
+
+        task::spawn(async move {
+            while let Some(data) = receiver.recv().await {
+                let event = format!("data: {}\n\n", data.replace('\n', "\ndata: "));
+                if sender.send_data(event.into()).await.is_err() {
+                    // The client went away
+                    break;
+                }
+            }
+        });
+
+        let mut response = Response::new(body);
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        response
     }
 
     async fn shutdown_command(
@@ -111,32 +319,94 @@ impl hyper::service::Service<Request<Body>> for ManagementService {
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
         let command_sender = self.data.command_sender.clone();
+        let authorised_name = self.authorised_fingerprint.name.clone();
 
         tracing::debug!(method=?req.method(), path=?req.uri().path(), user=?self.authorised_fingerprint.name, "Got management request");
 
         Box::pin(async move {
-            match (req.method(), req.uri().path()) {
-                (&Method::GET, "/statistics") => {
+            let (parts, body) = req.into_parts();
+            let path = parts.uri.path();
+
+            match (&parts.method, path) {
+                (&Method::GET, "/statistics") => Self::statistics_command(command_sender).await,
+                (&Method::GET, "/dump-network") => {
                     Self::server_management_command(
                         command_sender,
-                        ServerManagementCommandType::ServerStatistics,
+                        ServerManagementCommandType::DumpNetwork,
                     )
                     .await
                 }
-                (&Method::GET, "/dump-network") => {
+                (&Method::GET, "/dump-events") => {
                     Self::server_management_command(
                         command_sender,
-                        ServerManagementCommandType::DumpNetwork,
+                        ServerManagementCommandType::DumpEvents,
                     )
                     .await
                 }
-                (&Method::GET, "/dump-events") => {
+                (&Method::GET, "/event-graph") => {
                     Self::server_management_command(
                         command_sender,
-                        ServerManagementCommandType::DumpEvents,
+                        ServerManagementCommandType::EventGraph,
                     )
                     .await
                 }
+                (&Method::GET, "/pending-events") => {
+                    Self::server_management_command(
+                        command_sender,
+                        ServerManagementCommandType::PendingEvents,
+                    )
+                    .await
+                }
+                (&Method::GET, "/divergence") => {
+                    Self::server_management_command(
+                        command_sender,
+                        ServerManagementCommandType::CheckDivergence,
+                    )
+                    .await
+                }
+                (&Method::GET, "/audit-log") => {
+                    Self::application_command(
+                        command_sender,
+                        ApplicationManagementCommand::QueryAuditLog(query_params(&parts.uri)),
+                    )
+                    .await
+                }
+                (&Method::GET, "/audit-log/stream") => {
+                    Self::application_command(
+                        command_sender,
+                        ApplicationManagementCommand::StreamAuditLog(query_params(&parts.uri)),
+                    )
+                    .await
+                }
+                (&Method::POST, "/reload-tls") => Self::reload_tls_command(command_sender).await,
+                (&Method::POST, _) if path.starts_with("/servers/") && path.ends_with("/quit") => {
+                    let server_name = path
+                        .strip_prefix("/servers/")
+                        .and_then(|p| p.strip_suffix("/quit"))
+                        .unwrap_or_default();
+                    match server_name.parse() {
+                        Ok(server) => {
+                            Self::server_management_command(
+                                command_sender,
+                                ServerManagementCommandType::RemoveServer {
+                                    server,
+                                    requested_by: authorised_name,
+                                },
+                            )
+                            .await
+                        }
+                        Err(_) => {
+                            let mut response = Response::new(Body::from(
+                                serde_json::json!({
+                                    "error": format!("Invalid server name {}", server_name)
+                                })
+                                .to_string(),
+                            ));
+                            *response.status_mut() = StatusCode::BAD_REQUEST;
+                            Ok(response)
+                        }
+                    }
+                }
                 (&Method::POST, "/shutdown") => {
                     Self::shutdown_command(command_sender, ShutdownAction::Shutdown).await
                 }
@@ -147,8 +417,24 @@ impl hyper::service::Service<Request<Body>> for ManagementService {
                     Self::shutdown_command(command_sender, ShutdownAction::Upgrade).await
                 }
                 _ => {
-                    let mut response = Response::default();
-                    *response.status_mut() = StatusCode::NOT_FOUND;
+                    let Some(body) = read_limited_body(&parts.headers, body).await? else {
+                        return payload_too_large();
+                    };
+                    let request = ApplicationManagementRequest {
+                        method: parts.method.to_string(),
+                        path: path.to_string(),
+                        body,
+                    };
+                    let mut response = Self::application_command(
+                        command_sender,
+                        ApplicationManagementCommand::Request(request),
+                    )
+                    .await?;
+                    // Paths that the server type doesn't recognise don't exist, rather than
+                    // being unimplemented
+                    if response.status() == StatusCode::NOT_IMPLEMENTED {
+                        *response.status_mut() = StatusCode::NOT_FOUND;
+                    }
                     Ok(response)
                 }
             }
@@ -157,27 +443,45 @@ impl hyper::service::Service<Request<Body>> for ManagementService {
 }
 
 impl ManagementServer {
-    fn server_config(data: TlsData, client_ca: Vec<u8>) -> Arc<rustls::ServerConfig> {
+    fn server_config(
+        data: &TlsData,
+        client_ca: &[u8],
+    ) -> Result<Arc<rustls::ServerConfig>, rustls::Error> {
         let mut root_store = rustls::RootCertStore::empty();
         root_store
-            .add(&rustls::Certificate(client_ca))
+            .add(&rustls::Certificate(client_ca.to_vec()))
             .expect("Error adding certificate to store");
 
-        Arc::new(
+        Ok(Arc::new(
             rustls::ServerConfig::builder()
                 .with_safe_defaults()
                 .with_client_cert_verifier(rustls::server::AllowAnyAuthenticatedClient::new(
-                    root_store.clone(),
+                    root_store,
                 ))
                 .with_single_cert(
                     data.cert_chain
-                        .into_iter()
+                        .iter()
+                        .cloned()
                         .map(rustls::Certificate)
                         .collect(),
-                    rustls::PrivateKey(data.key),
-                )
-                .expect("Bad TLS server config"),
-        )
+                    rustls::PrivateKey(data.key.clone()),
+                )?,
+        ))
+    }
+
+    /// Build the configuration for management connections using the given
+    /// certificate and key, without applying it.
+    pub fn prepare_tls(
+        &self,
+        tls_data: &TlsData,
+    ) -> Result<Arc<rustls::ServerConfig>, rustls::Error> {
+        Self::server_config(tls_data, &self.client_ca)
+    }
+
+    /// Use a configuration built by [`prepare_tls`](Self::prepare_tls) for new
+    /// management connections. Existing connections are unaffected.
+    pub fn apply_tls(&self, config: Arc<rustls::ServerConfig>) {
+        *self.tls_config.write() = config;
     }
 
     async fn handle_connection(
@@ -228,11 +532,14 @@ impl ManagementServer {
             authorised_fingerprints: config.authorised_fingerprints,
         });
 
+        let tls_config = Arc::new(RwLock::new(
+            Self::server_config(&tls_data, &client_ca).expect("Bad TLS server config"),
+        ));
+
         let data = Arc::clone(&service_data);
+        let current_tls_config = Arc::clone(&tls_config);
         let listen_address = config.address;
         let server_task = task::spawn(async move {
-            let tls_config = Self::server_config(tls_data, client_ca);
-            let acceptor = Arc::new(TlsAcceptor::from(Arc::clone(&tls_config)));
             let listener = TcpListener::bind(&listen_address).await.expect("Failed to bind to management address");
 
             loop
@@ -243,10 +550,16 @@ impl ManagementServer {
                     {
                         if let Ok((conn, _)) = res
                         {
-                            if let Err(e) = Self::handle_connection(conn, Arc::clone(&acceptor), Arc::clone(&data)).await
-                            {
-                                tracing::warn!("Error handling management connection: {}", e);
-                            }
+                            // Streaming responses hold their connection open indefinitely,
+                            // so each connection needs its own task
+                            let acceptor = Arc::new(TlsAcceptor::from(Arc::clone(&current_tls_config.read())));
+                            let data = Arc::clone(&data);
+                            task::spawn(async move {
+                                if let Err(e) = Self::handle_connection(conn, acceptor, data).await
+                                {
+                                    tracing::warn!("Error handling management connection: {}", e);
+                                }
+                            }.in_current_span());
                         }
                     }
                     _ = &mut shutdown =>
@@ -261,6 +574,8 @@ impl ManagementServer {
         Self {
             command_receiver,
             server_task,
+            client_ca,
+            tls_config,
             //            service_data,
         }
     }
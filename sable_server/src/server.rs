@@ -1,7 +1,7 @@
 use crate::{config::*, *};
 
 use anyhow::Context;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use sable_network::{
     config::*,
     network::config::NetworkConfig,
@@ -11,10 +11,14 @@ use sable_network::{
     rpc::{RemoteServerRequest, ShutdownAction},
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use tokio::sync::{
-    broadcast,
-    mpsc::{unbounded_channel, UnboundedReceiver},
-    oneshot,
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::{
+        broadcast,
+        mpsc::{unbounded_channel, UnboundedReceiver},
+        oneshot,
+    },
+    task::JoinSet,
 };
 
 use std::{fs::File, io::Read, path::Path, sync::Arc};
@@ -49,7 +53,9 @@ where
         let mut file = File::open(filename)?;
         let mut config = String::new();
         file.read_to_string(&mut config)?;
-        Ok(json5::from_str(&config)?)
+        let config: Self = json5::from_str(&config)?;
+        config.event_log.validate()?;
+        Ok(config)
     }
 }
 
@@ -63,7 +69,8 @@ pub struct Server<ST> {
     log: Arc<ReplicatedEventLog>,
     server: Arc<ST>,
     management_config: ManagementConfig,
-    tls_data: TlsData,
+    tls_config: TlsConfig,
+    tls_data: RwLock<TlsData>,
     remote_command_recv: Mutex<Option<UnboundedReceiver<RemoteServerRequest>>>,
 }
 
@@ -137,10 +144,12 @@ where
             log,
             server,
             management_config: conf.management,
-            tls_data: conf
-                .tls_config
-                .load_from_disk()
-                .expect("Couldn't load TLS files"),
+            tls_data: RwLock::new(
+                conf.tls_config
+                    .load_from_disk()
+                    .expect("Couldn't load TLS files"),
+            ),
+            tls_config: conf.tls_config,
             remote_command_recv: Mutex::new(Some(remote_recv)),
         })
     }
@@ -255,18 +264,52 @@ where
 
         let mut server = management::ManagementServer::start(
             self.management_config.clone(),
-            self.tls_data.clone(),
+            self.tls_data.read().clone(),
             server_shutdown_recv,
         );
 
+        let mut hangup = signal(SignalKind::hangup()).expect("Couldn't install SIGHUP handler");
+        // Commands which may wait on the network or the application run in their own tasks,
+        // so as not to hold up other management requests
+        let mut command_tasks = JoinSet::new();
+
         let shutdown_action = loop {
-            if let Some(cmd) = server.recv().await {
+            let cmd = tokio::select! {
+                cmd = server.recv() => cmd,
+                Some(_) = command_tasks.join_next() => continue,
+                _ = hangup.recv() => {
+                    tracing::info!("Received SIGHUP; reloading TLS configuration");
+                    if let Err(e) = self.reload_tls(&server).await {
+                        tracing::error!("Couldn't reload TLS configuration: {:#}", e);
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(cmd) = cmd {
                 tracing::debug!("Received from management server");
                 match cmd {
                     management::ManagementCommand::ServerCommand(scmd) => {
                         let command = &scmd.cmd;
                         tracing::debug!(?command, "Management server command");
-                        self.node.handle_management_command(scmd).await;
+                        let node = Arc::clone(&self.node);
+                        command_tasks.spawn(async move {
+                            node.handle_management_command(scmd).await;
+                        });
+                    }
+                    management::ManagementCommand::ReloadTls(response) => {
+                        let result = self.reload_tls(&server).await.map_err(|e| {
+                            tracing::error!("Couldn't reload TLS configuration: {:#}", e);
+                            format!("{:#}", e)
+                        });
+                        let _ = response.send(result);
+                    }
+                    management::ManagementCommand::ApplicationCommand(command, response) => {
+                        tracing::debug!(?command, "Application management command");
+                        let server = Arc::clone(&self.server);
+                        command_tasks.spawn(async move {
+                            let _ = response.send(server.handle_management_command(command).await);
+                        });
                     }
                     management::ManagementCommand::Shutdown(action) => {
                         break action;
@@ -278,6 +321,10 @@ where
             }
         };
 
+        // These hold references to the node and server, which must be released before
+        // they can be saved
+        command_tasks.shutdown().await;
+
         server_shutdown_send
             .send(())
             .expect("Couldn't signal management service to shut down");
@@ -286,6 +333,43 @@ where
         shutdown_action
     }
 
+    /// Re-read the TLS certificate and key files, and apply them to new client,
+    /// gossip network and management connections. Every configuration is built
+    /// before any is applied, so if anything fails to load or validate, the existing
+    /// certificates remain in use everywhere.
+    async fn reload_tls(&self, management: &management::ManagementServer) -> anyhow::Result<()> {
+        let tls_data = self
+            .tls_config
+            .load_from_disk()
+            .context("Could not load TLS files")?;
+        tls_data.validate()?;
+
+        let management_config = management
+            .prepare_tls(&tls_data)
+            .context("Could not build management TLS configuration")?;
+        let gossip_config = self
+            .log
+            .prepare_tls()
+            .context("Could not build gossip network TLS configuration")?;
+        self.server
+            .validate_tls(&tls_data)
+            .context("Could not build server TLS configuration")?;
+
+        // This is the only step which can still fail, as the listener process may
+        // reject the certificates, so apply it first and leave everything else as it
+        // was if it does
+        self.server
+            .reload_tls(&tls_data)
+            .await
+            .context("Could not reload server TLS configuration")?;
+        management.apply_tls(management_config);
+        self.log.apply_tls(gossip_config);
+        *self.tls_data.write() = tls_data;
+
+        tracing::info!("Reloaded TLS configuration");
+        Ok(())
+    }
+
     /// Save the state of the server, including all its component parts, for resumption after a code upgrade.
     pub async fn save(self) -> Result<ServerState<ST>, ServerSaveError> {
         // Order matters here.
@@ -341,8 +425,14 @@ where
             Some(remote_send),
         )?);
 
+        let tls_data = server_config
+            .tls_config
+            .load_from_disk()
+            .expect("Couldn't load TLS data files");
+
         let server = Arc::new(ST::restore(
             state.server_state,
+            &tls_data,
             Arc::clone(&node),
             history_recv,
             &processed_server_config,
@@ -353,10 +443,8 @@ where
             log,
             server,
             management_config: server_config.management,
-            tls_data: server_config
-                .tls_config
-                .load_from_disk()
-                .expect("Couldn't load TLS data files"),
+            tls_data: RwLock::new(tls_data),
+            tls_config: server_config.tls_config,
             remote_command_recv: Mutex::new(Some(remote_recv)),
         })
     }
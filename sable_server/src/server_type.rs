@@ -4,7 +4,10 @@ use async_trait::async_trait;
 use serde::{de::DeserializeOwned, Serialize};
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::sync::{broadcast, mpsc::UnboundedReceiver};
+use tokio::sync::{
+    broadcast,
+    mpsc::{self, UnboundedReceiver},
+};
 
 #[derive(Debug, Error)]
 pub enum ServerSaveError {
@@ -14,6 +17,51 @@ pub enum ServerSaveError {
     EventLogSaveError(sable_network::sync::EventLogSaveError),
 }
 
+/// A management request which is handled by the application logic, rather than
+/// by the network layer
+#[derive(Debug)]
+pub enum ApplicationManagementCommand {
+    /// A request for a path not recognised by the management service, for the
+    /// server type to interpret
+    Request(ApplicationManagementRequest),
+    /// Collect application-specific statistics, as a JSON object whose fields are
+    /// added to those reported by the network node
+    Statistics,
+    /// Search the audit log, filtered by the given query parameters
+    QueryAuditLog(Vec<(String, String)>),
+    /// Follow new audit log entries matching the given query parameters
+    StreamAuditLog(Vec<(String, String)>),
+}
+
+/// An HTTP request to the management service which is specific to a server type
+#[derive(Debug)]
+pub struct ApplicationManagementRequest {
+    /// The request method, such as `GET` or `POST`
+    pub method: String,
+    /// The path component of the request URI
+    pub path: String,
+    /// The request body
+    pub body: Vec<u8>,
+}
+
+/// The result of an [`ApplicationManagementCommand`]
+#[derive(Debug)]
+pub enum ApplicationManagementResponse {
+    /// The command succeeded, with the given response body
+    Success(String),
+    /// The command succeeded, and each message received from the channel should be sent
+    /// to the client as a server-sent event. The stream ends when the sender is dropped.
+    Stream(mpsc::Receiver<String>),
+    /// The request couldn't be understood; the string describes why
+    BadRequest(String),
+    /// The request was valid but couldn't be carried out; the string describes why
+    Failed(String),
+    /// The object the command referred to doesn't exist
+    NotFound,
+    /// This server type doesn't support the command
+    NotSupported,
+}
+
 /// Trait to be implemented by providers of server application logic.
 ///
 /// An implementor of this trait can be constructed and used by [`run_server`](crate::run::run_server).
@@ -57,11 +105,33 @@ pub trait ServerType: Send + Sync + Sized + 'static {
     /// Restore from saved state
     fn restore(
         state: Self::Saved,
+        tls_data: &TlsData,
         node: Arc<NetworkNode>,
         history_receiver: UnboundedReceiver<NetworkHistoryUpdate>,
         config: &Self::ProcessedConfig,
     ) -> std::io::Result<Self>;
 
+    /// Check that reloaded TLS certificates can be used by any client-facing
+    /// listeners, without applying them.
+    fn validate_tls(&self, _tls_data: &TlsData) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Apply reloaded TLS certificates to any client-facing listeners, returning once
+    /// they are in use. The certificates have already been passed to
+    /// [`validate_tls`](Self::validate_tls).
+    async fn reload_tls(&self, _tls_data: &TlsData) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Handle a management command directed at the application
+    async fn handle_management_command(
+        &self,
+        _command: ApplicationManagementCommand,
+    ) -> ApplicationManagementResponse {
+        ApplicationManagementResponse::NotSupported
+    }
+
     /// Handle a request originating from a remote server
     fn handle_remote_command(&self, request: RemoteServerRequestType) -> RemoteServerResponse;
 }
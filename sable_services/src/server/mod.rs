@@ -145,6 +145,7 @@ where
 
     fn restore(
         _state: Self::Saved,
+        _tls_data: &TlsData,
         _node: Arc<NetworkNode>,
         _history_receiver: UnboundedReceiver<sable_network::rpc::NetworkHistoryUpdate>,
         _config: &Self::ProcessedConfig,
@@ -222,6 +223,7 @@ where
                 tracing::warn!(?req, "Got unsupported request");
                 Ok(RemoteServerResponse::NotSupported)
             }
+            StateHash => unreachable!("State hash requests are answered by the network node"),
         };
 
         match result {